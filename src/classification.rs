@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use crate::models::TrainCar;
+use crate::timetable::Rider;
+
+// Hub-and-spoke freight. A station marked as a classification hub doesn't let freight trains run straight through it:
+// every to-order or consolidated train that comes in is broken up, and each consignment's cars go onto the classification
+// tracks, filed under the next station on their way. Once enough cars are waiting for one next hop, or the oldest of them
+// has waited long enough, the hub makes them up into outbound trains (a consolidated train for each line of route) and
+// sends them on. Passenger trains and timetabled services keep to their timetables and aren't touched.
+//
+// Every car that passes through a hub gets a dwell record in the ledger, and the report at the end of the run adds them up.
+
+const RESET: &str = "\x1b[0m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+
+pub const DEFAULT_HUB_DEPARTURE_CARS: usize = 6;   // Cars waiting for one next hop that make it worth running a train
+pub const DEFAULT_HUB_MAX_DWELL_HOURS: f64 = 4.0;  // Nothing waits longer than this for company, if there's an engine to take it
+
+
+/// One consignment's cars, sitting on the classification tracks until a train goes its way.
+pub struct Cut {
+    pub rider: Rider,
+    pub cars: Vec<TrainCar>,
+    pub arrived_at: f64,
+}
+
+/// The ledger's record of one car's stay at a hub.
+#[derive(Debug, Clone)]
+pub struct DwellRecord {
+    pub hub: u32,
+    pub car_id: u32,
+    pub next_hop: u32,
+    pub arrived_at: f64,
+    pub departed_at: f64,
+}
+
+impl DwellRecord {
+    pub fn hours(&self) -> f64 {
+        self.departed_at - self.arrived_at
+    }
+}
+
+
+/// The end-of-run classification report, one line per hub.
+pub fn print_dwell_report(dwell: &[DwellRecord]) {
+    if dwell.is_empty() {
+        return;
+    }
+    let mut hubs: BTreeMap<u32, Vec<&DwellRecord>> = BTreeMap::new();
+    for record in dwell {
+        hubs.entry(record.hub).or_default().push(record);
+    }
+
+    println!("\n{BOLD}{CYAN}--- Classification Hubs: {} car(s) sorted ---{RESET}", dwell.len());
+    for (hub, records) in &hubs {
+        let hours: Vec<f64> = records.iter().map(|record| record.hours()).collect();
+        let average = hours.iter().sum::<f64>() / hours.len() as f64;
+        let Some(longest) = records.iter().max_by(|a, b| a.hours().total_cmp(&b.hours())) else {
+            continue;
+        };
+        let mut next_hops: Vec<u32> = records.iter().map(|record| record.next_hop).collect();
+        next_hops.sort();
+        next_hops.dedup();
+        println!("  {GREEN}Station {}: {} car(s) out towards {:?} | {:.1}h dwell on average, {:.1}h at worst (Car {}){RESET}", hub, records.len(), next_hops, average, longest.hours(), longest.car_id);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::models::{CarClass, CargoCategory, EngineClass, EngineType, Priority};
+use crate::passengers::PassengerService;
+use crate::timetable::ScheduledService;
+use crate::selection::{EnginePolicyKind, RebalancePolicy};
+use crate::signalling::{DeadlockPolicy, TrackWorking};
+
+// Everything that lives in a JSON file on disk: the network map (sodor.json), the starting inventories (seed.json)
+// and the locomotive classes (engines.json).
+// No logic here beyond reading and writing. The stations never see these structs, only the commands built from them.
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Config {
+    pub stations: Vec<StationConfig>,
+    pub tracks: Vec<TrackConfig>,
+    // Only set for maps imported from GeoJSON: the lat/long that sits at (0, 0) on our planar map, so we can export back out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<GeoReference>,
+    // Timetabled freight services. Each runs on its own schedule, whether or not anyone has ordered a train.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<ScheduledService>,
+    // What the signal box does when two trains are each waiting on the other: "back_off" (the default) or "reroute".
+    #[serde(default)]
+    pub deadlock_policy: DeadlockPolicy,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct GeoReference {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct StationConfig {
+    pub id: u32,
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    // Metres above sea level. Leave it out and the station sits at sea level, like the rest of flat old Sodor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<f64>,
+    // Stations with a maintenance shed service engines that are due, taking them out of the roundhouse for service_hours.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub maintenance_shed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_hours: Option<f64>,
+    // How the roundhouse picks between engines that could all do the job. Defaults to the weakest capable one.
+    #[serde(default)]
+    pub engine_policy: EnginePolicyKind,
+    // What to do with engines from other depots once we're done with them, and (for "target_mix") how many of each class to keep.
+    #[serde(default)]
+    pub rebalance: RebalancePolicy,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub target_mix: HashMap<EngineType, usize>,
+    // What the station can spend on new empty cars when the neighbours can't spare any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub car_budget: Option<u32>,
+    // Passenger trains that start here, e.g. { "terminus": 1, "every_hours": 4.0, "coaches": 2 }.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passenger_services: Vec<PassengerService>,
+    // A passing loop lets a through train wait off the main line. Without one (false), a train held here stays in the block it came in on.
+    #[serde(default = "has_passing_loop", skip_serializing_if = "Clone::clone")]
+    pub passing_loop: bool,
+    // How many cars the yard tracks hold, engines the roundhouse stalls hold and consignments the warehouse holds.
+    // Leave them out for the defaults (100, 12 and 100). A full station holds arriving trains outside until there's room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yard_capacity: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roundhouse_capacity: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warehouse_capacity: Option<usize>,
+    // A classification hub breaks up every freight train that comes in and re-sorts the cars by where they go next.
+    // An outbound train leaves once hub_departure_cars are waiting for the same next hop, or the first of them has
+    // waited hub_max_dwell_hours. Leave them out for the defaults (6 cars, 4 hours).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub classification_hub: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hub_departure_cars: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hub_max_dwell_hours: Option<f64>,
+}
+
+fn has_passing_loop() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TrackConfig {
+    pub origin: u32,
+    pub destination: u32,
+    // Real track wiggles. If we know the length along the rails, use it instead of the straight line between the stations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<f64>,
+    // The bends in between, in the same planar x/y as the stations. Only used to draw the track again on export.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waypoints: Vec<[f64; 2]>,
+    // "single" (one line, worked both ways) or "double" (the default, a line each way), and how many trains the block
+    // takes at once: in total on single track, in each direction on double. Defaults to one.
+    #[serde(default, skip_serializing_if = "TrackWorking::is_double")]
+    pub working: TrackWorking,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<u32>,
+}
+
+
+// The starting inventory for each station: what's in the roundhouse, the yard and the warehouse when the whistle blows.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SeedConfig {
+    pub stations: Vec<StationSeed>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct StationSeed {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub engines: Vec<EngineSeed>,
+    #[serde(default)]
+    pub cars: Vec<CarSeed>,
+    #[serde(default)]
+    pub cargo: Vec<CargoSeed>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passengers: Vec<PassengerSeed>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EngineSeed {
+    pub id: u32,
+    pub engine_type: EngineType,
+    pub fuel: f32,
+    #[serde(default)]
+    pub km_since_service: f64, // Start it part-worn (or overdue) to see the sheds earn their keep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home: Option<u32>, // Leave it out and the engine belongs to the station it starts at.
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CarSeed {
+    pub id: u32,
+    #[serde(default)]
+    pub class: CarClass,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CargoSeed {
+    pub id: u32,
+    pub destination: u32,
+    pub weight: u32,
+    pub description: String,
+    #[serde(default)]
+    pub category: CargoCategory,
+    // "low", "normal" (the default), "high" or "urgent", and the timetable hour the customer needs it by, if they care.
+    // "not_before" opens a delivery window, if they've nowhere to put it until then.
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<f64>,
+    // Perishables only: how many hours it keeps in a reefer, and how many times faster it goes off out of one (3 if left out).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shelf_life_hours: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ambient_spoil_rate: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PassengerSeed {
+    pub name: String,
+    pub destination: u32,
+}
+
+
+// The locomotive classes (engines.json). Any class named like a built-in (Thomas, Percy, Gordon, Diesel) replaces it.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EngineCatalog {
+    pub classes: Vec<EngineClass>,
+}
+
+
+/// Reads any of our JSON files into its struct. Panics with the path in the message, because there's no simulation to run without it.
+pub fn load_json<T: DeserializeOwned>(path: &str) -> T {
+    let file_content = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+    serde_json::from_str(&file_content).unwrap_or_else(|e| panic!("Failed to parse {}: {}", path, e))
+}
+
+pub fn save_json<T: Serialize>(path: &str, value: &T) -> std::io::Result<()> {
+    let text = serde_json::to_string_pretty(value).map_err(std::io::Error::other)?;
+    std::fs::write(path, text)
+}
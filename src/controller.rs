@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::facilities::{Station, StationProfile};
+use crate::models::{Location, StationCommand, Switchboard, TrainError};
+use crate::network::{GlobalLedger, SharedNetwork};
+use crate::signalling::SharedSignalBox;
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BOLD: &str = "\x1b[1m";
+
+
+// The Fat Controller (finally!). The stations still run themselves, but somebody has to own the map
+// and announce engineering works. The controller is the only one who takes the write lock on the network.
+pub struct Controller {
+    pub network: SharedNetwork,
+    pub switchboard: Switchboard, // The same switchboard the Producers read, so new stations are reachable the moment they open.
+    pub ledger: Arc<Mutex<GlobalLedger>>, // New stations need the ledger too.
+    pub signal_box: SharedSignalBox,      // And the signal box. New track is double, one train each way, until somebody says otherwise.
+}
+
+
+impl Controller {
+    pub fn new(network: SharedNetwork, switchboard: Switchboard, ledger: Arc<Mutex<GlobalLedger>>, signal_box: SharedSignalBox) -> Self {
+        Controller {
+            network,
+            switchboard,
+            ledger,
+            signal_box,
+        }
+    }
+
+    /// Closes the track between `a` and `b` and tells both stations to drop each other from their neighbors.
+    /// Trains already out on the track finish their hop; everyone else re-routes at their next station.
+    pub fn close_track(&self, a: u32, b: u32) -> Result<(), TrainError> {
+        // 1. Update the map FIRST, so nobody plans a new route over the track while the stations are being told.
+        // The write lock is dropped at the end of this statement.
+        let closed = self.network.write().unwrap().close_track(a, b);
+        if closed.is_none() {
+            println!("{RED}Fat Controller: There is no open track between {} and {} to close!{RESET}", a, b);
+            return Err(TrainError::TrackNotFound { origin: a, destination: b });
+        }
+
+        // 2. Then hang up the radios on both ends.
+        println!("{BOLD}{RED}Fat Controller: Engineering works between {} and {}. Track closed until further notice.{RESET}", a, b);
+        self.notify(a, StationCommand::TrackClosed { neighbor: b });
+        self.notify(b, StationCommand::TrackClosed { neighbor: a });
+        Ok(())
+    }
+
+    /// Reopens a closed track. The stations get their radios back BEFORE the map changes, so any route
+    /// that uses the reopened track is guaranteed to find the neighbor's channel when it dispatches.
+    pub fn reopen_track(&self, a: u32, b: u32) -> Result<(), TrainError> {
+        if !self.network.read().unwrap().is_track_closed(a, b) {
+            println!("{RED}Fat Controller: The track between {} and {} isn't closed!{RESET}", a, b);
+            return Err(TrainError::TrackNotFound { origin: a, destination: b });
+        }
+
+        let (tx_a, tx_b) = match (self.radio(a), self.radio(b)) {
+            (Some(tx_a), Some(tx_b)) => (tx_a, tx_b),
+            _ => return Err(TrainError::TrackNotFound { origin: a, destination: b }),
+        };
+        self.notify(a, StationCommand::TrackReopened { neighbor: b, neighbor_tx: tx_b });
+        self.notify(b, StationCommand::TrackReopened { neighbor: a, neighbor_tx: tx_a });
+
+        self.network.write().unwrap().reopen_track(a, b);
+        println!("{BOLD}{GREEN}Fat Controller: Engineering works between {} and {} are complete. Track reopened.{RESET}", a, b);
+        Ok(())
+    }
+
+    /// Opens a brand new station while the network is running: spawns its thread, lays its tracks to `connections`,
+    /// and introduces it to each neighbor with NewNeighbor. The new station's id and name come from its profile.
+    pub fn add_station(&self, location: Location, profile: StationProfile, connections: &[u32]) -> Result<(), TrainError> {
+        let id = profile.id;
+        let name = profile.name.clone();
+        let maintenance_shed = profile.maintenance_shed;
+        if self.switchboard.read().unwrap().contains_key(&id) || self.network.read().unwrap().has_station(id) {
+            println!("{RED}Fat Controller: Station {} already exists!{RESET}", id);
+            return Err(TrainError::DuplicateId(id));
+        }
+        for neighbor in connections {
+            if self.radio(*neighbor).is_none() {
+                println!("{RED}Fat Controller: Cannot connect {} to unknown Station {}.{RESET}", name, neighbor);
+                return Err(TrainError::TrackNotFound { origin: id, destination: *neighbor });
+            }
+        }
+
+        println!("{BOLD}{GREEN}Fat Controller: Opening {} (Station {}) with tracks to {:?}.{RESET}", name, id, connections);
+
+        // 1. The new station's own radio, and the radios of the neighbors it will be connected to.
+        let (tx, rx) = mpsc::channel();
+        let neighbors: HashMap<u32, Sender<StationCommand>> = connections.iter()
+            .filter_map(|neighbor| self.radio(*neighbor).map(|neighbor_tx| (*neighbor, neighbor_tx)))
+            .collect();
+
+        // 2. Start the station thread before anyone can route a train to it.
+        Station::new(profile, neighbors, tx.clone(), Arc::clone(&self.network), Arc::clone(&self.ledger), Arc::clone(&self.signal_box), rx);
+
+        // 3. Introduce the newcomer to its neighbors, and put it on the switchboard.
+        for neighbor in connections {
+            self.notify(*neighbor, StationCommand::NewNeighbor { neighbor: id, neighbor_tx: tx.clone() });
+        }
+        self.switchboard.write().unwrap().insert(id, tx);
+
+        // 4. Only now does the station appear on the map, so any route that uses it will find every radio in place.
+        let mut network = self.network.write().unwrap();
+        network.register_station(id, location);
+        network.set_maintenance_shed(id, maintenance_shed);
+        for neighbor in connections {
+            network.add_track(id, *neighbor);
+        }
+        Ok(())
+    }
+
+    /// Closes a station for good. Its tracks are closed first so no new trains are routed there, then the station drains
+    /// its engines, cars, cargo and missions into its former neighbors and shuts its thread down.
+    /// All or nothing: if any track can't be closed, or the station refuses to drain, every track is left (or put back) open.
+    pub fn decommission_station(&self, id: u32) -> Result<(), TrainError> {
+        let Some(station_tx) = self.radio(id) else {
+            println!("{RED}Fat Controller: Station {} is not on the switchboard.{RESET}", id);
+            return Err(TrainError::MissionImpossible { reason: format!("Station {} does not exist", id) });
+        };
+
+        // 1. The heirs are the station's neighbors, nearest first.
+        let mut connections: Vec<(u32, f64)> = self.network.read().unwrap().get_tracks(&id).cloned().unwrap_or_default();
+        connections.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        let heirs: Vec<(u32, Sender<StationCommand>)> = connections.iter()
+            .filter_map(|(neighbor, _)| self.radio(*neighbor).map(|tx| (*neighbor, tx)))
+            .collect();
+        if heirs.is_empty() {
+            println!("{RED}Fat Controller: Station {} has no neighbors to take over its assets. It stays open.{RESET}", id);
+            return Err(TrainError::MissionImpossible { reason: format!("Station {} has no heirs", id) });
+        }
+
+        println!("{BOLD}{YELLOW}Fat Controller: Decommissioning Station {}. Heirs (nearest first): {:?}.{RESET}", id, heirs.iter().map(|(h, _)| *h).collect::<Vec<u32>>());
+
+        // 2. Cut it off from the network. Every track is checked and closed under one write lock, so either they all
+        //    close or none do. Only then are the stations told.
+        {
+            let mut network = self.network.write().unwrap();
+            if let Some((neighbor, _)) = connections.iter().find(|(neighbor, _)| network.get_distance(id, *neighbor).is_none()) {
+                println!("{RED}Fat Controller: The track between {} and {} is already gone. Station {} stays open.{RESET}", id, neighbor, id);
+                return Err(TrainError::TrackNotFound { origin: id, destination: *neighbor });
+            }
+            for (neighbor, _) in &connections {
+                network.close_track(id, *neighbor);
+            }
+        }
+        for (neighbor, _) in &connections {
+            println!("{BOLD}{RED}Fat Controller: Closing the track between {} and {} for good.{RESET}", id, neighbor);
+            self.notify(id, StationCommand::TrackClosed { neighbor: *neighbor });
+            self.notify(*neighbor, StationCommand::TrackClosed { neighbor: id });
+        }
+
+        // 3. Drain it, and wait for it to confirm before we tear down the paperwork.
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if station_tx.send(StationCommand::Decommission { heirs, reply_to: reply_tx }).is_err() {
+            println!("{RED}Fat Controller: Station {} is not answering its radio.{RESET}", id);
+        } else {
+            match reply_rx.recv() {
+                Ok(Ok(())) => println!("{GREEN}Fat Controller: Station {} has been drained.{RESET}", id),
+                Ok(Err(e)) => {
+                    // It refused before handing anything over, so put its tracks back the way they were.
+                    println!("{RED}Fat Controller: Station {} could not be drained: {:?}. Reopening its tracks.{RESET}", id, e);
+                    for (neighbor, _) in &connections {
+                        let _ = self.reopen_track(id, *neighbor);
+                    }
+                    return Err(e);
+                },
+                Err(e) => println!("{RED}Fat Controller: Station {} hung up during decommissioning: {:?}{RESET}", id, e),
+            }
+        }
+
+        // 4. Off the switchboard and off the map.
+        self.switchboard.write().unwrap().remove(&id);
+        self.network.write().unwrap().deregister_station(id);
+        Ok(())
+    }
+
+    fn radio(&self, station_id: u32) -> Option<Sender<StationCommand>> {
+        self.switchboard.read().unwrap().get(&station_id).cloned()
+    }
+
+    fn notify(&self, station_id: u32, command: StationCommand) {
+        match self.radio(station_id) {
+            Some(tx) => {
+                if tx.send(command).is_err() {
+                    println!("{RED}Fat Controller: DEAD-LETTER: Station {} is not answering its radio.{RESET}", station_id);
+                }
+            },
+            None => println!("{RED}Fat Controller: Station {} is not on the switchboard.{RESET}", station_id),
+        }
+    }
+}
+
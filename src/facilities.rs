@@ -1,12 +1,19 @@
-use crate::models::{Train, TrainCar, Engine, Mission, TrainError, RejectedAsset, EngineType, Cargo, FreightOrder ,Location, MissionReport};
-use crate::network::{GlobalLedger, RailwayNetwork};
+use crate::models::{Train, TrainCar, CarClass, Engine, Mission, TrainError, RejectedAsset, EngineType, Cargo, CargoCategory, FreightOrder ,Location, MissionReport, Priority, by_urgency, consist_can_complete_mission, sim_hours_now};
+use crate::network::{GlobalLedger, SharedNetwork};
+use crate::passengers::{Passenger, PassengerService, PASSENGER_WEIGHT};
+use crate::selection::{EnginePolicyKind, EngineQuery, EngineSelectionPolicy, RebalancePolicy};
+use crate::sla::{DeliveryRecord, Punctuality};
+use crate::classification::{Cut, DwellRecord, DEFAULT_HUB_DEPARTURE_CARS, DEFAULT_HUB_MAX_DWELL_HOURS};
+use crate::signalling::{BlockWait, DeadlockPolicy, HeldTrain, SharedSignalBox};
+use crate::timetable::{Rider, ScheduledRun, ScheduledService, next_run_id, BOOKING_GRACE_HOURS, BOOKING_WINDOW_HOURS, CONSOLIDATED_SERVICE, HOLD_LIMIT_HOURS};
 use core::error;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use rand::Rng;
 
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{self, Sender, Receiver};
 use std::thread;
+use std::time::Duration;
 use crate::models::StationCommand;
 
 use std::sync::atomic::{AtomicU32, Ordering};
@@ -19,7 +26,23 @@ const YELLOW: &str = "\x1b[33m";
 const CYAN: &str = "\x1b[36m";
 const BOLD: &str = "\x1b[1m";
 
-const EMPTY_CAR_WEIGHT: u32 = 2000; // Let's say every empty car weighs 2000kg. This is important for fuel calculations, because the engine has to pull not just the cargo, but also the weight of the cars themselves.
+const MAX_ENGINES_PER_TRAIN: usize = 3; // Double-heading, plus a banker at the back. Any more and the couplings give up.
+
+pub const DEFAULT_SERVICE_HOURS: f64 = 4.0; // A routine overhaul in a proper shed.
+const FIELD_REPAIR_HOURS: f64 = 12.0; // No shed: the fitters have to come out from somewhere else with their toolboxes.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2); // How often a station wakes itself up to retry missions and check on the shed.
+
+pub const DEFAULT_CAR_BUDGET: u32 = 10_000; // Enough for ten boxcars, for stations whose config doesn't say otherwise.
+const EMPTY_CAR_RESERVE: usize = 2; // Empty cars a donor keeps back for itself, over and above what its own parked missions need.
+const CAR_REQUEST_PATIENCE_HOURS: f64 = 6.0; // How long we wait for the neighbours to send empty cars before we build our own.
+pub const DEFAULT_YARD_CAPACITY: usize = 100;      // Cars the yard tracks can hold. The yard report has always said "/100".
+pub const DEFAULT_ROUNDHOUSE_CAPACITY: usize = 12; // Stalls in the roundhouse. Engines in the shed don't need one.
+pub const DEFAULT_WAREHOUSE_CAPACITY: usize = 100; // Consignments (or parts of one) the warehouse can hold.
+const HOLD_OUTSIDE_LIMIT_HOURS: f64 = 12.0; // After this long at the home signal, a train is squeezed in whether there's room or not.
+const CONSOLIDATION_WINDOW_HOURS: f64 = 1.0; // How long a new mission waits in the marshalling sidings for others going its way
+const TOP_UP_PATIENCE_HOURS: f64 = 24.0; // How long a station under its target mix waits for an engine before it asks again.
+const PARKED_MISSION_LIMIT_HOURS: f64 = 48.0; // A mission parked this long for want of an engine or cars goes back to its Producer as a failure.
+const PRIORITY_AGING_HOURS: f64 = 8.0; // A parked mission goes up a priority level for every this long it waits, so low-priority work can't be starved forever.
 
 // We start at 100 so it doesn't collide with the hardcoded cars (1-6) you made in main!
 static GLOBAL_CAR_ID: AtomicU32 = AtomicU32::new(1000);
@@ -76,6 +99,16 @@ pub trait CanReport {
         }
 
     }
+
+    fn send_late_report(&self, mission_id: u32, details: &str, hours_late: f64, channel: Option<Sender<MissionReport>>) {
+        let name = self.get_reporter_name();
+        let message = format!("Mission {} delivered at {}, {:.1}h after the deadline. Details: {}", mission_id, name, hours_late, details);
+        if let Some(chan) = channel {
+            let _ = chan.send(MissionReport::Late(message));
+        } else {
+            println!("{RED}[{}] DEAD-LETTER: No reply channel available to report late delivery for mission {}. Details: {}{RESET}", name, mission_id, details);
+        }
+    }
 }
 
 
@@ -129,7 +162,9 @@ pub struct Railyard {
     pub trains: Vec<Train>,
     pub cars: HashMap<u32, TrainCar>,
     pub next_train_id: u32,
-    pub purgatory: Vec<RejectedAsset>,
+    pub purgatory: Vec<RejectedAsset>, // The stray siding. Doesn't count against the yard's capacity.
+    pub capacity: usize,
+    pub classification: BTreeMap<u32, Vec<Cut>>, // Cars on the classification tracks, by the next station on their way. Only a hub has any. They're yard tracks too.
 }
 
 
@@ -139,40 +174,108 @@ impl Railyard {
 
 
 
-    /// Finds any empty car in the yard, loads the cargo into it, and returns the car.
-    pub fn load_cargo_into_empty_car(&mut self, cargo: Cargo) -> Result<TrainCar, TrainError> {
-        // 1. Find the ID of an empty car
-        let empty_car_id = self.cars.iter()
-            .find(|(_, car)| car.cargo.is_none())
-            .map(|(&id, _)| id); // Just grab the ID
+    /// Loads a consignment into the empty cars that suit it best and returns them. Usually that's one car;
+    /// a consignment too heavy for any single car is split into linked parts, one per car.
+    pub fn load_consignment(&mut self, cargo: Cargo) -> Result<Vec<TrainCar>, TrainError> {
+        // 1. Work out which cars it goes in, and how much rides in each
+        let (allocation, left_over) = self.allocate_cars(cargo.category, cargo.actual_weight, &HashSet::new());
+        if left_over > 0 {
+            return Err(TrainError::NoCompatibleCar { cargo_id: cargo.id, category: cargo.category, weight: cargo.actual_weight });
+        }
 
-        // 2. If we found one, remove it from the yard, load it, and return it
-        if let Some(id) = empty_car_id {
-            let mut car = self.cars.remove(&id).unwrap();
-            car.cargo = Some(cargo); // LOAD THE CAR!
-            Ok(car)
+        // 2. Split it if it needs more than one car
+        let parts = if allocation.len() > 1 {
+            println!("{YELLOW}Yard: {} ({}kg) is too heavy for one car. Splitting it across {} cars.{RESET}", cargo.item, cargo.actual_weight, allocation.len());
+            cargo.split(&allocation.iter().map(|(_, weight)| *weight).collect::<Vec<u32>>())
         } else {
-            Err(TrainError::MissionImpossible { 
-                reason: "No empty cars available in the yard!".to_string() 
-            })
+            vec![cargo]
+        };
+
+        // 3. Take the cars out of the yard and load them
+        Ok(allocation.iter().zip(parts).map(|((id, _), part)| {
+            let mut car = self.cars.remove(id).unwrap();
+            car.cargo = Some(part); // LOAD THE CAR!
+            car
+        }).collect())
+    }
+
+    /// The empty car (not in `skip`) that can carry this load with the least payload to spare, so the big cars stay free for the big loads.
+    fn best_car_for(&self, category: CargoCategory, weight: u32, skip: &HashSet<u32>) -> Option<u32> {
+        self.cars.values()
+            .filter(|car| car.cargo.is_none() && !skip.contains(&car.id) && car.class.fits(category, weight))
+            .min_by_key(|car| (car.class.max_payload(), car.id))
+            .map(|car| car.id)
+    }
+
+    /// Picks the empty cars (not in `skip`) for a load. If one car can take it all, that's the best fit.
+    /// Otherwise we fill the roomiest compatible cars one at a time until what's left fits in a single car.
+    /// Returns each car with the weight it would carry, and the weight still left over if we ran out of cars.
+    fn allocate_cars(&self, category: CargoCategory, weight: u32, skip: &HashSet<u32>) -> (Vec<(u32, u32)>, u32) {
+        let mut used = skip.clone();
+        let mut allocation = Vec::new();
+        let mut remaining = weight;
+        loop {
+            if let Some(id) = self.best_car_for(category, remaining, &used) {
+                allocation.push((id, remaining));
+                return (allocation, 0);
+            }
+            let roomiest = self.cars.values()
+                .filter(|car| car.cargo.is_none() && !used.contains(&car.id) && car.class.carries(category))
+                .max_by_key(|car| (car.class.max_payload(), std::cmp::Reverse(car.id)));
+            match roomiest {
+                Some(car) => {
+                    allocation.push((car.id, car.class.max_payload()));
+                    used.insert(car.id);
+                    remaining -= car.class.max_payload(); // Safe: it didn't fit, so there's more left than this car holds
+                },
+                None => return (allocation, remaining),
+            }
         }
     }
 
+    /// A dry run of loading this cargo, heaviest first (the same order assemble_cars uses).
+    /// Returns the tare and number of the cars we'd use, and each piece of cargo we couldn't find (enough) cars for, with the weight still stranded.
+    pub fn plan_loading<'a>(&self, cargo: &[&'a Cargo]) -> (u32, usize, Vec<(&'a Cargo, u32)>) {
+        let (used, unmatched) = self.allocate_manifest(cargo);
+        let tare = used.iter().map(|id| self.cars[id].class.tare()).sum();
+        (tare, used.len(), unmatched)
+    }
 
-    pub fn load_cargo_into_empty_cars (&mut self, cargo: Vec<Cargo>) -> Result<Vec<TrainCar>, TrainError> {
-        let mut cars = Vec::<TrainCar>::new();
-        for cargo_item in cargo {
-            let car = self.load_cargo_into_empty_car(cargo_item)?;
-            cars.push(car);
+    /// The same dry run, counted up by class: how many of our empty cars of each kind this cargo would take.
+    pub fn cars_wanted_by_class(&self, cargo: &[&Cargo]) -> HashMap<CarClass, usize> {
+        let mut wanted = HashMap::new();
+        for id in self.allocate_manifest(cargo).0 {
+            *wanted.entry(self.cars[&id].class).or_default() += 1;
         }
-        Ok(cars)
+        wanted
     }
 
+    fn allocate_manifest<'a>(&self, cargo: &[&'a Cargo]) -> (HashSet<u32>, Vec<(&'a Cargo, u32)>) {
+        let mut manifest = cargo.to_vec();
+        manifest.sort_by_key(|item| std::cmp::Reverse(item.actual_weight));
+
+        let mut used = HashSet::new();
+        let mut unmatched = Vec::new();
+        for item in manifest {
+            let (allocation, left_over) = self.allocate_cars(item.category, item.actual_weight, &used);
+            used.extend(allocation.into_iter().map(|(id, _)| id));
+            if left_over > 0 {
+                unmatched.push((item, left_over));
+            }
+        }
+        (used, unmatched)
+    }
 
 
     // Copilot was here! Helping with the functional stuff. Thanks, buddy!
-    pub fn validate_empty_cars(&self, mission: &Mission) -> bool {
-        return mission.cargo_ids.len() <= self.cars.values().filter(|car| car.cargo.is_none()).count();
+    pub fn empty_car_count(&self, class: CarClass) -> usize {
+        self.cars.values().filter(|car| car.cargo.is_none() && car.class == class).count()
+    }
+
+    /// Pulls up to `count` empty cars of this class out of the lockers, e.g. to lend them to a neighbour.
+    pub fn take_empty_cars(&mut self, class: CarClass, count: usize) -> Vec<TrainCar> {
+        let ids: Vec<u32> = self.cars.iter().filter(|(_, car)| car.cargo.is_none() && car.class == class).map(|(&id, _)| id).take(count).collect();
+        ids.iter().filter_map(|id| self.cars.remove(id)).collect()
     }
 
 
@@ -180,16 +283,27 @@ impl Railyard {
 
 
 
-    fn new(id: u32) -> Self {
+    fn new(id: u32, capacity: usize) -> Self {
         Railyard {
             id,
             trains: Vec::new(),
             cars: HashMap::new(),
             next_train_id: 1,
             purgatory: Vec::new(),
+            capacity,
+            classification: BTreeMap::new(),
         }
     }
 
+    /// How many more cars the yard tracks can take. Cars waiting on the classification tracks take up room as well.
+    pub fn free_tracks(&self) -> usize {
+        self.capacity.saturating_sub(self.cars.len() + self.cars_on_classification_tracks())
+    }
+
+    pub fn cars_on_classification_tracks(&self) -> usize {
+        self.classification.values().flatten().map(|cut| cut.cars.len()).sum()
+    }
+
     fn generate_new_train_id(&mut self) -> u32 {
         GLOBAL_TRAIN_ID.fetch_add(1, Ordering::SeqCst)
     }
@@ -217,13 +331,14 @@ impl Railyard {
         } else {
             for train in &self.trains {
                 let load = train.calculate_cargo_weight();
-                println!("    {GREEN}🚂 [TRAIN {:02}]{RESET} | Engine: {:?} (ID: {}) | Cars: {} | Load: {}kg", 
-                    train.id, train.engine.engine_type, train.engine.id, train.cars.len(), load);
+                let engines: Vec<String> = train.engines.iter().map(|engine| format!("{:?} (ID: {})", engine.engine_type, engine.id)).collect();
+                println!("    {GREEN}🚂 [TRAIN {:02}]{RESET} | Engines: {} | Cars: {} | Load: {}kg", 
+                    train.id, engines.join(" + "), train.cars.len(), load);
             }
         }
 
         // 2. THE MAIN YARD (The Lockers)
-        println!("\n  {BOLD}MAIN YARD LOCKERS ({}/100 capacity used){RESET}", self.cars.len()); 
+        println!("\n  {BOLD}MAIN YARD LOCKERS ({}/{} capacity used){RESET}", self.cars.len() + self.cars_on_classification_tracks(), self.capacity);
         if self.cars.is_empty() {
             println!("    (No cars currently parked)");
         } else {
@@ -232,8 +347,7 @@ impl Railyard {
                     Some(c) => format!("{} ({}kg)", c.item, c.actual_weight),
                     None => "Empty".to_string(),
                 };
-                let pax = car.passenger.as_deref().unwrap_or("None");
-                println!("    {CYAN}[CAR ID: {:02}]{RESET} | Pax: {:<10} | Cargo: {}", id, pax, cargo_desc);
+                println!("    {CYAN}[CAR ID: {:02}]{RESET} {:?} | Pax: {:<3} | Cargo: {}", id, car.class, car.passengers.len(), cargo_desc);
             }
         }
 
@@ -256,16 +370,33 @@ impl Railyard {
                 if queue.is_empty() { continue; } // Skip empty stalls
                 println!("    [{:?}] Stall - {} Engine(s) Waiting:", etype, queue.len());
                 for (i, engine) in queue.iter().enumerate() {
-                    println!("      {}. Engine {} | Fuel: {:?}", i + 1, engine.id, engine.current_fuel);
+                    println!("      {}. Engine {} | Fuel: {:?} | {:.0}km since service", i + 1, engine.id, engine.current_fuel, engine.km_since_service);
                 }
             }
         }
+        if !roundhouse.shed.is_empty() {
+            println!("    [Shed] {} Engine(s) under repair:", roundhouse.shed.len());
+            for (engine, ready_at) in &roundhouse.shed {
+                println!("      - Engine {} ({:?}) | Ready in {:.1}h", engine.id, engine.engine_type, (ready_at - sim_hours_now()).max(0.0));
+            }
+        }
         
         println!("{BOLD}{CYAN}━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━{RESET}\n");
     }
 
 
-    pub fn receive_car(&mut self, mut car: TrainCar) -> Result<Option<Cargo>, (TrainCar, Vec<TrainError>)> {
+    pub fn receive_car(&mut self, car: TrainCar) -> Result<Option<Cargo>, (Box<TrainCar>, Vec<TrainError>)> {
+        self.take_in(car, false)
+    }
+
+    /// Docks a car whether there's a free yard track or not. For cars with nowhere else to go:
+    /// a train let in after waiting too long outside, or the wreckage of one that never made it.
+    /// Duplicates and contraband still go to purgatory.
+    pub fn squeeze_in(&mut self, car: TrainCar) -> Result<Option<Cargo>, (Box<TrainCar>, Vec<TrainError>)> {
+        self.take_in(car, true)
+    }
+
+    fn take_in(&mut self, mut car: TrainCar, squeeze: bool) -> Result<Option<Cargo>, (Box<TrainCar>, Vec<TrainError>)> {
 
         let mut issues = Vec::<TrainError>::new();
 
@@ -287,16 +418,31 @@ impl Railyard {
             }
         }
 
+        // 3. The Shelf-Life Check: bring a perishable's clock up to date for its time on the train. A reefer kept it cold.
+        //    Whether it's still any good is the station's business, not the yard's.
+        let chilled = car.class == CarClass::Reefer;
+        if let Some(cargo) = &mut car.cargo {
+            cargo.age(chilled);
+        }
+
+        // 4. The Space Check: every yard track is full. The car goes to the stray siding like any other reject,
+        //    unless it's being squeezed in, in which case it stands on the running lines until there's room.
+        if !squeeze && self.free_tracks() == 0 {
+            println!("{RED}Railyard Error: No room for Car {}. All {} yard tracks are full!{RESET}", car.id, self.capacity);
+            issues.push(TrainError::NoRoom { facility: "yard".to_string(), capacity: self.capacity });
+        }
+
         if issues.is_empty() {
-            // 3. Success: The state change is clear
+            // 5. Success: The state change is clear
             println!("{GREEN}Railyard: Car {} safely docked in locker.{RESET}", car.id);
+            car.destination = None; // It's ours now, and goes wherever we send it next
             let cargo = car.cargo.take(); // We want to pass the cargo up to the warehouse, but we also want to keep the car in the yard's inventory. By using .take(), we move the cargo out of the car and replace it with None, which allows us to return the cargo to the caller while still keeping the car in our HashMap for future reference.
             self.cars.insert(car.id, car);
             Ok(cargo)
         }
         else {
-            // 4. Failure: We return the car and the ledger of issues for transparency and debugging.
-            Err((car, issues))
+            // 6. Failure: We return the car and the ledger of issues for transparency and debugging.
+            Err((Box::new(car), issues)) // Boxed: a car with its cargo is a big thing to hand back by value
         }
     }
 
@@ -320,29 +466,53 @@ impl Railyard {
         }
     }
 
-    pub fn decouple_by_id(&mut self, train: &mut Train, id: u32){
+    /// Move a car identified by its `id` out of a train and into the yard.
+    ///
+    /// Its load (if any) comes back for the warehouse. A car the yard won't have goes to
+    /// purgatory, and its ID comes back as the error so the station can report it.
+    pub fn decouple_by_id(&mut self, train: &mut Train, id: u32) -> Result<Option<Cargo>, u32> {
         if let Some(pos) = train.cars.iter().position(|c| c.id == id) {
             let car = train.cars.remove(pos);
-
-            if let Err((car, issues)) = self.receive_car(car) {
-                println!("Failed to return Car {} to the yard: {:?}. Moving to purgatory.", car.id, issues);
-                let rejected_asset: RejectedAsset = RejectedAsset::new(car, issues, train.mission_id); // We can fill in the timestamp and source_mission later when we implement those features.
-                self.purgatory.push(rejected_asset);
+            println!("RailYard: Decoupling Car {} from Train {}.", id, train.id);
+
+            match self.squeeze_in(car) { // It's being set down off a train standing in the station; there's nowhere else for it
+                Ok(cargo) => Ok(cargo),
+                Err((car, issues)) => {
+                    println!("Failed to return Car {} to the yard: {:?}. Moving to purgatory.", car.id, issues);
+                    let rejected_asset: RejectedAsset = RejectedAsset::new(*car, issues, train.mission_id); // We can fill in the timestamp and source_mission later when we implement those features.
+                    self.purgatory.push(rejected_asset);
+                    Err(id)
+                }
             }
 
         } else {
             println!("Car {} is not attached to Train {}.", id, train.id);
+            Ok(None)
         }
     }
 
+    /// Loads a consignment into empty cars and leaves them standing in the yard, labelled for where they come off,
+    /// ready for `couple_by_id`. Returns their IDs.
+    pub fn load_for_pickup(&mut self, cargo: Vec<Cargo>, destination: u32) -> Result<Vec<u32>, TrainError> {
+        let cars = self.assemble_cars(cargo)?;
+        Ok(cars.into_iter().map(|mut car| {
+            car.destination = Some(destination);
+            let id = car.id;
+            self.cars.insert(id, car);
+            id
+        }).collect())
+    }
+
     // This essential method returns a vector of fully loaded cars ready to be hitched up to the engine and sent on its way to fulfill the mission.
     pub fn assemble_cars(&mut self, cargo: Vec<Cargo>) -> Result<Vec<TrainCar>, TrainError> {
         
         // We've already validated that we have enough empty cars for this mission before we even started assembling, so we can be confident that this won't fail due to lack of cars. If it does fail, it's an unexpected error that we should know about immediately, which is why we use the `?` operator to propagate any errors up to the caller without having to write explicit error handling logic here.
+        // Heaviest first, so the big loads get first pick of the big cars. Same order as plan_loading.
+        let mut cargo = cargo;
+        cargo.sort_by_key(|item| std::cmp::Reverse(item.actual_weight));
         let mut cars = Vec::<TrainCar>::new();
         for cargo_item in cargo {
-            let car = self.load_cargo_into_empty_car(cargo_item)?;
-            cars.push(car);
+            cars.extend(self.load_consignment(cargo_item)?);
         }
         Ok(cars)
         
@@ -355,117 +525,258 @@ impl Railyard {
 pub struct Roundhouse {
     pub id: u32,
     pub stalls: HashMap<EngineType, VecDeque<Engine>>,
+    pub shed: Vec<(Engine, f64)>, // Engines under the spanner, and the timetable hour they'll be ready again.
+    pub has_shed: bool,
+    pub service_hours: f64,
+    pub policy: Box<dyn EngineSelectionPolicy>, // Who goes out when several engines could do the job
+    pub housed_at: HashMap<u32, f64>,           // Engine ID -> the timetable hour it pulled into the roundhouse
+    pub rebalance: RebalancePolicy,
+    pub target_mix: HashMap<EngineType, usize>, // How many of each class we'd like to keep, for RebalancePolicy::TargetMix
+    pub capacity: usize,                        // Stalls. The shed is a separate building.
 }
 
 
 
 impl Roundhouse {
-    pub fn new(id:u32) -> Self {
+    pub fn new(id:u32, profile: &StationProfile) -> Self {
         Roundhouse {
             id,
             stalls: HashMap::new(),
+            shed: Vec::new(),
+            has_shed: profile.maintenance_shed,
+            service_hours: profile.service_hours,
+            policy: profile.engine_policy.build(),
+            housed_at: HashMap::new(),
+            rebalance: profile.rebalance,
+            target_mix: profile.target_mix.clone(),
+            capacity: profile.roundhouse_capacity,
         }
     }
 
-    /// Houses an engine in the appropriate stall based on its type.
-    pub fn house(&mut self, engine: Engine) {
+    /// How many more engines the stalls can take.
+    pub fn free_stalls(&self) -> usize {
+        self.capacity.saturating_sub(self.stalls.values().map(VecDeque::len).sum())
+    }
+
+    /// Houses an engine in the appropriate stall based on its type. If we have a shed and the engine is due, it goes in for service instead.
+    /// An engine without a home adopts the first roundhouse it's put in. Hands the engine back if every stall is taken.
+    pub fn house(&mut self, mut engine: Engine) -> Result<(), Engine> {
+        engine.home.get_or_insert(self.id);
+        if self.has_shed && engine.service_due() {
+            let hours = self.service_hours;
+            self.send_for_repairs(engine, hours);
+            return Ok(());
+        }
+        if self.free_stalls() == 0 {
+            return Err(engine);
+        }
+        self.squeeze_in(engine);
+        Ok(())
+    }
+
+    /// Puts an engine in a stall whether there's one free or not. For engines with nowhere else to go.
+    pub fn squeeze_in(&mut self, engine: Engine) {
+        self.housed_at.insert(engine.id, sim_hours_now());
         self.stalls
             .entry(engine.engine_type) // 1. Check the stall for this EngineType
-            .or_insert_with(VecDeque::new)  // 2. If it doesn't exist, build a new track (VecDeque)
+            .or_default()  // 2. If it doesn't exist, build a new track (VecDeque)
             .push_back(engine);             // 3. Park the engine on the track
     }
 
     pub fn dispatch(&mut self, etype: EngineType) -> Option<Engine> {
-        self.stalls
+        let engine = self.stalls
             .get_mut(&etype)? // Find the stall
-            .pop_front()      // Take the one that's been waiting longest
+            .pop_front()?;    // Take the one that's been waiting longest
+        self.housed_at.remove(&engine.id); // Gone from our roundhouse. It gets a fresh time if it ever comes back.
+        Some(engine)
+    }
+
+
+    /// Finds an engine that can do the job, and lets the station's selection policy pick between the capable ones.
+    /// `paired_capacity` is the strength of an engine it will be coupled to (0 if it runs alone): it only has to cover
+    /// the shortfall, and only burns fuel for its own share of the load. `lending` is true when the engine is leaving to help a neighbour.
+    pub fn find_suitable_engine(&mut self, total_weight: f64, paired_capacity: f64, distance_km: f64, gradient: f64, lending: bool) -> Result<Engine, TrainError> {
+        let query = EngineQuery { total_weight, paired_capacity, distance_km, gradient, lending };
+
+        let candidates = self.capable_engines(&query);
+        let chosen = self.policy.choose(&candidates, self, &query);
+
+        if let Some(engine) = chosen.and_then(|id| self.take(id)) {
+            println!("{GREEN}Roundhouse {}: Dispatching Engine {} of type {:?} for mission ({}kg over {}km, climbing {:.1}m/km), picked {}.{RESET}", self.id, engine.id, engine.engine_type, query.share_for(engine.engine_type), distance_km, gradient, self.policy.name());
+            return Ok(engine);
+        }
+
+        // If nobody capable is on hand (or the policy won't let them go), return an error.
+        println!("{RED}Roundhouse {}: No suitable engines available for mission ({}kg over {}km, climbing {:.1}m/km).{RESET}", self.id, total_weight, distance_km, gradient);
+        Err(TrainError::MissionImpossible { reason: "NO ENGINES CAN COMPLETE MISSION!".to_string() })
     }
 
+    /// An engine of this class we can lend to a station topping up its target mix, if it can make the trip. Under
+    /// TargetMix we don't go below our own target to do it; otherwise any engine of the class is spare.
+    pub fn spare_engine_of_class(&mut self, class: EngineType, distance_km: f64, gradient: f64) -> Result<Engine, TrainError> {
+        let keep = if self.rebalance == RebalancePolicy::TargetMix { self.target_mix.get(&class).copied().unwrap_or(0) } else { 0 };
+        let spare = self.stalls.get(&class)
+            .filter(|queue| queue.len() > keep)
+            .and_then(|queue| queue.iter().find(|engine| engine.can_complete_mission(0.0, distance_km, gradient)))
+            .map(|engine| engine.id);
+        match spare.and_then(|id| self.take(id)) {
+            Some(engine) => Ok(engine),
+            None => Err(TrainError::MissionImpossible { reason: format!("No {:?} to spare", class) }),
+        }
+    }
 
-    pub fn check_suitable_engine(&self, total_weight: f64, distance_km: f64) -> Option<&Engine> {
-        // We check each stall in order of engine strength
-        let roster = [
-            EngineType::Percy, 
-            EngineType::Thomas, 
-            EngineType::Diesel, 
-            EngineType::Gordon
-        ];
+    /// Every engine on hand that could do the job, weakest class first and longest-waiting first within a class.
+    pub fn capable_engines(&self, query: &EngineQuery) -> Vec<&Engine> {
+        // 1. The Escalation Roster (Weakest to Strongest)
+        let roster = EngineType::roster();
+        let mut candidates = Vec::new();
 
         for etype in roster {
+            // Check if this TYPE is physically strong enough (with its partner, if it has one)
+            if etype.max_capacity() + query.paired_capacity < query.total_weight {
+                continue;
+            }
+            let share = query.share_for(etype);
             if let Some(queue) = self.stalls.get(&etype) {
-                for engine in queue {
-                    if engine.can_complete_mission(total_weight, distance_km) {
-                        return Some(engine);
-                    }
-                }
+                candidates.extend(queue.iter().filter(|engine| engine.can_complete_mission(share, query.distance_km, query.gradient)));
             }
         }
-        None // If we loop through the whole roster and find nothing, return None.
+        candidates
     }
 
-    // Create a method that tells us only if we possess an engine perfect in strenght, not too weak, not too strong, just right. This is for the "Goldilocks" gossip strategy where we only want to ask for help if we don't have the perfect engine in our own roundhouse.
-    pub fn check_ideal_engine(&self, total_weight: f64, distance_km: f64) -> Option<&Engine> {
-        // We check each stall in order of engine strength
-        let roster = [
-            EngineType::Percy, 
-            EngineType::Thomas, 
-            EngineType::Diesel, 
-            EngineType::Gordon
-        ];
+    /// When this engine pulled in. Engines we've lost track of count as having just arrived.
+    pub fn housed_since(&self, engine_id: u32) -> f64 {
+        self.housed_at.get(&engine_id).copied().unwrap_or(f64::MAX)
+    }
 
-        for etype in roster {
-            if let Some(queue) = self.stalls.get(&etype) {
-                for engine in queue {
-                    if engine.can_complete_mission(total_weight, distance_km) {
-                        // Check if it's a perfect match (not too weak, not too strong)
-                        if engine.is_ideal_for_mission(total_weight) {
-                            return Some(engine);
-                        }
-                    }
-                }
+    /// One engine if one will do. Otherwise couple up the strongest engines on hand (up to MAX_ENGINES_PER_TRAIN)
+    /// until one more would make the consist strong enough, and let the station's policy pick that last one.
+    pub fn find_suitable_consist(&mut self, total_weight: f64, distance_km: f64, gradient: f64) -> Result<Vec<Engine>, TrainError> {
+        if let Ok(engine) = self.find_suitable_engine(total_weight, 0.0, distance_km, gradient, false) {
+            return Ok(vec![engine]);
+        }
+
+        // The leads go strongest first, so we use as few engines as possible.
+        let mut strongest: Vec<&Engine> = self.stalls.values().flatten().collect();
+        strongest.sort_by(|a, b| b.engine_type.max_capacity().partial_cmp(&a.engine_type.max_capacity()).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut leads: Vec<&Engine> = Vec::new();
+        let mut picked = None;
+        for lead in strongest.into_iter().take(MAX_ENGINES_PER_TRAIN - 1) {
+            leads.push(lead);
+            let query = EngineQuery { total_weight, paired_capacity: leads.iter().map(|engine| engine.engine_type.max_capacity()).sum(), distance_km, gradient, lending: false };
+            // Whoever could finish the consist, and keep the whole of it within reach of its fuel.
+            let candidates: Vec<&Engine> = self.capable_engines(&query).into_iter()
+                .filter(|candidate| leads.iter().all(|lead| lead.id != candidate.id))
+                .filter(|candidate| {
+                    let mut consist = leads.clone();
+                    consist.push(candidate);
+                    consist_can_complete_mission(&consist, total_weight, distance_km, gradient)
+                })
+                .collect();
+            if let Some(last) = self.policy.choose(&candidates, self, &query) {
+                let mut ids: Vec<u32> = leads.iter().map(|engine| engine.id).collect();
+                ids.push(last);
+                picked = Some(ids);
+                break;
+            }
+        }
+        if let Some(ids) = picked {
+            println!("{GREEN}Roundhouse {}: Coupling Engines {:?} together for mission ({}kg over {}km, climbing {:.1}m/km), last one picked {}.{RESET}", self.id, ids, total_weight, distance_km, gradient, self.policy.name());
+            return Ok(ids.into_iter().filter_map(|id| self.take(id)).collect());
+        }
+
+        println!("{RED}Roundhouse {}: Not even a double-header can manage this one ({}kg over {}km, climbing {:.1}m/km).{RESET}", self.id, total_weight, distance_km, gradient);
+        Err(TrainError::MissionImpossible { reason: "NO ENGINES CAN COMPLETE MISSION!".to_string() })
+    }
+
+    /// The strongest engine on hand, i.e. what a donor would be paired with. 0 if the roundhouse is empty.
+    pub fn strongest_capacity(&self) -> f64 {
+        self.stalls.values().flatten().map(|engine| engine.engine_type.max_capacity()).fold(0.0, f64::max)
+    }
+
+    /// Takes an engine out of service for `hours` of timetable time. Stations without a shed can still patch up a broken engine, it just takes longer.
+    pub fn send_for_repairs(&mut self, engine: Engine, hours: f64) {
+        let hours = if self.has_shed { hours } else { hours.max(FIELD_REPAIR_HOURS) };
+        println!("{YELLOW}Roundhouse {}: Engine {} ({:?}) is in for repairs after {:.0}km. Back in {:.1}h.{RESET}", self.id, engine.id, engine.engine_type, engine.km_since_service, hours);
+        self.shed.push((engine, sim_hours_now() + hours));
+    }
+
+    /// Returns every engine whose service is finished to the stalls. Returns how many came out.
+    pub fn release_serviced(&mut self) -> usize {
+        let now = sim_hours_now();
+        let mut released = 0;
+        let mut index = 0;
+        while index < self.shed.len() {
+            // Finished engines wait in the shed until there's a stall to put them in.
+            if self.shed[index].1 <= now && self.free_stalls() > 0 {
+                let (mut engine, _) = self.shed.swap_remove(index);
+                engine.service();
+                self.housed_at.insert(engine.id, now);
+                self.stalls.entry(engine.engine_type).or_default().push_back(engine);
+                released += 1;
+            } else {
+                index += 1;
             }
         }
-        None // If we loop through the whole roster and find nothing, return None.
+        released
     }
 
-    pub fn find_suitable_engine(&mut self, total_weight: f64, distance_km: f64) -> Result<Engine, TrainError> {
-        
-        // 1. The Escalation Roster (Weakest to Strongest)
-        let roster = [
-            EngineType::Percy, 
-            EngineType::Thomas, 
-            EngineType::Diesel, 
-            EngineType::Gordon
-        ];
-
-        // 2. Iterate through the roster in order
-        for etype in roster {
-            // Check if this TYPE is physically strong enough
-            if etype.max_capacity() >= total_weight {
-                println!("{YELLOW}Roundhouse {}: Checking for available {:?} engines...{RESET}", self.id, etype);
-                
-                // If it is, look inside that specific stall
-                if let Some(queue) = self.stalls.get_mut(&etype) {
-                    
-                    // 1. Find the position of the first capable engine
-                    let winner_index = queue.iter().position(|engine| {
-                        engine.can_complete_mission(total_weight, distance_km)
-                    });
-
-                    // 2. Chain it using the `.and_then()` you love!
-                    // If position returned Some(index), and_then passes that index into queue.remove()
-                    if let Some(engine) = winner_index.and_then(|index| queue.remove(index)) {
-                        println!("{GREEN}Roundhouse {}: Dispatching Engine {} of type {:?} for mission ({}kg over {}km).{RESET}", self.id, engine.id, engine.engine_type, total_weight, distance_km);
-                        return Ok(engine);
+    /// Takes out the engines that should go back to their home depots, according to our rebalancing policy.
+    /// A station with missions still waiting for power keeps everything it has.
+    pub fn take_homeward_engines(&mut self, busy: bool) -> Vec<Engine> {
+        self.homeward_engine_ids(busy).into_iter().filter_map(|id| self.take(id)).collect()
+    }
+
+    /// The engines `take_homeward_engines` would send off, without taking them. Backhauls only ever use these.
+    pub fn homeward_engine_ids(&self, busy: bool) -> Vec<u32> {
+        if busy {
+            return Vec::new();
+        }
+        let mut homeward = Vec::new();
+        for (etype, queue) in &self.stalls {
+            let keep = match self.rebalance {
+                RebalancePolicy::Off => continue,
+                RebalancePolicy::ReturnHome => 0,
+                RebalancePolicy::TargetMix => self.target_mix.get(etype).copied().unwrap_or(0),
+            };
+            // Only strangers leave. Our own engines are home already, even if we have more than we'd like.
+            let surplus = queue.len().saturating_sub(keep);
+            homeward.extend(queue.iter().filter(|engine| engine.home.is_some_and(|home| home != self.id)).take(surplus).map(|engine| engine.id));
+        }
+        homeward
+    }
+
+    /// Without a shed of our own, engines that are due for service can't get it here. Hands over the ones that
+    /// `can_travel` says will make it to one.
+    pub fn take_due_engines(&mut self, can_travel: impl Fn(&Engine) -> bool) -> Vec<Engine> {
+        if self.has_shed {
+            return Vec::new();
+        }
+        let mut due = Vec::new();
+        for queue in self.stalls.values_mut() {
+            let mut index = 0;
+            while index < queue.len() {
+                if queue[index].service_due() && can_travel(&queue[index]) {
+                    if let Some(engine) = queue.remove(index) {
+                        self.housed_at.remove(&engine.id);
+                        due.push(engine);
                     }
+                } else {
+                    index += 1;
                 }
-
             }
         }
-        
-        // If we loop through the whole roster and find nothing, return an error.
-        println!("{RED}Roundhouse {}: No suitable engines available for mission ({}kg over {}km).{RESET}", self.id, total_weight, distance_km);
-        Err(TrainError::MissionImpossible { reason: "NO ENGINES CAN COMPLETE MISSION!".to_string() })
+        due
+    }
+
+    fn take(&mut self, engine_id: u32) -> Option<Engine> {
+        let engine = self.stalls.values_mut().find_map(|queue| {
+            let index = queue.iter().position(|engine| engine.id == engine_id)?;
+            queue.remove(index)
+        })?;
+        self.housed_at.remove(&engine_id);
+        Some(engine)
     }
 }
 
@@ -473,19 +784,100 @@ impl Roundhouse {
 pub struct Warehouse {
     pub id: u32,
     pub inventory: HashMap<u32, Cargo>, // We can use the cargo ID as the key for easy retrieval and inventory management
+    pub partials: HashMap<u32, Vec<Cargo>>, // Parts of split consignments, by cargo ID, waiting for the rest to turn up
+    pub capacity: usize,                    // Each consignment, or each part of one, takes a space
+    pub disposal: Vec<Cargo>,               // The skip round the back: perishables that went off before they got anywhere
 }
 
 impl Warehouse {
-    pub fn new(id:u32) -> Self {
+    pub fn new(id:u32, capacity: usize) -> Self {
         Warehouse {
             id,
             inventory: HashMap::new(),
+            partials: HashMap::new(),
+            capacity,
+            disposal: Vec::new(),
+        }
+    }
+
+    /// How many more consignments (or parts) the warehouse can take.
+    pub fn free_space(&self) -> usize {
+        let used = self.inventory.len() + self.partials.values().map(Vec::len).sum::<usize>();
+        self.capacity.saturating_sub(used)
+    }
+
+    /// Stores a consignment, or hands it back if the warehouse is full.
+    pub fn store(&mut self, cargo: Cargo) -> Result<(), Cargo> {
+        if self.free_space() == 0 {
+            return Err(cargo);
+        }
+        self.squeeze_in(cargo);
+        Ok(())
+    }
+
+    /// Stores a consignment whether there's room or not. For cargo with nowhere else to go.
+    pub fn squeeze_in(&mut self, cargo: Cargo) {
+        let Some(part) = cargo.part else {
+            println!("{BOLD}{YELLOW}Warehouse: Received {} ({}kg) for processing/holding.{RESET}", cargo.item, cargo.actual_weight);
+            self.inventory.insert(cargo.id, cargo);
+            return;
+        };
+
+        // One piece of a split consignment. It only goes on the books once every part is back under one roof.
+        println!("{BOLD}{YELLOW}Warehouse: Received part {} of {} of {} ({}kg).{RESET}", part.index, part.of, cargo.item, cargo.actual_weight);
+        let id = cargo.id;
+        let parts = self.partials.entry(id).or_default();
+        parts.push(cargo);
+        if parts.len() as u32 >= part.of && let Some(whole) = self.partials.remove(&id).and_then(Cargo::rejoin) {
+            println!("{BOLD}{GREEN}Warehouse: All {} parts of {} are in. Rejoined as one consignment ({}kg).{RESET}", part.of, whole.item, whole.actual_weight);
+            self.inventory.insert(id, whole);
+        }
+    }
+
+    /// The consignee collects whatever of these consignments we're holding. Returns how many went.
+    pub fn hand_over(&mut self, cargo_ids: &[u32]) -> usize {
+        cargo_ids.iter().filter(|id| self.inventory.remove(id).is_some()).count()
+    }
+
+    /// Spoiled cargo goes in the skip. It doesn't take up warehouse space, and it isn't going anywhere.
+    pub fn dispose(&mut self, cargo: Cargo) {
+        println!("{BOLD}{RED}Warehouse: {} (cargo {}) has spoiled. Into the skip it goes.{RESET}", cargo.item, cargo.id);
+        self.disposal.push(cargo);
+    }
+
+    /// Checks the shelves for perishables that have gone off while sitting here, and clears them into the skip.
+    /// A split consignment is only as good as its worst part, so one bad part takes the rest with it.
+    /// Returns the IDs of the consignments that were thrown out.
+    pub fn clear_spoiled(&mut self) -> Vec<u32> {
+        let spoiled_ids: Vec<u32> = self.inventory.iter_mut()
+            .filter_map(|(id, cargo)| cargo.age(false).then_some(*id))
+            .collect();
+        let spoiled_partials: Vec<u32> = self.partials.iter_mut()
+            .filter_map(|(id, parts)| {
+                let mut spoiled = false;
+                for part in parts.iter_mut() { // Every part gets aged, so no stopping at the first bad one
+                    spoiled |= part.age(false);
+                }
+                spoiled.then_some(*id)
+            })
+            .collect();
+
+        for id in &spoiled_ids {
+            if let Some(cargo) = self.inventory.remove(id) {
+                self.dispose(cargo);
+            }
+        }
+        for id in &spoiled_partials {
+            for part in self.partials.remove(id).unwrap_or_default() {
+                self.dispose(part);
+            }
         }
+        spoiled_ids.into_iter().chain(spoiled_partials).collect()
     }
 
-    pub fn store(&mut self, cargo: Cargo) {
-        println!("{BOLD}{YELLOW}Warehouse: Received {} ({}kg) for processing/holding.{RESET}", cargo.item, cargo.actual_weight);
-        self.inventory.insert(cargo.id, cargo);
+    /// Is this consignment still missing some of its parts?
+    pub fn is_incomplete(&self, cargo_id: u32) -> bool {
+        self.partials.contains_key(&cargo_id)
     }
 
     pub fn process_outbound(&mut self) {
@@ -528,7 +920,10 @@ impl Warehouse {
 
     pub fn get_cargo_by_id(&mut self, id: u32) -> Result<Cargo, TrainError> {
         match self.inventory.remove(&id) {
-            Some(cargo) => Ok(cargo),
+            Some(mut cargo) => {
+                cargo.age(false); // Charge its time on the shelf before it goes out. Whatever car it ends up in takes it from here.
+                Ok(cargo)
+            },
             None => Err(TrainError::MissingCargo { cargo_id: vec![id] }),
         }
     }
@@ -549,7 +944,7 @@ impl Warehouse {
             Ok(cargo)
         } else {
             for cargo_item in cargo {
-                self.store(cargo_item); // Rollback any cargo we successfully retrieved to save it from Rust's ownership wrath
+                self.squeeze_in(cargo_item); // Rollback any cargo we successfully retrieved to save it from Rust's ownership wrath (its space is still free)
             }
             Err(TrainError::MissingCargo { cargo_id: missing_ids }) // Return all missing IDs
         }
@@ -572,6 +967,64 @@ impl Warehouse {
 
 
 
+// What a customer walking in off the street asks for: a priority, and a delivery window (not before, not after).
+// Most are happy with Normal whenever. The odd one is in a hurry, or can't take it until their new shed is up.
+fn walk_in_terms() -> (Priority, Option<f64>, Option<f64>) {
+    let now = sim_hours_now();
+    match rand::thread_rng().gen_range(0..10) {
+        0 | 1 => (Priority::Low, None, None),
+        2 => (Priority::High, Some(now + 12.0), Some(now + 48.0)),
+        3 => (Priority::Urgent, None, Some(now + 24.0)),
+        _ => (Priority::Normal, None, None),
+    }
+}
+
+// The earliest freight can leave and still not get there before its window opens: the window's opening, less the trip
+// behind the fastest class of engine there is. None if it has no window to wait for.
+fn window_departure(not_before: Option<f64>, distance: f64) -> Option<f64> {
+    let opens = not_before?;
+    let top_speed = EngineType::roster().iter().map(|class| class.speed()).max()? as f64;
+    Some(opens - distance / top_speed)
+}
+
+// Urgent freight doesn't wait for company, and a mission that's already been parked or bumped has waited enough.
+fn worth_marshalling(mission: &Mission) -> bool {
+    mission.attempts == 0 && mission.parked_since.is_none() && mission.priority < Priority::Urgent
+}
+
+// Sorts freight leaving here into trains, one per line of route. Longest routes go first, so each one after can look
+// for a train already going past its stop.
+fn along_one_line<T>(mut entries: Vec<T>, route: impl Fn(&T) -> &[u32]) -> Vec<Vec<T>> {
+    entries.sort_by_key(|entry| std::cmp::Reverse(route(entry).len()));
+    let mut trains: Vec<Vec<T>> = Vec::new();
+    for entry in entries {
+        match trains.iter_mut().find(|members| route(&members[0]).starts_with(route(&entry))) {
+            Some(members) => members.push(entry),
+            None => trains.push(vec![entry]),
+        }
+    }
+    trains
+}
+
+// How urgent a parked mission is by now. Every PRIORITY_AGING_HOURS it has waited lifts it a level.
+fn aged_priority(mission: &Mission, now: f64) -> Priority {
+    let waited = mission.parked_since.map_or(0.0, |since| now - since);
+    mission.priority.raised((waited / PRIORITY_AGING_HOURS) as u32)
+}
+
+// The IDs of the split consignments riding in these cars, once each.
+fn split_cargo_ids<'a>(cars: impl IntoIterator<Item = &'a TrainCar>) -> Vec<u32> {
+    let mut ids: Vec<u32> = cars.into_iter()
+        .filter_map(|car| car.cargo.as_ref())
+        .filter(|cargo| cargo.part.is_some())
+        .map(|cargo| cargo.id)
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+
 // This is JUST data. No threads, no channels, no logic.
 pub struct StationMetadata {
     pub id: u32,
@@ -580,6 +1033,58 @@ pub struct StationMetadata {
 
 }
 
+// What a station has to work with, beyond its position on the map. Read from the network config.
+pub struct StationProfile {
+    pub id: u32,
+    pub name: String,
+    pub maintenance_shed: bool,
+    pub service_hours: f64,
+    pub engine_policy: EnginePolicyKind,
+    pub rebalance: RebalancePolicy,
+    pub target_mix: HashMap<EngineType, usize>,
+    pub car_budget: u32, // Money to spend on new cars when the neighbours can't spare any
+    pub passenger_services: Vec<PassengerService>, // The passenger trains that start here
+    pub timetable: Arc<Vec<ScheduledService>>,    // Every timetabled freight service on the railway, not just ours: freight can join one part way along
+    pub yard_capacity: usize,
+    pub roundhouse_capacity: usize,
+    pub warehouse_capacity: usize,
+    pub classification_hub: bool,
+    pub hub_departure_cars: usize,
+    pub hub_max_dwell_hours: f64,
+}
+
+/// How far a gossiped request may still spread, and who has already heard it. Handed on (stamped) from hop to hop.
+#[derive(Debug, Clone, Copy)]
+pub struct Gossip {
+    pub ttl: u32,
+    pub branch_notified: [u32; 64],
+    pub notified_count: usize,
+}
+
+impl Gossip {
+    /// A fresh request, starting at `requester_id`.
+    pub fn new(requester_id: u32, ttl: u32) -> Self {
+        Gossip { ttl, branch_notified: [requester_id; 64], notified_count: 1 }
+    }
+}
+
+/// What an engine has to be able to do for an engine request: pull this much (alongside what's on hand), on this route.
+/// Or, for a station topping up its target mix, be of this class.
+#[derive(Debug, Clone, Copy)]
+pub struct EngineNeeds {
+    pub min_capacity: f64,
+    pub paired_capacity: f64,
+    pub max_hop: f64,
+    pub max_gradient: f64,
+    pub class: Option<EngineType>,
+}
+
+impl Default for StationProfile {
+    fn default() -> Self {
+        StationProfile { id: 0, name: String::new(), maintenance_shed: false, service_hours: DEFAULT_SERVICE_HOURS, engine_policy: EnginePolicyKind::default(), rebalance: RebalancePolicy::default(), target_mix: HashMap::new(), car_budget: DEFAULT_CAR_BUDGET, passenger_services: Vec::new(), timetable: Arc::new(Vec::new()), yard_capacity: DEFAULT_YARD_CAPACITY, roundhouse_capacity: DEFAULT_ROUNDHOUSE_CAPACITY, warehouse_capacity: DEFAULT_WAREHOUSE_CAPACITY, classification_hub: false, hub_departure_cars: DEFAULT_HUB_DEPARTURE_CARS, hub_max_dwell_hours: DEFAULT_HUB_MAX_DWELL_HOURS }
+    }
+}
+
     pub struct Station {
         // pub id: u32,
         // pub name: String,
@@ -592,14 +1097,26 @@ pub struct StationMetadata {
 
 
 impl Station {
-    pub fn new(id: u32, name: &str, neighbors: HashMap<u32, Sender<StationCommand>>, tx: Sender<StationCommand>, map: Arc<RailwayNetwork>, ledger: Arc<Mutex<GlobalLedger>>, rx: Receiver<StationCommand>) {
+    pub fn new(profile: StationProfile, neighbors: HashMap<u32, Sender<StationCommand>>, tx: Sender<StationCommand>, map: SharedNetwork, ledger: Arc<Mutex<GlobalLedger>>, signal_box: SharedSignalBox, rx: Receiver<StationCommand>) {
         // Create a channel for this station
         // instantiate roundhouse, yard, and warehouse, and copy station name, before moving them into the thread
-        let station_name = String::from(name);
-        let station_id = id;
+        let station_name = profile.name.clone();
+        let station_id = profile.id;
         let tx = tx; // The station's own Sender for receiving commands
 
-        let mut state = StationState::new(id, station_name.clone(), neighbors, map.clone(), ledger.clone(), tx.clone());
+        let mut state = StationState::new(&profile, neighbors, map.clone(), ledger.clone(), signal_box, tx.clone());
+
+        // The heartbeat. Wakes the station up every so often to let engines out of the shed and retry parked missions.
+        // It stops by itself once the station's mailbox is gone.
+        let heartbeat_tx = tx.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(HEARTBEAT_INTERVAL);
+                if heartbeat_tx.send(StationCommand::CheckStatus).is_err() {
+                    break;
+                }
+            }
+        });
         // Spawn a thread to run the station's internal loop
         thread::spawn(move || {
             // The station's internal state
@@ -615,16 +1132,39 @@ impl Station {
                         state.handle_receive_train(train, reply_to);
                     },
 
-                    StationCommand::HandleEmergencySOS { mission_id, destination, surviving_cars, report_to } => {
-                        state.handle_emergency_sos(mission_id, destination, surviving_cars, report_to);
+                    StationCommand::PassengersArrive { passengers } => {
+                        state.handle_passengers_arrive(passengers);
+                    },
+
+                    StationCommand::RunService { service_id, run_id } => {
+                        state.handle_run_service(service_id, run_id);
+                    },
+
+                    StationCommand::BlockCleared { from, to } => {
+                        state.handle_block_cleared(from, to);
+                    },
+
+                    StationCommand::ResolveDeadlock { train_id } => {
+                        state.handle_resolve_deadlock(train_id);
+                    },
+
+                    StationCommand::HandleBreakdown { train, engine_id } => {
+                        state.handle_breakdown(train, engine_id);
+                    },
+
+                    StationCommand::HandleEmergencySOS { mission_id, destination, priority, deadline, surviving_cars, report_to } => {
+                        state.handle_emergency_sos(mission_id, destination, priority, deadline, surviving_cars, report_to);
                     },
 
                     StationCommand::IntakeCar { cars, reply_to } => {
-                       state.handle_intake_cars(cars, Some(reply_to));
+                       state.handle_intake_cars(cars, Some(reply_to), false);
                     },
                     StationCommand::IntakeCargo { cargo, reply_to } => {
                         state.handle_intake_cargo(cargo, Some(reply_to));
                     },
+                    StationCommand::IntakeConsignment { cargo, destination, priority, not_before, deadline, reply_to } => {
+                        state.handle_intake_consignment(cargo, destination, priority, not_before, deadline, Some(reply_to));
+                    },
                     StationCommand::IntakeEngine { engine, reply_to } => {
                         println!("{BOLD}{CYAN}[{}] Received command to intake a new engine into the roundhouse.{RESET}", station_name);
                         state.handle_intake_engine(engine, Some(reply_to));
@@ -632,18 +1172,61 @@ impl Station {
                     StationCommand::NewNeighbor { neighbor, neighbor_tx } => {
                         state.handle_new_neighbor(neighbor, neighbor_tx);
                     },
-                    StationCommand::RequestEmptyCars { count } => {
-                        state.handle_request_empty_cars(count);
+                    StationCommand::TrackClosed { neighbor } => {
+                        state.handle_track_closed(neighbor);
+                    },
+                    StationCommand::TrackReopened { neighbor, neighbor_tx } => {
+                        state.handle_track_reopened(neighbor, neighbor_tx);
+                    },
+                    StationCommand::TransferCargo { cargo } => {
+                        state.handle_transfer_cargo(cargo);
+                    },
+                    StationCommand::WatchBackhauls { reports } => {
+                        state.backhauls.extend(reports);
+                    },
+                    StationCommand::TransferCars { cars } => {
+                        state.handle_intake_cars(cars, None, true);
+                    },
+                    StationCommand::Decommission { heirs, reply_to } => {
+                        if !state.handle_decommission(heirs, reply_to) {
+                            continue; // We refused, so we're still open for business.
+                        }
+                        println!("{BOLD}{RED}[{}]::Station {}: Decommissioned. Lights out.{RESET}", station_name, station_id);
+                        break; // Nothing left to run. Any train still on its way here will bounce back to where it came from.
+                    },
+                    StationCommand::EmptyCarRequest { requester_id, request_id, car_class, count, ttl, branch_notified, notified_count } => {
+                        state.handle_empty_car_request(requester_id, request_id, car_class, count, Gossip { ttl, branch_notified, notified_count });
+                    }
+                    StationCommand::BuildCars { car_class, count } => {
+                        state.handle_build_cars(car_class, count);
                     }
-                    StationCommand::EngineRequest { requester_id, request_id, mission_id, min_capacity, mission_max_hop, ttl, branch_notified, notified_count } => {
-                        state.handle_engine_request(requester_id, request_id, mission_id, min_capacity, mission_max_hop, ttl, branch_notified, notified_count);
+                    StationCommand::EngineRequest { requester_id, request_id, mission_id, min_capacity, paired_capacity, mission_max_hop, mission_max_gradient, class, ttl, branch_notified, notified_count } => {
+                        let needs = EngineNeeds { min_capacity, paired_capacity, max_hop: mission_max_hop, max_gradient: mission_max_gradient, class };
+                        state.handle_engine_request(requester_id, request_id, mission_id, needs, Gossip { ttl, branch_notified, notified_count });
                     }
                     StationCommand::EngineRequestResponse { request_id, station_id, engine } => {
                         //TODO: We need to know which mission this is for so we can route the engine to the right place once we get it. We can add that to the command if needed.
                     }
                     StationCommand::CheckStatus => {// The Alarm Clock: station sends to itself every X seconds to trigger regular status checks and maintenance tasks like checking pending missions, gossiping about engines, etc.
-                        println!("{BOLD}{CYAN}[{}]::Station {}: Checking pending missions...{RESET}", station_name, station_id);
+                        let released = state.roundhouse.release_serviced();
+                        if released > 0 {
+                            println!("{BOLD}{GREEN}[{}]::Station {}: {} engine(s) back from the shed.{RESET}", station_name, station_id, released);
+                        }
+                        state.check_perishables(); // Before the retries, so nothing sets off with a load that's gone off
+                        state.release_held_for_window();
+                        state.marshal_trains();
+                        state.make_up_outbound_trains();
                         state.check_pending_missions();
+                        state.run_passenger_services();
+                        state.check_timetable();
+                        state.retry_signal_queue(); // In case a BlockCleared went astray
+                        state.collect_deliveries();
+                        state.admit_held_trains();
+                        state.send_engines_for_service();
+                        state.check_backhaul_reports();
+                        state.find_backhauls(); // Before anyone is sent home light
+                        state.send_engines_home();
+                        state.top_up_target_mix();
                     }
                     StationCommand::PrintStatus => {
                         println!("{BOLD}{CYAN}[{}]::Station {}: Status Report Requested:{RESET}", station_name, station_id);
@@ -664,7 +1247,10 @@ impl Station {
 }
 
 
-
+pub type BookedMission = (String, f64, Mission);   // Service ID, the hour it leaves its origin, and the mission riding it
+pub type HeldRun = (ScheduledService, u32, f64);    // A timetabled run, its run ID, and since when it has waited for an engine
+pub type HeldAtSignal = (Train, Vec<u32>, f64);     // A train, its route, and since when it has waited at the signal
+pub type ClaimedBackhaul = (Receiver<MissionReport>, FreightOrder); // The report channel of an order we claimed ourselves, and the order
 
 pub struct StationState {
     pub id: u32,
@@ -673,11 +1259,31 @@ pub struct StationState {
     pub roundhouse: Roundhouse,
     pub warehouse: Warehouse,
     pub neighbors: HashMap<u32, Sender<StationCommand>>,
-    pub map: Arc<RailwayNetwork>,
+    pub map: SharedNetwork, // Shared behind a RwLock so the Fat Controller can close and reopen tracks while we run.
     pub ledger: Arc<Mutex<GlobalLedger>>,
     pub seen_engine_request: HashSet<u32>, // To prevent engine request loops, we keep track of which engine requests we've already seen and handled. The key is the mission ID. When we receive an engine request, we check this HashSet first. If we've already seen it, we ignore it to prevent infinite loops of stations passing the same request back and forth. If we haven't seen it, we mark it as seen and proceed with handling the request.
     pub tx: Sender<StationCommand>, // The Boomerang
     pub pending_missions: Vec<Mission>, // 
+    pub seen_car_request: HashSet<u32>, // Same loop protection as seen_engine_request, for empty-car requests.
+    pub empty_cars_requested_at: Option<f64>, // When we last asked the neighbours for empty cars. None if we're not waiting on anyone.
+    pub engines_requested_at: HashMap<EngineType, f64>, // When we last asked the neighbours for an engine of a class we're short of, for TargetMix
+    pub short_of_cars: HashSet<u32>, // The missions we're waiting on empty cars for. The clock above only stops once they've all got theirs.
+    pub car_budget: u32,
+    pub platform: Vec<Passenger>, // Waiting for a train that calls where they're going, first come first served
+    pub services: Vec<(PassengerService, f64)>, // Our passenger services, and the timetable hour each one next leaves
+    pub timetable: Arc<Vec<ScheduledService>>,
+    pub booked: Vec<BookedMission>,    // Missions waiting for a timetabled service: the service ID, and the timetable hour it leaves its origin
+    pub marshalling: Vec<(Mission, f64)>, // New missions waiting a little in case something else is going their way, and since when
+    pub held_for_window: Vec<(Mission, f64)>, // Missions whose delivery window opens too far off to set out yet, and the timetable hour each can leave
+    pub held_runs: Vec<HeldRun>, // Runs due away from here that are still waiting for an engine, with their run ID and since when
+    pub signal_box: SharedSignalBox,
+    pub signal_queue: VecDeque<HeldAtSignal>, // Trains held at the signal: the train, its route, and the timetable hour it started waiting
+    pub held_outside: VecDeque<(Train, f64)>, // Trains at our home signal that we've no room to unload, still in their block, and since when
+    pub awaiting_collection: Vec<u32>,        // Delivered cargo the consignees haven't picked up from the warehouse yet
+    pub backhauls: Vec<ClaimedBackhaul>,      // Orders we claimed for a backhaul. We watch for the report, like a Producer would.
+    pub classification_hub: bool,
+    pub hub_departure_cars: usize,
+    pub hub_max_dwell_hours: f64,
 }
 
 
@@ -691,19 +1297,40 @@ impl CanReport for StationState {
 
 
 impl StationState {
-    pub fn new(id: u32, name: String, neighbors: HashMap<u32, Sender<StationCommand>>, map: Arc<RailwayNetwork>, ledger: Arc<Mutex<GlobalLedger>>, tx: Sender<StationCommand>) -> Self {
+    pub fn new(profile: &StationProfile, neighbors: HashMap<u32, Sender<StationCommand>>, map: SharedNetwork, ledger: Arc<Mutex<GlobalLedger>>, signal_box: SharedSignalBox, tx: Sender<StationCommand>) -> Self {
+        let id = profile.id;
         StationState {
             id,
-            name,
-            yard: Railyard::new(id),
-            roundhouse: Roundhouse::new(id),
-            warehouse: Warehouse::new(id),
+            name: profile.name.clone(),
+            yard: Railyard::new(id, profile.yard_capacity),
+            roundhouse: Roundhouse::new(id, profile),
+            warehouse: Warehouse::new(id, profile.warehouse_capacity),
             neighbors,
             map,
             ledger,
             tx,
             seen_engine_request: HashSet::new(),
             pending_missions: Vec::new(),
+            seen_car_request: HashSet::new(),
+            empty_cars_requested_at: None,
+            engines_requested_at: HashMap::new(),
+            short_of_cars: HashSet::new(),
+            car_budget: profile.car_budget,
+            platform: Vec::new(),
+            services: profile.passenger_services.iter().filter(|service| service.every_hours > 0.0).map(|service| (service.clone(), sim_hours_now())).collect(),
+            timetable: Arc::clone(&profile.timetable),
+            booked: Vec::new(),
+            marshalling: Vec::new(),
+            held_for_window: Vec::new(),
+            held_runs: Vec::new(),
+            signal_box,
+            signal_queue: VecDeque::new(),
+            held_outside: VecDeque::new(),
+            awaiting_collection: Vec::new(),
+            backhauls: Vec::new(),
+            classification_hub: profile.classification_hub,
+            hub_departure_cars: profile.hub_departure_cars,
+            hub_max_dwell_hours: profile.hub_max_dwell_hours,
         }
     }
 
@@ -711,20 +1338,26 @@ impl StationState {
     // The VIP Pass is `&mut self`. This allows the method to open its own briefcase!
     pub fn handle_assemble_mission(
         &mut self, 
-        mission: Mission, 
+        mut mission: Mission, 
         //distance: f64, 
         //route: Vec<String>, 
         //destination: String, 
         //reply_to: Sender<Result<Train, TrainError>>
     ) {
         println!("{BOLD}{CYAN}[{}] Received command to assemble mission {}.{RESET}", self.name, mission.id);
+        // Parked, marshalling or booked while its destination closed. It goes to the heir, with the rest of that station's freight.
+        if let Some(heir) = self.ledger.lock().unwrap().forwarding_address(mission.destination) {
+            println!("{YELLOW}[{}] Station {} has closed. Mission {} now goes to Station {}.{RESET}", self.name, mission.destination, mission.id, heir);
+            mission.destination = heir;
+        }
         
         // 1. Paste your assemble_train logic here!
         // First, we need to use our map! Choo choo!
 
 
 
-        let (distance, route) = match self.map.find_shortest_path(self.id, mission.destination) {
+        let path = self.map.read().unwrap().find_shortest_path(self.id, mission.destination);
+        let (distance, route) = match path {
             Some((d, r)) => {
                 println!(
                     "{YELLOW}Network: Shortest path for Mission {} is {} km via {:?}.{RESET}",
@@ -735,6 +1368,7 @@ impl StationState {
             None => {
                 println!("{RED}Network Error: No track laid between {} and {}.{RESET}", self.name, mission.destination);
                 let error = TrainError::MissionImpossible { reason: "Destination unreachable".to_string() };
+                self.no_longer_short(mission.id);
               
                 match mission.reply_channel {
                     Some(sender) => {
@@ -759,6 +1393,29 @@ impl StationState {
 
         // At this point, we have the distance and route calculated, so we can proceed with the assembly logic using these values.
 
+        // The customer can't take it yet. Even the fastest engine on the railway, leaving now, would get there before the window
+        // opens, so it waits here until it can leave and get there no sooner.
+        if let Some(leave_at) = window_departure(mission.not_before, distance) && sim_hours_now() < leave_at {
+            println!("{CYAN}[{}] Mission {} can't be taken before {:.1}h. It waits here until {:.1}h.{RESET}", self.name, mission.id, mission.not_before.unwrap_or_default(), leave_at);
+            self.held_for_window.push((mission, leave_at));
+            return;
+        }
+
+        // Unless there's a timetabled service going that way soon. Then the freight rides on that instead of getting a train of its own.
+        // Only on the first try: a mission that was bumped off a service (or never picked up) goes the old-fashioned way.
+        if mission.attempts == 0 && let Some((service_id, departs)) = self.find_service_for(&mission) {
+            println!("{CYAN}[{}] Mission {} is booked on Service {}, due away from its origin at {:.1}h.{RESET}", self.name, mission.id, service_id, departs);
+            self.booked.push((service_id, departs, mission));
+            return;
+        }
+
+        // Otherwise it waits a little in the marshalling sidings, in case something else is going the same way and they can share.
+        if worth_marshalling(&mission) {
+            println!("{CYAN}[{}] Mission {} waits in the marshalling sidings in case anything else is going its way.{RESET}", self.name, mission.id);
+            self.marshalling.push((mission, sim_hours_now()));
+            return;
+        }
+
         // Now for the fun part: we're going to completely rewrite assemble_train as part of the Station's responsibilities, because the Station is now the mastermind behind the whole operation, and it needs to have access to its internal state (the yard and roundhouse) to pull this off. The network is just a map and dispatcher, so it makes more sense for the Station to handle the assembly logic directly.
 
         println!("{BOLD}{CYAN}[{}]::Station {}: Starting assembly for Mission {}: {}kg to {} via {:?}.{RESET}", self.name, self.id, mission.id, mission.cargo_ids.len(), mission.destination, route);
@@ -771,6 +1428,7 @@ impl StationState {
             Err(e) => {
                 println!("{RED}Yard Error: Failed to calculate total cargo weight for Mission {}: {:?}.{RESET}", mission.id, e);
                 
+                self.no_longer_short(mission.id);
                 let details = "Failed to calculate total cargo weight. This likely means that one or more cargo items specified in the mission's cargo_ids are missing from the warehouse inventory. The warehouse is responsible for keeping track of all cargo and their weights, so if it cannot provide the total weight, it indicates a critical issue with the inventory management. This failure prevents us from determining whether we have a suitable engine available in the roundhouse, which is essential for proceeding with the assembly of the train. Please investigate the warehouse inventory and ensure that all cargo items for this mission are properly stored and accounted for.";
                 self.report_mission_failure(&mission, details);
                 //self.report_mission_failure(&mission, &format!("Failed to calculate total cargo weight: {:?}", e));
@@ -778,54 +1436,84 @@ impl StationState {
             }
         };
 
-        // 2. Mathematically project the final gross weight of the train!
-        let num_cars_needed = mission.cargo_ids.len() as u32;
-        let empty_cars_weight = num_cars_needed * 2000;
-        let true_total_weight = total_cargo_weight + empty_cars_weight;
+        // 2. Work out which cars the cargo will ride in. A tanker weighs more than a flatbed, so we need this before we can weigh the train.
+        // Before we even try to find an engine, let's check if we have enough (of the right kind of) empty cars in the yard to load all the cargo.
+        let manifest: Vec<&Cargo> = mission.cargo_ids.iter().filter_map(|id| self.warehouse.inventory.get(id)).collect();
+        let (empty_cars_weight, _, unmatched) = self.yard.plan_loading(&manifest);
+        // Cargo that no kind of car on the railway could carry. No point asking the neighbours for that.
+        let unloadable = unmatched.iter()
+            .find(|(item, left_over)| CarClass::for_load(item.category, *left_over).is_none())
+            .map(|(item, _)| TrainError::NoCompatibleCar { cargo_id: item.id, category: item.category, weight: item.actual_weight });
+        let mut shortfall: HashMap<CarClass, u32> = HashMap::new();
+        for (class, count) in unmatched.iter().filter_map(|(item, left_over)| CarClass::for_load(item.category, *left_over)) {
+            *shortfall.entry(class).or_default() += count;
+        }
 
-        println!("{YELLOW}Warehouse: Total projected gross weight for Mission {} is {}kg ({}kg cargo + {}kg rolling stock).{RESET}", 
-            mission.id, true_total_weight, total_cargo_weight, empty_cars_weight);
+        if let Some(error) = unloadable {
+            println!("{RED}Yard Error: Mission {}: {}.{RESET}", mission.id, error);
+            self.no_longer_short(mission.id);
+            self.report_mission_failure(&mission, &format!("{:?}", error));
+            return;
+        }
 
-        // Before we even try to find an engine, let's check if we have enough empty cars in the yard to load all the cargo. 
-        match self.yard.validate_empty_cars(&mission) {
-            true => println!("{GREEN}Yard: Validation successful for Mission {}. Enough empty cars available.{RESET}", mission.id),
-            false => {
-                println!("{RED}Yard Error: Validation failed for Mission {}. Not enough empty cars available.{RESET}", mission.id);
-                //let error = TrainError::MissionImpossible { reason: "Not enough empty cars available".to_string() };
-                let details = "Not enough empty cars available for the mission. This indicates a shortage in the yard's inventory of empty cars, which is critical for assembling the train. Please investigate the yard's inventory and ensure that sufficient empty cars are available for upcoming missions.";
+        if shortfall.is_empty() {
+            println!("{GREEN}Yard: Validation successful for Mission {}. Enough empty cars available.{RESET}", mission.id);
+            self.no_longer_short(mission.id);
+        } else {
+            println!("{RED}Yard Error: Validation failed for Mission {}. Short of empty cars: {:?}.{RESET}", mission.id, shortfall);
+            self.short_of_cars.insert(mission.id);
+            let build_cost: u32 = shortfall.iter().map(|(class, count)| class.build_cost() * count).sum();
 
-                
-                // self.tx.send(StationCommand::RequestEmptyCars { count: mission.cargo_ids.len() as u32 }).unwrap_or_else(|e| {
-                //     println!("{RED}[{}] DEAD-LETTER: Failed to send request for empty cars for mission {} due to yard validation failure.{RESET}", self.name, mission.id);
-                // });
-                match self.tx.send(StationCommand::RequestEmptyCars { count: mission.cargo_ids.len() as u32 }) {
-                    Ok(_) => println!("{YELLOW}[{}]::Station {}: Sent request for {} empty cars to yard due to validation failure for Mission {}.{RESET}", self.name, self.id, mission.cargo_ids.len(), mission.id),
-                    Err(e) => println!("{RED}[{}] DEAD-LETTER: Failed to send request for empty cars for mission {} due to yard validation failure. Error: {:?}{RESET}", self.name, mission.id, e),
-                }
+            // Cars don't grow on trees. First we ask the neighbours. If nobody has sent any by the time our patience runs out,
+            // we pay the works to build them. Only when the money is gone too do we give the mission back to the producer.
+            match self.empty_cars_requested_at {
+                None => {
+                    self.empty_cars_requested_at = Some(sim_hours_now());
+                    for (class, count) in shortfall {
+                        let request_id = self.yard.generate_new_request_id();
+                        self.initiate_empty_car_request(request_id, class, count, 8);
+                    }
+                },
+                Some(asked_at) if sim_hours_now() - asked_at < CAR_REQUEST_PATIENCE_HOURS => {
+                    println!("{YELLOW}[{}] Still waiting on the neighbours for empty cars: {:?}.{RESET}", self.name, shortfall);
+                },
+                Some(_) if self.car_budget >= build_cost => {
+                    println!("{YELLOW}[{}] Nobody could spare {:?}. Ordering new cars from the works.{RESET}", self.name, shortfall);
+                    self.empty_cars_requested_at = Some(sim_hours_now()); // The works are next in our mailbox. Don't pester the neighbours meanwhile.
+                    for (car_class, count) in shortfall {
+                        if let Err(e) = self.tx.send(StationCommand::BuildCars { car_class, count }) {
+                            println!("{RED}[{}] DEAD-LETTER: Failed to order empty cars for mission {}. Error: {:?}{RESET}", self.name, mission.id, e);
+                        }
+                    }
+                },
+                Some(_) => {
+                    let details = "Not enough empty cars available for the mission, none to borrow from the neighbours, and no money left to build more.";
+                    self.no_longer_short(mission.id);
+                    self.report_mission_failure(&mission, details);
+                    return;
+                },
+            }
 
+            self.park_mission(mission);
+            return;
+        }
 
+        // 3. Mathematically project the final gross weight of the train!
+        let true_total_weight = total_cargo_weight + empty_cars_weight;
 
-                self.report_mission_failure(&mission, details);
-                return;
-            }
-        }
+        println!("{YELLOW}Warehouse: Total projected gross weight for Mission {} is {}kg ({}kg cargo + {}kg rolling stock).{RESET}", 
+            mission.id, true_total_weight, total_cargo_weight, empty_cars_weight);
 
         // Knowing max hop distance is crucial for the engine selection. It ensures the engine's fuel capacity can handle the longest stretch of track without refueling.
-        let mut max_hop_distance = 0.0;
-        for i in 0..route.len() - 1 {
-            if let Some(dist) = self.map.get_distance(route[i], route[i+1]) {
-                if dist > max_hop_distance {
-                    max_hop_distance = dist;
-                }
-            }
-        }
+        // The steepest climb matters too: a bank can stall an engine that would sail across the flat.
+        let (max_hop_distance, max_hop_gradient) = self.map.read().unwrap().route_profile(&route);
 
         
         //let strict_mode = true; // Default is strictly "Goldilocks" - we will only accept an engine that is a perfect match for the mission's needs. If we don't have the perfect engine, we ask for help instead of just taking the next best thing. If pending missions becomes backlogged, we can consider relaxing this to allow for "overqualified" engines to take on missions that are below their ideal capacity, but for now we want to focus on the Goldilocks strategy to really test the engine request and network collaboration features.
 
         // Now we can finally check the roundhouse for a suitable engine, using the total weight and distance to determine which engines are capable of fulfilling this mission.
-        let engine = match self.roundhouse.find_suitable_engine(true_total_weight as f64, max_hop_distance) { // We use the max_hop_distance for the engine check because that's the longest single stretch of track the engine's fuel needs to cover
-            Ok(engine) => engine,
+        let engines = match self.roundhouse.find_suitable_consist(true_total_weight as f64, max_hop_distance, max_hop_gradient) { // We use the max_hop_distance for the engine check because that's the longest single stretch of track the engine's fuel needs to cover
+            Ok(engines) => engines,
             Err(e) => {
                 println!("{RED}Roundhouse {} Error: Failed to find suitable engine for Mission {}: {:?}.{RESET}", self.id, mission.id, e);
                 
@@ -834,9 +1522,13 @@ impl StationState {
                 
                 // let details = "No suitable engines available for the mission. This indicates that the roundhouse does not have any engines that are capable of handling the required total weight and distance for this mission. Please investigate the roundhouse inventory and ensure that sufficient engines are available and properly maintained for upcoming missions.";
                 // self.report_mission_failure(&mission, details);
-                self.pending_missions.push(mission);
+                self.park_mission(mission);
 
-                self.initiate_engine_request(self.id, request_id, Some(mission_id), true_total_weight as f64, max_hop_distance, 8); // We can set a TTL of 8 to allow the request to propagate through the network without risking infinite loops. This gives enough time for neighboring stations to check their roundhouses and respond if they have a suitable engine, while also ensuring that the request doesn't bounce around indefinitely if no suitable engines are available in the network.
+                // If we've got an engine that's just not strong enough on its own, we only need a partner for it, not a whole new engine.
+                let on_hand = self.roundhouse.strongest_capacity();
+                let paired_capacity = if on_hand > 0.0 && on_hand < true_total_weight as f64 { on_hand } else { 0.0 };
+
+                self.initiate_engine_request(self.id, request_id, Some(mission_id), EngineNeeds { min_capacity: true_total_weight as f64, paired_capacity, max_hop: max_hop_distance, max_gradient: max_hop_gradient, class: None }, 8); // We can set a TTL of 8 to allow the request to propagate through the network without risking infinite loops. This gives enough time for neighboring stations to check their roundhouses and respond if they have a suitable engine, while also ensuring that the request doesn't bounce around indefinitely if no suitable engines are available in the network.
 
                 // for neighbor in self.neighbors.values() {
                 //     match neighbor.send(StationCommand::EngineRequest { 
@@ -873,8 +1565,10 @@ impl StationState {
             Ok(cars) => cars,
             Err(e) => {
                 println!("{RED}Yard Error: Failed to assemble cars for Mission {}: {:?}.{RESET}", mission.id, e);
-                // Since we already took the engine out of the roundhouse, we need to return it back to avoid losing it due to a failed assembly!
-                self.roundhouse.house(engine);
+                // Since we already took the engines out of the roundhouse, we need to return them back to avoid losing them due to a failed assembly!
+                for engine in engines {
+                    self.house_engine(engine);
+                }
                 // if reply_to.send(Err(e)).is_err() {
                 //     println!("{RED}[{}] DEAD-LETTER: Failed to send assembly failure for mission {} due to car assembly error.{RESET}", self.name, mission.id);
                 // }
@@ -903,13 +1597,21 @@ impl StationState {
 
         let train = Train {
             id: self.yard.generate_new_train_id(),
-            engine,
+            engines,
             cars: attached_cars,
             mission_id: Some(mission.id), // We can include the whole mission in the train for easy access to all its details during transit and at the destination, which will be helpful for reporting and any potential issues that arise during the journey.
             destination: mission.destination.clone(),
             report_to: mission.reply_channel.clone(),
+            service: false,
+            schedule: None,
+            riders: Vec::new(),
+            highpriority: mission.highpriority,
+            priority: mission.priority,
+            deadline: mission.deadline,
+            block: None,
         };
 
+        self.plan_arrival(mission.id, distance, &train);
         self.dispatch_train(train, route);
     }
 
@@ -917,45 +1619,156 @@ impl StationState {
 pub fn retry_pending_missions(&mut self) {
     // 1. Drain the HashMap into a local Vector. 
     // The moment this line finishes, `self.pending_missions` is empty and un-borrowed!
-    let parked_missions: VecDeque<Mission> = self.pending_missions.drain(..).collect();
+    let mut parked_missions: Vec<Mission> = self.pending_missions.drain(..).collect();
+
+    // 2. Anything that's waited two days hasn't got an engine or cars coming. It goes back to its Producer as a failure
+    // rather than sitting here forever, and the Producer tries it again while the order has tries left.
+    let now = sim_hours_now();
+    let (expired, waiting): (Vec<Mission>, Vec<Mission>) = parked_missions.into_iter()
+        .partition(|mission| mission.parked_since.is_some_and(|since| now - since >= PARKED_MISSION_LIMIT_HOURS));
+    parked_missions = waiting;
+    for mission in expired {
+        println!("{RED}[{}] Mission {} has been parked for {:.0}h. Giving it back to its Producer.{RESET}", self.name, mission.id, PARKED_MISSION_LIMIT_HOURS);
+        self.no_longer_short(mission.id);
+        let reason = format!("Parked for {:.0}h and no engine or cars came for it.", PARKED_MISSION_LIMIT_HOURS);
+        self.send_failure_report(mission.id, &reason, mission.reply_channel);
+    }
+
+    // 3. Most urgent first, so they get first pick of the engines and cars. Long-parked work has climbed the ladder by now,
+    // and among equals the one that has waited longest goes first.
+    parked_missions.sort_by(|a, b| {
+        by_urgency((aged_priority(a, now), a.deadline), (aged_priority(b, now), b.deadline))
+            .then_with(|| a.parked_since.unwrap_or(now).total_cmp(&b.parked_since.unwrap_or(now)))
+    });
 
-    // 2. Feed them right back into the funnel!
+    // 4. Feed them right back into the funnel!
     for mission in parked_missions {
-        println!("{YELLOW}[{}]{RESET} Retrying parked mission {}...", self.name, mission.id);
+        println!("{YELLOW}[{}]{RESET} Retrying parked mission {} ({:?}, now {:?})...", self.name, mission.id, mission.priority, aged_priority(&mission, now));
         self.handle_assemble_mission(mission);
     }
 }
 
+    // Parks a mission until an engine or cars turn up. The clock for its climb up the priority ladder starts the first time.
+    fn park_mission(&mut self, mut mission: Mission) {
+        mission.parked_since.get_or_insert_with(sim_hours_now);
+        self.pending_missions.push(mission);
+    }
+
 
     pub fn handle_receive_train(&mut self, mut train: Train, reply_to: Sender<Result<(), TrainError>>) {
         let _ = reply_to.send(Ok(())); // Send success back to transit thread so it can terminate.
-        train.engine.refuel(); // Refuel the engine upon arrival to ensure it's ready for the next leg of the journey or for disassembly if this is the final destination.
+        train.engines.iter_mut().for_each(Engine::refuel); // Refuel the engines upon arrival to ensure they're ready for the next leg of the journey or for disassembly if this is the final destination.
         //println!("{:?}", train);
         println!("{GREEN}[{}]::Station {}: Processing arrival of Train {}.{RESET}", self.name, self.id, train.id);
+        // No room to unload it? It waits at the home signal, still in its block, so anything behind it waits at the signal before.
+        if let Some(shortfall) = self.no_room_for(&train) {
+            println!("{YELLOW}[{}] No room for Train {} ({}). Holding it outside the station.{RESET}", self.name, train.id, shortfall);
+            self.hold_outside(train, sim_hours_now());
+            return;
+        }
+        self.admit_train(train, reply_to);
+    }
+
+    // Stops a train at the home signal, and puts it in the wait-for graph like any other held train, so trains queued behind
+    // it in the block can be seen waiting on it. It's waiting for room rather than a block; a train going further will want
+    // the block to its next stop after that, and one that stops here wants nothing else, so it has no edges of its own.
+    fn hold_outside(&mut self, train: Train, since: f64) {
+        let next_stop = if train.destination == self.id {
+            self.id
+        } else {
+            self.map.read().unwrap().find_shortest_path(self.id, train.destination).and_then(|(_, route)| route.get(1).copied()).unwrap_or(self.id)
+        };
+        let mut signal_box = self.signal_box.lock().unwrap();
+        signal_box.hold(train.id, HeldTrain { station: self.id, wants: (self.id, next_stop), holds: train.block, behind: None, highpriority: train.highpriority, since, station_tx: self.tx.clone() });
+        if let Some((deadlock, victim_tx)) = signal_box.detect_deadlock() {
+            let _ = victim_tx.send(StationCommand::ResolveDeadlock { train_id: deadlock.victim });
+        }
+        drop(signal_box);
+        self.held_outside.push_back((train, since));
+    }
+
+    // The train is in the station, and whatever it's leaving here has somewhere to go.
+    fn admit_train(&mut self, mut train: Train, reply_to: Sender<Result<(), TrainError>>) {
+        self.readdress_for_closures(&mut train);
+        // In off the block. A train going further from a station with no passing loop has nowhere to wait but the main line, so it keeps the block until it leaves.
+        let standing_on_main_line = train.destination != self.id && !self.signal_box.lock().unwrap().has_passing_loop(self.id);
+        if !standing_on_main_line {
+            self.release_block(&mut train);
+        }
+        if self.breaks_up(&train) {
+            self.break_up_at_hub(train);
+            return;
+        }
+        if train.schedule.is_some() {
+            self.handle_scheduled_arrival(train);
+            return;
+        }
         let final_destination = train.destination;
         let current_location = self.id;
         let station_tx_clone = self.tx.clone(); // Clone the station's own Sender for use in this method, so we can send SOS if needed
 
+        // A passenger train calling on its way through: anyone for here gets off. (At the terminus everyone gets off with the cars.)
+        if train.service && current_location != final_destination {
+            self.alight_passengers(&mut train.cars, false);
+        }
+
         if current_location == final_destination {
             // TODO: Check to see if the train only has an engine and no cars. It's an engine_request response. We need to notify . . . who exactly, Polaris?
             println!("{GREEN}[{}]::Station {}: Train {} has reached its final destination! Unloading...{RESET}", self.name, self.id, train.id);
+            self.set_down_riders(&mut train, true); // Anything it picked up on the way comes off first, and is reported on its own
             //crack the egg
-            let ( engine, cars, mission_id, report_to) = (train.engine,train.cars, train.mission_id, train.report_to); // Destructure the "Gestalt"
+            let ( engines, mut cars, mission_id, report_to, service) = (train.engines,train.cars, train.mission_id, train.report_to, train.service); // Destructure the "Gestalt"
             //let num_cars = cars.len();
             //let failed_ids: Vec<u32>; // We can fill this with any issues that arise during disassembly, and then include it in the MissionReport for transparency and debugging. For now, we'll just keep it empty to represent a perfect disassembly.
             // 1. Return the Power
             //engine.refuel(); // We can refuel the engine before returning it to the roundhouse
-            self.roundhouse.house(engine);
+            for engine in engines {
+                self.house_engine(engine);
+            }
             // 2. Return the Cars
+            let delivered_cars = cars.len();
+            let spoiled = self.dispose_spoiled_loads(&mut cars);
+            let anything_left = cars.iter().any(|car| car.cargo.is_some()); // If it all went off, it was never really delivered
+            if mission_id.is_some() {
+                self.awaiting_collection.extend(cars.iter().filter_map(|car| car.cargo.as_ref().map(|cargo| cargo.id)));
+            }
+            let split_ids = split_cargo_ids(&cars);
             let failed_ids = self.process_cars(cars, mission_id); // We can extract this logic into a separate method to keep things cleaner, and it can return the ledger of any failed cars for reporting.
+            // A split consignment only counts as delivered once all its parts have been rejoined.
+            let incomplete: Vec<u32> = split_ids.into_iter().filter(|id| self.warehouse.is_incomplete(*id)).collect();
+            let late_by = mission_id.filter(|_| anything_left || spoiled.is_empty()).and_then(|id| self.record_delivery(id));
 
-            if failed_ids.is_empty() {
-                
-                let details = "Successfully disassembled train and processed all cargo without issues.";
-                self.send_success_report(mission_id.expect("Unloading cargo after receiving a train implies a Mission is involved! There should be an id!"), details, report_to);
-            } else {
-                let details = "Partial success during train disassembly. Some items in purgatory.";
-                 self.send_partial_failure_report(mission_id.expect("Unloading cargo after receiving a train implies a Mission is involved! There should be an id!"), details, &failed_ids, report_to);
+            match mission_id {
+                None if service => {
+                    println!("{GREEN}[{}]::Station {}: Passenger train {} terminates here. All change!{RESET}", self.name, self.id, train.id);
+                },
+                None if delivered_cars > 0 => {
+                    // Empty cars lent by a neighbour. The parked missions below will snap them up.
+                    println!("{GREEN}[{}]::Station {}: Train {} delivered {} empty car(s).{RESET}", self.name, self.id, train.id, delivered_cars);
+                },
+                None => {
+                    // A light engine coming home. Nobody is waiting on a report.
+                    println!("{GREEN}[{}]::Station {}: Light engine move {} is home.{RESET}", self.name, self.id, train.id);
+                },
+                Some(mission_id) if !spoiled.is_empty() => {
+                    let details = format!("Cargo {:?} had spoiled by the time it arrived and has gone for disposal.", spoiled);
+                    self.send_partial_failure_report(mission_id, &details, &failed_ids, report_to);
+                },
+                Some(mission_id) if failed_ids.is_empty() && incomplete.is_empty() => {
+                    let details = "Successfully disassembled train and processed all cargo without issues.";
+                    match late_by {
+                        Some(hours_late) => self.send_late_report(mission_id, details, hours_late, report_to),
+                        None => self.send_success_report(mission_id, details, report_to),
+                    }
+                },
+                Some(mission_id) if !incomplete.is_empty() => {
+                    let details = format!("Consignment(s) {:?} arrived with parts missing and can't be rejoined.", incomplete);
+                    self.send_partial_failure_report(mission_id, &details, &failed_ids, report_to);
+                },
+                Some(mission_id) => {
+                    let details = "Partial success during train disassembly. Some items in purgatory.";
+                    self.send_partial_failure_report(mission_id, details, &failed_ids, report_to);
+                },
             }
 
             self.print_status();
@@ -986,26 +1799,32 @@ pub fn retry_pending_missions(&mut self) {
             let mission_id = train.mission_id;
             let final_destination = train.destination;
             let current_location = self.id;
-            let (distance, route) = match self.map.find_shortest_path(current_location, final_destination) {
+            let shortest_path = self.map.read().unwrap().find_shortest_path(current_location, final_destination);
+            let route = match shortest_path {
                 Some((d, r)) => {
                     println!(
                         "{YELLOW}Network: Shortest path for Mission {} Train {} to final destination {} is {} km via {:?}.{RESET}",
                         mission_id.unwrap_or(0), train.id, final_destination, d, r
                     );
-                    (d, r)
+                    r
                 },
                 None => {
                     println!("{RED}Network Error: No track laid between {} and {}. Cannot forward train.{RESET}", self.name, final_destination);
                     // --- THE VOID PATCH: Salvage Operation ---
-                    self.roundhouse.house(train.engine);
+                    self.release_block(&mut train);
+                    self.set_down_riders(&mut train, true); // Freight it picked up on the way is re-ordered from here
+                    self.alight_passengers(&mut train.cars, true);
+                    for engine in train.engines {
+                        self.house_engine(engine);
+                    }
                     for car in train.cars {
                         let car_id = car.id;
-                        match self.yard.receive_car(car) {
-                            Ok(Some(cargo)) => { self.warehouse.store(cargo); },
+                        match self.yard.squeeze_in(car) {
+                            Ok(Some(cargo)) => { self.store_cargo(cargo); },
                             Ok(None) => {}, // Car is empty but safely in the yard
                             Err((homeless_car, e)) => {
                                 println!("{RED}Train {}: Failed to process Car {} during salvage: {:?}. Moving to purgatory.{RESET}", train.id, car_id, e);
-                                let rejected_asset = RejectedAsset::new(homeless_car, e, train.mission_id,);
+                                let rejected_asset = RejectedAsset::new(*homeless_car, e, train.mission_id,);
                                 self.yard.purgatory.push(rejected_asset);
                             }
                         }
@@ -1025,26 +1844,47 @@ pub fn retry_pending_missions(&mut self) {
                     //     // let _ = sender.send(report);
                     // }
                     let reason = "Failed at the None arm of the Dijkstra check";
-                    self.send_failure_report(train.mission_id.expect("This is a failure report; There should be a mission_id on this train!"), reason, train.report_to);
+                    if let Some(mission_id) = train.mission_id { // Light engines have nobody to tell. They just stay here.
+                        self.send_failure_report(mission_id, reason, train.report_to);
+                    }
                     return;
                 }
             };
+            if train.service {
+                self.board_passengers(&mut train, &route);
+            }
+            // Cars for here come off, and freight waiting here for further down the line gets on, if the engines can take it.
+            self.set_down_riders(&mut train, false);
+            self.pick_up_along_the_way(&mut train, &route[1..]);
             self.dispatch_train(train, route);               
         }
     }
 
     // This is the method we call when a train arrives with an SOS from a failed mission. The engine is lost, but some or all of the cars survive and make it to the station. We need to process those cars, report on the situation, and then dispatch a replacement train to fulfill the original mission if possible.
     // Destination is critical for this method, because the original mission's destination may now be unreachable due to the emergency, so we need to update the mission with a new destination (this station) for the replacement train, and then rely on the network's routing logic to find a new path from this station to the original destination that avoids whatever caused the emergency in the first place.
-    pub fn handle_emergency_sos(&mut self, mission_id: u32, destination: u32, surviving_cars: Vec<TrainCar>, report_to: Option<Sender<MissionReport>>) {
+    pub fn handle_emergency_sos(&mut self, mission_id: u32, destination: u32, priority: Priority, deadline: Option<f64>, surviving_cars: Vec<TrainCar>, report_to: Option<Sender<MissionReport>>) {
         println!("{RED}[{}] 🚨 EMERGENCY: Processing SOS for Mission {}.{RESET}", self.name, mission_id);
         
         // We'll need the surviving cargo ids to create the replacement freight order. This ensures that they can be accessed by the producer of the replacement train, so they can be loaded into the new train and continue on their journey to the original destination.
-        let salvaged_cargo_ids = surviving_cars.iter().filter_map(|car| car.cargo.as_ref().map(|cargo| cargo.id)).collect::<Vec<u32>>();
+        let mut salvaged_cargo_ids = surviving_cars.iter().filter_map(|car| car.cargo.as_ref().map(|cargo| cargo.id)).collect::<Vec<u32>>();
+        salvaged_cargo_ids.sort_unstable();
+        salvaged_cargo_ids.dedup(); // The parts of a split consignment share an ID, and needn't ride next to each other
         
 
         //processes cars and returns any issues to report, such as cars that failed intake and had to be moved to purgatory. We can include this information in the MissionReport to provide transparency about the salvage operation and any losses incurred.
         let stranded_issues = self.process_cars(surviving_cars, Some(mission_id)); // We can extract this logic into a separate method to keep things cleaner, and it can return the ledger of any failed cars for reporting.
 
+        // A light engine, a passenger train or a run of empty cars. There's no freight to send on again, and nobody waiting on a report.
+        if salvaged_cargo_ids.is_empty() && report_to.is_none() {
+            return;
+        }
+        // A freight mission whose surviving cars are all empty. Its producer still hears about it, but there's nothing to re-order.
+        if salvaged_cargo_ids.is_empty() {
+            let reason = format!("Engine lost, and none of the surviving cars had any of the cargo left in them. Stranded: {:?}.", stranded_issues);
+            self.send_partial_failure_report(mission_id, &reason, &stranded_issues, report_to);
+            return;
+        }
+
         let reason: String = if stranded_issues.is_empty() {
             //"Engine lost, but all surviving cars were successfully salvaged.".to_string()
             format!("Engine lost, but all surviving cars were successfully salvaged. Salvaged cargo IDs: {:?}.", salvaged_cargo_ids)
@@ -1057,6 +1897,9 @@ pub fn retry_pending_missions(&mut self) {
             destination,
             origin: self.id,
             ttl: 5,
+            priority: priority.max(Priority::High), // The customer has been let down once already
+            not_before: None, // The ledger still has the original window
+            deadline,
         };
 
         let mut ledger_access = self.ledger.lock().unwrap();
@@ -1067,34 +1910,92 @@ pub fn retry_pending_missions(&mut self) {
     }
 
 
+    // An engine gave up out on the line and the train was dragged back here. Much like an SOS, except nothing was wrecked:
+    // the broken engine goes in for repairs, its partners go back in the roundhouse, and the cargo waits for another train.
+    pub fn handle_breakdown(&mut self, mut train: Train, engine_id: u32) {
+        let mission_id = train.mission_id.unwrap_or(0);
+        println!("{RED}[{}] 🔧 BREAKDOWN: Engine {} failed on Train {} (Mission {}). Towed back to the station.{RESET}", self.name, engine_id, train.id, mission_id);
+
+        let hours = self.roundhouse.service_hours;
+        for engine in train.engines.drain(..) {
+            if engine.id == engine_id {
+                self.roundhouse.send_for_repairs(engine, hours);
+            } else {
+                self.house_engine(engine);
+            }
+        }
+        if let Some(run) = &train.schedule {
+            self.cancel_run(run.run_id); // The rest of the run is off. Whoever was riding it gets a train of their own.
+        }
+
+        // A timetabled run can be carrying several people's freight. Each consignment is re-ordered, and its producer told, on its own.
+        for (rider, cars) in train.split_for_salvage() {
+            let mut salvaged_cargo_ids = cars.iter().filter_map(|car| car.cargo.as_ref().map(|cargo| cargo.id)).collect::<Vec<u32>>();
+            salvaged_cargo_ids.sort_unstable();
+            salvaged_cargo_ids.dedup(); // The parts of a split consignment share an ID, and needn't ride next to each other
+            let stranded_issues = self.process_cars(cars, Some(rider.mission_id));
+
+            // A light engine on its way to help someone, or a run of empty cars. Nothing to re-order, and nobody waiting on a report;
+            // the requester will ask again on their next heartbeat.
+            if salvaged_cargo_ids.is_empty() && rider.report_to.is_none() {
+                continue;
+            }
+            if salvaged_cargo_ids.is_empty() {
+                let reason = format!("Engine {} broke down, and none of the cars it brought back had any of the cargo left in them.", engine_id);
+                self.send_partial_failure_report(rider.mission_id, &reason, &stranded_issues, rider.report_to);
+                continue;
+            }
+
+            let replacement_freight_order: FreightOrder = FreightOrder {
+                id: rider.mission_id,
+                cargo_ids: salvaged_cargo_ids.clone(),
+                destination: rider.destination,
+                origin: self.id,
+                ttl: 5,
+                priority: rider.priority.max(Priority::High), // The customer has been let down once already
+                not_before: None,
+                deadline: rider.deadline,
+            };
+            self.ledger.lock().unwrap().pending_cargo.push(replacement_freight_order);
+
+            let reason = format!("Engine {} broke down. Cargo {:?} is back at {} awaiting another train.", engine_id, salvaged_cargo_ids, self.name);
+            self.send_partial_failure_report(rider.mission_id, &reason, &stranded_issues, rider.report_to);
+        }
+    }
+
+
     // This is the "loading phase" for incoming cars that are not part of a train. 
-    fn handle_intake_cars(&mut self, cars: Vec<TrainCar>, reply_to: Option<Sender<Result<(), TrainError>>>) {
+    // Cars from a closing station are squeezed in whether there's room or not; the yard they came from is gone.
+    fn handle_intake_cars(&mut self, cars: Vec<TrainCar>, reply_to: Option<Sender<Result<(), TrainError>>>, squeeze: bool) {
         println!("{BOLD}{CYAN}[{}] Populating yard with {} incoming cars from a perfectly standard, non-emergency source. It's not an emergency, promise!{RESET}", self.name, cars.len());
         let mut intake_issues = Vec::new();
 
 
         for car in cars {
             let car_id = car.id;
-            match self.yard.receive_car(car) {
+            let received = if squeeze { self.yard.squeeze_in(car) } else { self.yard.receive_car(car) };
+            match received {
                 Ok(Some(cargo)) => { 
                     
-                    let mut destination: u32;
-                    loop {
-                        destination = rand::thread_rng().gen_range(0..=6);
-                        if destination != self.id {
-                            break;
-                        }
-                    }
+                    let Some(destination) = self.random_destination() else {
+                        println!("{RED}[{}] Nowhere to send cargo {} on this map! Holding it in the warehouse.{RESET}", self.name, cargo.id);
+                        self.store_cargo(cargo);
+                        continue;
+                    };
 
                     let item_id = cargo.id;
-                    self.warehouse.store(cargo); 
+                    self.store_cargo(cargo); 
 
+                    let (priority, not_before, deadline) = walk_in_terms();
                     let freight_order = FreightOrder {
                         id: GLOBAL_ORDER_ID.fetch_add(1, Ordering::SeqCst), // Generate a new unique order ID for this cargo
                         cargo_ids: vec![item_id], // Create a freight order for this individual cargo item
                         destination, // Use the previously determined destination
                         origin: self.id,
                         ttl: 5,
+                        priority,
+                        not_before,
+                        deadline,
                     };
 
                     let mut ledger_access = self.ledger.lock().unwrap();
@@ -1104,7 +2005,7 @@ pub fn retry_pending_missions(&mut self) {
                 Err((homeless_car, e)) => {
                     intake_issues.push(homeless_car.id);
                     println!("{RED} Failed to process Car {} during intake: {:?}. Moving to purgatory.{RESET}", car_id, e);
-                    let rejected_asset = RejectedAsset::new(homeless_car, e, None); // We don't have a mission ID in this context, so we can pass None
+                    let rejected_asset = RejectedAsset::new(*homeless_car, e, None); // We don't have a mission ID in this context, so we can pass None
                     self.yard.purgatory.push(rejected_asset);
                 }
             }
@@ -1125,20 +2026,21 @@ pub fn retry_pending_missions(&mut self) {
         println!("{BOLD}{CYAN}[{}] Receiving {} cargo shipments into the warehouse.{RESET}", self.name, cargo.len());
         //create MutexGuard to access the ledger and log the incoming cargo.
         let mut ledger_access = self.ledger.lock().unwrap();
+        let mut turned_away = Vec::new();
         for item in cargo {
-
-            
-            let mut destination: u32;
-            loop {
-                destination = rand::thread_rng().gen_range(0..=6);
-                if destination != self.id {
-                    break;
-                }
-            }
-
             let item_id = item.id;
-            self.warehouse.store(item);
+            if let Err(item) = self.warehouse.store(item) {
+                println!("{RED}[{}] The warehouse is full ({} spaces). Turning away cargo {}.{RESET}", self.name, self.warehouse.capacity, item.id);
+                turned_away.push(item_id);
+                continue;
+            }
+            
+            let Some(destination) = self.random_destination() else {
+                println!("{RED}[{}] Nowhere to send cargo {} on this map! Holding it in the warehouse.{RESET}", self.name, item_id);
+                continue;
+            };
 
+            let (priority, not_before, deadline) = walk_in_terms();
             ledger_access.pending_cargo.push(FreightOrder {
                 id: GLOBAL_ORDER_ID.fetch_add(1, Ordering::SeqCst), // Generate a new unique order ID for this cargo
                 cargo_ids: vec![item_id], // Create a freight order for this individual cargo item
@@ -1146,17 +2048,61 @@ pub fn retry_pending_missions(&mut self) {
                 destination, // Use the previously determined destination
                 origin: self.id,
                 ttl: 5,
+                priority,
+                not_before,
+                deadline,
             });
 
         }
+        if let Some(channel) = reply_to {
+            if turned_away.is_empty() {
+                let _ = channel.send(Ok(()));
+            } else {
+                println!("{RED}[{}] Cargo turned away for want of space: {:?}{RESET}", self.name, turned_away);
+                let _ = channel.send(Err(TrainError::NoRoom { facility: "warehouse".to_string(), capacity: self.warehouse.capacity }));
+            }
+        }
+    }
+
+    fn handle_intake_consignment(&mut self, cargo: Cargo, destination: u32, priority: Priority, not_before: Option<f64>, deadline: Option<f64>, reply_to: Option<Sender<Result<(), TrainError>>>) {
+        println!("{BOLD}{CYAN}[{}] Receiving consignment {} bound for Station {}.{RESET}", self.name, cargo.id, destination);
+        let item_id = cargo.id;
+        if let Err(cargo) = self.warehouse.store(cargo) {
+            println!("{RED}[{}] The warehouse is full ({} spaces). Turning away consignment {}.{RESET}", self.name, self.warehouse.capacity, cargo.id);
+            if let Some(channel) = reply_to {
+                let _ = channel.send(Err(TrainError::NoRoom { facility: "warehouse".to_string(), capacity: self.warehouse.capacity }));
+            }
+            return;
+        }
+
+        self.ledger.lock().unwrap().pending_cargo.push(FreightOrder {
+            id: GLOBAL_ORDER_ID.fetch_add(1, Ordering::SeqCst),
+            cargo_ids: vec![item_id],
+            destination,
+            origin: self.id,
+            ttl: 5,
+            priority,
+            not_before,
+            deadline,
+        });
+
         if let Some(channel) = reply_to {
             let _ = channel.send(Ok(()));
         }
     }
 
+    // Any station on the map other than ourselves. The map decides, so generated networks and runtime additions just work.
+    fn random_destination(&self) -> Option<u32> {
+        let candidates: Vec<u32> = self.map.read().unwrap().station_ids().into_iter().filter(|id| *id != self.id).collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[rand::thread_rng().gen_range(0..candidates.len())])
+    }
+
     pub fn handle_intake_engine(&mut self, engine: Engine, reply_to: Option<Sender<Result<(), TrainError>>>) {
         println!("{BOLD}{CYAN}[{}] Intaking engine {} of type {:?} into the roundhouse.{RESET}", self.name, engine.id, engine.engine_type);
-        self.roundhouse.house(engine);
+        self.house_engine(engine); // Engines can't be sent back where they came from (a closing station has nowhere to keep them), so a full roundhouse makes do
         if let Some(channel) = reply_to {
             let _ = channel.send(Ok(()));
         }
@@ -1168,34 +2114,247 @@ pub fn retry_pending_missions(&mut self) {
         self.neighbors.insert(neighbor, tx);
     }
 
-    pub fn handle_request_empty_cars(&mut self, count: u32) {
-        println!("{BOLD}{YELLOW}[{}] ⚠️ EMERGENCY LOGISTICS: Generating {} new empty cars from the ether...{RESET}", self.name, count);
+    // Engineering works! The map has already been updated by the Fat Controller, so all we need to do is hang up the radio to this neighbor.
+    // Trains that were planning to use this track don't need to be told: every station re-runs Dijkstra when a train arrives, so they re-route at their next stop.
+    pub fn handle_track_closed(&mut self, neighbor: u32) {
+        println!("{BOLD}{RED}[{}] Track to neighbor {} is closed. Removing from neighbors.{RESET}", self.name, neighbor);
+        self.neighbors.remove(&neighbor);
+    }
+
+    pub fn handle_track_reopened(&mut self, neighbor: u32, tx: Sender<StationCommand>) {
+        println!("{BOLD}{GREEN}[{}] Track to neighbor {} has reopened.{RESET}", self.name, neighbor);
+        self.neighbors.insert(neighbor, tx);
+        // A mission that was parked because its destination was cut off might be routable again.
+        self.retry_pending_missions();
+    }
+
+    fn handle_transfer_cargo(&mut self, cargo: Vec<Cargo>) {
+        println!("{BOLD}{CYAN}[{}] Taking over {} cargo shipments from a closing station.{RESET}", self.name, cargo.len());
+        for item in cargo {
+            self.store_cargo(item);
+        }
+    }
+
+    // The station is closing for good. Everything we own goes to our former neighbors: engines and empty cars are shared
+    // out round-robin, while the warehouse, its freight orders and our parked missions all go to the nearest heir together
+    // (a mission is useless without its cargo sitting in the same warehouse).
+    /// Returns false if we refused to close, in which case nothing has been handed over and the station carries on.
+    pub fn handle_decommission(&mut self, heirs: Vec<(u32, Sender<StationCommand>)>, reply_to: Sender<Result<(), TrainError>>) -> bool {
+        println!("{BOLD}{RED}[{}]::Station {}: Decommissioning! Draining the station to {} heir(s).{RESET}", self.name, self.id, heirs.len());
+
+        let Some((heir_id, heir_tx)) = heirs.first().cloned() else {
+            println!("{RED}[{}] Nobody to hand our assets to! Refusing to close.{RESET}", self.name);
+            let _ = reply_to.send(Err(TrainError::MissionImpossible { reason: format!("Station {} has no heirs to drain into", self.id) }));
+            return false;
+        };
+        // The heirs acknowledge intake on this channel, but we're on our way out and don't need to wait for them.
+        let (ack_tx, _ack_rx) = mpsc::channel();
+
+        // 1. Engines, round-robin. Anything still in the shed goes too, half-fixed; the heir can finish the job.
+        let mut engines: Vec<Engine> = self.roundhouse.stalls.drain().flat_map(|(_, queue)| queue).collect();
+        self.roundhouse.housed_at.clear();
+        engines.extend(self.roundhouse.shed.drain(..).map(|(engine, _)| engine));
+        for (i, engine) in engines.into_iter().enumerate() {
+            let (id, tx) = &heirs[i % heirs.len()];
+            println!("{YELLOW}[{}] Sending Engine {} to Station {}.{RESET}", self.name, engine.id, id);
+            let _ = tx.send(StationCommand::IntakeEngine { engine, reply_to: ack_tx.clone() });
+        }
+
+        // 2. Rolling stock, round-robin. Purgatory cars get a second inspection at their new home. Cars still on the
+        // classification tracks are unloaded first, and their freight re-ordered, so it follows the warehouse to the heir.
+        for cut in std::mem::take(&mut self.yard.classification).into_values().flatten() {
+            self.give_up_on_cut(cut, "The hub closed while the cars were being sorted.");
+        }
+        let mut cars: Vec<TrainCar> = self.yard.cars.drain().map(|(_, car)| car).collect();
+        cars.extend(self.yard.purgatory.drain(..).map(|asset| asset.car));
+        let mut shares: Vec<Vec<TrainCar>> = heirs.iter().map(|_| Vec::new()).collect();
+        for (i, car) in cars.into_iter().enumerate() {
+            shares[i % heirs.len()].push(car);
+        }
+        for ((id, tx), share) in heirs.iter().zip(shares) {
+            if !share.is_empty() {
+                println!("{YELLOW}[{}] Sending {} car(s) to Station {}.{RESET}", self.name, share.len(), id);
+                let _ = tx.send(StationCommand::TransferCars { cars: share });
+            }
+        }
+
+        // 3. The warehouse goes to the nearest heir, and the ledger learns the forwarding address for our freight orders.
+        let mut cargo: Vec<Cargo> = self.warehouse.inventory.drain().map(|(_, cargo)| cargo).collect();
+        cargo.extend(self.warehouse.partials.drain().flat_map(|(_, parts)| parts)); // The heir rejoins them if the rest ever turn up
+        if !cargo.is_empty() {
+            let _ = heir_tx.send(StationCommand::TransferCargo { cargo });
+        }
+        if !self.backhauls.is_empty() {
+            let _ = heir_tx.send(StationCommand::WatchBackhauls { reports: self.backhauls.drain(..).collect() }); // Still out on the line
+        }
+        let moved = self.ledger.lock().unwrap().rehome_orders(self.id, heir_id);
+        println!("{YELLOW}[{}] Re-homed {} pending freight order(s) to Station {}.{RESET}", self.name, moved, heir_id);
+
+        // Trains held at our signals, or outside waiting for room, are worked through to the nearest heir, which sends them on from there.
+        for (mut train, _, _) in self.signal_queue.drain(..).collect::<Vec<_>>() {
+            self.signal_box.lock().unwrap().stop_holding(train.id);
+            self.release_block(&mut train);
+            let _ = heir_tx.send(StationCommand::ReceiveTrain { train, reply_to: ack_tx.clone() });
+        }
+        for (mut train, _) in self.held_outside.drain(..).collect::<Vec<_>>() {
+            self.signal_box.lock().unwrap().stop_holding(train.id);
+            self.release_block(&mut train);
+            let _ = heir_tx.send(StationCommand::ReceiveTrain { train, reply_to: ack_tx.clone() });
+        }
+
+        // The people on the platform walk over to the nearest heir and wait there instead.
+        if !self.platform.is_empty() {
+            let _ = heir_tx.send(StationCommand::PassengersArrive { passengers: self.platform.drain(..).collect() });
+        }
+
+        // 4. Parked missions follow their cargo. Same channel as the cargo, so the heir stores it before it tries to assemble.
+        // So do missions waiting on a timetabled service; the heir books them again if it's on the line too.
+        // Runs still waiting here for an engine aren't going anywhere now.
+        for (_, run_id, _) in self.held_runs.drain(..).collect::<Vec<_>>() {
+            self.cancel_run(run_id);
+        }
+        let booked = self.booked.drain(..).map(|(_, _, mission)| mission);
+        let marshalling = self.marshalling.drain(..).map(|(mission, _)| mission);
+        let held = self.held_for_window.drain(..).map(|(mission, _)| mission);
+        for mut mission in self.pending_missions.drain(..).chain(booked).chain(marshalling).chain(held) {
+            mission.origin = heir_id;
+            let _ = heir_tx.send(StationCommand::AssembleMission { mission });
+        }
+        self.short_of_cars.clear(); // The heir does its own asking
+        self.empty_cars_requested_at = None;
+
+        let _ = reply_to.send(Ok(()));
+        true
+    }
+
+    // The only place new cars come from: the works builds them, and it isn't free. We build what we can afford.
+    pub fn handle_build_cars(&mut self, car_class: CarClass, count: u32) {
+        let affordable = (self.car_budget / car_class.build_cost()).min(count);
+        if affordable == 0 {
+            println!("{RED}[{}] Can't afford any new {:?} cars (budget {}, {} each).{RESET}", self.name, car_class, self.car_budget, car_class.build_cost());
+            return;
+        }
+        self.car_budget -= affordable * car_class.build_cost();
+        println!("{BOLD}{YELLOW}[{}] 🏭 Building {} new {:?} car(s) for {}. Budget left: {}.{RESET}", self.name, affordable, car_class, affordable * car_class.build_cost(), self.car_budget);
         
-        for _ in 0..count {
+        for _ in 0..affordable {
             // Grab a globally unique ID safely!
             let safe_id = self.yard.generate_new_car_id();
             
             let new_car = TrainCar {
                 id: safe_id, 
                 cargo: None,
-                passenger: None,
+                passengers: Vec::new(),
+                class: car_class,
+                destination: None,
             };
             
-            //let _ = self.yard.receive_car(new_car); 
             if let Err((homeless_car, error)) = self.yard.receive_car(new_car) {
-                println!("{RED}Failed to receive generated empty car with ID {}: {:?}. Moving to purgatory.{RESET}", safe_id, error);
-                let rejected_asset = RejectedAsset::new(homeless_car, error, None);
+                println!("{RED}Failed to receive new empty car with ID {}: {:?}. Moving to purgatory.{RESET}", safe_id, error);
+                let rejected_asset = RejectedAsset::new(*homeless_car, error, None);
                 self.yard.purgatory.push(rejected_asset);
             }
         }
     }
 
-    pub fn handle_engine_request(&mut self, requester_id: u32, request_id: u32, mission_id: Option<u32>, min_capacity: f64, mut mission_max_hop: f64, mut ttl: u32, mut branch_notified: [u32; 64], mut notified_count: usize) {
-        println!("{BOLD}{YELLOW}[{}]::Station {}: Received engine request {} for mission ID {:?} for an engine with minimum capacity {}kg, mission max hop {}km, and TTL {} from Station {}.{RESET}", self.name, self.id, request_id, mission_id, min_capacity, mission_max_hop, ttl, requester_id);
+    // A neighbour is short of empty cars. If we can spare some (and an engine to pull them), we send them over as an
+    // empty-car train. Whatever we couldn't cover is passed on to our neighbours, the same way engine requests are.
+    pub fn handle_empty_car_request(&mut self, requester_id: u32, request_id: u32, car_class: CarClass, count: u32, mut gossip: Gossip) {
+        println!("{BOLD}{YELLOW}[{}]::Station {}: Received empty car request {} for {} {:?} car(s), TTL {} from Station {}.{RESET}", self.name, self.id, request_id, count, car_class, gossip.ttl, requester_id);
+        gossip.ttl -= 1;
+
+        if !self.seen_car_request.insert(request_id) {
+            println!("{YELLOW}Already processed empty car request {}. Ignoring to prevent loops.{RESET}", request_id);
+            return;
+        }
+
+        // Keep enough for our own parked missions, plus a couple spare. Only the cars of this class that our parked cargo
+        // would actually ride in count: a station waiting on tankers can still lend out its flatbeds.
+        let parked_cargo: Vec<&Cargo> = self.pending_missions.iter()
+            .flat_map(|mission| mission.cargo_ids.iter())
+            .filter_map(|id| self.warehouse.inventory.get(id))
+            .collect();
+        let needed_here = self.yard.cars_wanted_by_class(&parked_cargo).get(&car_class).copied().unwrap_or(0);
+        let spare = self.yard.empty_car_count(car_class).saturating_sub(needed_here + EMPTY_CAR_RESERVE);
+        let give = spare.min(count as usize);
+        let total_engines_available: usize = self.roundhouse.stalls.values().map(|engines| engines.len()).sum();
+        let route_to_requester = self.map.read().unwrap().find_shortest_path(self.id, requester_id).map(|(_, route)| route);
+
+        let mut remaining = count;
+        if let (true, true, Some(route)) = (give > 0, total_engines_available > 1, route_to_requester) {
+            let (max_hop, max_gradient) = self.map.read().unwrap().route_profile(&route);
+            let weight = (give as u32 * car_class.tare()) as f64;
+            match self.roundhouse.find_suitable_engine(weight, 0.0, max_hop, max_gradient, true) {
+                Ok(engine) => {
+                    let cars = self.yard.take_empty_cars(car_class, give);
+                    println!("{GREEN}[{}] Sending {} empty {:?} car(s) behind Engine {} to Station {}.{RESET}", self.name, cars.len(), car_class, engine.id, requester_id);
+                    remaining -= cars.len() as u32;
+                    let empty_car_train = Train {
+                        id: self.yard.generate_new_train_id(),
+                        engines: vec![engine],
+                        cars,
+                        mission_id: None,
+                        destination: requester_id,
+                        report_to: None,
+                        service: false,
+                        schedule: None,
+                        riders: Vec::new(),
+                        highpriority: false,
+                        priority: Priority::default(),
+                        deadline: None,
+                        block: None,
+                    };
+                    self.dispatch_train(empty_car_train, route);
+                },
+                Err(e) => println!("{RED}[{}] Have {} empty car(s) to spare, but no engine to pull them: {:?}.{RESET}", self.name, give, e),
+            }
+        }
+
+        if remaining == 0 {
+            return;
+        }
+        if gossip.ttl > 0 {
+            self.forward_empty_car_request(requester_id, request_id, car_class, remaining, gossip);
+        } else {
+            println!("{RED}[Station {} Yard]: TTL expired for empty car request from Station {}.{RESET}", self.id, requester_id);
+        }
+    }
+
+    fn initiate_empty_car_request(&mut self, request_id: u32, car_class: CarClass, count: u32, ttl: u32) {
+        println!("{YELLOW}Yard {}: Asking the neighbours for {} empty {:?} car(s) with request ID {}.{RESET}", self.id, count, car_class, request_id);
+        self.seen_car_request.insert(request_id);
+        self.forward_empty_car_request(self.id, request_id, car_class, count, Gossip::new(self.id, ttl));
+    }
+
+    fn forward_empty_car_request(&self, requester_id: u32, request_id: u32, car_class: CarClass, count: u32, gossip: Gossip) {
+        let Some((assignments, stamped)) = self.plan_gossip(gossip) else {
+            println!("{RED}[Station {} Yard]: No valid neighbors to forward empty car request for Station {}.{RESET}", self.id, requester_id);
+            return;
+        };
+
+        for (chosen_id, assigned_ttl) in assignments {
+            let Some(neighbor) = self.neighbors.get(&chosen_id) else { continue };
+            match neighbor.send(StationCommand::EmptyCarRequest {
+                requester_id,
+                request_id,
+                car_class,
+                count,
+                ttl: assigned_ttl,
+                branch_notified: stamped.branch_notified,
+                notified_count: stamped.notified_count,
+            }) {
+                Ok(_) => println!("{YELLOW}[{}] Forwarded empty car request {} to neighbor {} with TTL {}.{RESET}", self.name, request_id, chosen_id, assigned_ttl),
+                Err(e) => println!("{RED}[{}] DEAD-LETTER: Failed to forward empty car request {} to neighbor {}. Error: {:?}{RESET}", self.name, request_id, chosen_id, e),
+            }
+        }
+    }
+
+    pub fn handle_engine_request(&mut self, requester_id: u32, request_id: u32, mission_id: Option<u32>, mut needs: EngineNeeds, mut gossip: Gossip) {
+        println!("{BOLD}{YELLOW}[{}]::Station {}: Received engine request {} for mission ID {:?} for an engine with minimum capacity {}kg (paired with {}kg on hand), mission max hop {}km, mission max gradient {:.1}m/km, and TTL {} from Station {}.{RESET}", self.name, self.id, request_id, mission_id, needs.min_capacity, needs.paired_capacity, needs.max_hop, needs.max_gradient, gossip.ttl, requester_id);
         // check the number of engines of ANY TYPE across the entire roundhouse. We cannot give away our last engine, so we need to make sure we have at least 2 engines before we can fulfill this request. If we have 2 or more engines, we can send one to the requester. If we only have 1 engine, we cannot fulfill the request without risking our own operations, so we will have to decline.
         // we will iterate across the hashmap of engine types and count the total number of engines available. If the total number is greater than 1, we can fulfill the request. If the total number is 1 or less, we cannot fulfill the request.
         
-        ttl -= 1; // Decrement TTL at the start of the method to ensure that we account for the hop to this station, even if we end up not forwarding the request due to lack of engines or TTL expiration. This way, the TTL accurately reflects the number of hops the request has taken through the network, regardless of whether it gets forwarded or not.
+        gossip.ttl -= 1; // Decrement TTL at the start of the method to ensure that we account for the hop to this station, even if we end up not forwarding the request due to lack of engines or TTL expiration. This way, the TTL accurately reflects the number of hops the request has taken through the network, regardless of whether it gets forwarded or not.
 
         if self.seen_engine_request.contains(&request_id) {
             println!("{YELLOW}Already processed engine request {}. Ignoring to prevent loops.{RESET}", request_id);
@@ -1205,7 +2364,7 @@ pub fn retry_pending_missions(&mut self) {
             }
             
         let total_engines_available: usize = self.roundhouse.stalls.iter().map(|(_, engines)| engines.len()).sum();
-        let route_to_requester = match self.map.find_shortest_path(self.id, requester_id) {
+        let route_to_requester = match self.map.read().unwrap().find_shortest_path(self.id, requester_id) {
             Some((_, r)) => r,
             None => {
                 println!("{RED}Network Error: No track laid between {} and {}. Cannot fulfill engine request.{RESET}", self.id, requester_id);
@@ -1213,43 +2372,44 @@ pub fn retry_pending_missions(&mut self) {
             }
         };
         //let max_hop_to_requester = route_to_requester.windows(2).filter_map(|pair| self.map.get_distance(pair[0], pair[1])).fold(0./0., f64::max); // Calculate the max hop distance to the requester, which is needed to determine if we have a suitable engine that can make it there.
-        for i in 0..route_to_requester.len() - 1 {
-            if let Some(dist) = self.map.get_distance(route_to_requester[i], route_to_requester[i+1]) {
-                if dist > mission_max_hop {
-                    mission_max_hop = dist;
-                }
-            }
-        }
+        // The engine has to survive the trip over here as well as the mission itself, so widen the envelope to cover both.
+        let (hop_to_requester, gradient_to_requester) = self.map.read().unwrap().route_profile(&route_to_requester);
+        needs.max_hop = needs.max_hop.max(hop_to_requester);
+        needs.max_gradient = needs.max_gradient.max(gradient_to_requester);
         
         if total_engines_available > 1 {
-            match self.roundhouse.find_suitable_engine(min_capacity, mission_max_hop){
+            let found = match needs.class {
+                Some(class) => self.roundhouse.spare_engine_of_class(class, needs.max_hop, needs.max_gradient),
+                None => self.roundhouse.find_suitable_engine(needs.min_capacity, needs.paired_capacity, needs.max_hop, needs.max_gradient, true),
+            };
+            match found {
                 Ok(engine) => {
                     println!("{GREEN}Roundhouse {}: Found suitable engine {} for requester {} for request {}. Dispatching...{RESET}", self.id, engine.id, requester_id, request_id);
                     // We can dispatch the engine to the requester using the network's routing logic, which will find the best path from this station to the requester and send the engine along that path. We can create a temporary Train with just the engine and no cars to represent this transfer.
                     let temp_train = Train {
                         id: self.yard.generate_new_train_id(),
-                        engine,
+                        engines: vec![engine],
                         cars: Vec::new(),
                         mission_id,
                         destination: requester_id,
                         report_to: None,
+                        service: false,
+                        schedule: None,
+                        riders: Vec::new(),
+                        highpriority: false,
+                        priority: Priority::default(),
+                        deadline: None,
+                        block: None,
                     };
                     self.dispatch_train(temp_train, route_to_requester);
 
                     // After dispatching the engine, we need to check if we should forward the request to our neighbors to see if they can also fulfill it, in case the requester needs multiple engines or if the requester is actually looking for an engine that meets the minimum capacity but also has other specific requirements that this engine doesn't meet. We can use the TTL to determine if we should forward the request, and we can use the branch_notified array to keep track of which neighbors have already been notified about this request to prevent loops. We will only forward the request if the TTL is greater than 0, and we will decrement the TTL before forwarding. We will also add this station's ID to the branch_notified array before forwarding, and we will increment the notified_count to keep track of how many neighbors have been notified.
                     println!("{YELLOW}Roundhouse {}: Checking if we should forward the engine request to neighbors after dispatching an engine to requester {}.{RESET}", self.id, requester_id);
-                    if ttl > 0 {
+                    if needs.class.is_some() {
+                        // A top-up asks for one engine at a time. It's on its way; nobody else need send one.
+                    } else if gossip.ttl > 0 {
                         //ttl -= 1; // Decrement TTL before forwarding
-                        self.forward_engine_request(
-                            requester_id,
-                            request_id,
-                            mission_id,
-                            min_capacity,
-                            mission_max_hop,
-                            ttl,
-                            branch_notified,
-                            notified_count,
-                        );
+                        self.forward_engine_request(requester_id, request_id, mission_id, needs, gossip);
                     } else {
                         println!("{RED}[Station {} Roundhouse]: TTL expired for engine request from Station {}.{RESET}", self.id, requester_id);
                     }
@@ -1259,18 +2419,9 @@ pub fn retry_pending_missions(&mut self) {
                 },
                 Err(e) => {
                     println!("{RED}Roundhouse {} Error: Failed to find suitable engine for request from Station {}: {:?}.{RESET}", self.id, requester_id, e);
-                    if ttl > 0 {
+                    if gossip.ttl > 0 {
                         //ttl -= 1; // Decrement TTL before forwarding
-                        self.forward_engine_request(
-                            requester_id,
-                            request_id,
-                            mission_id,
-                            min_capacity,
-                            mission_max_hop,
-                            ttl,
-                            branch_notified,
-                            notified_count,
-                        );
+                        self.forward_engine_request(requester_id, request_id, mission_id, needs, gossip);
                     } else {
                         println!("{RED}[Station {} Roundhouse]: TTL expired for engine request from Station {}.{RESET}", self.id, requester_id);
                     }
@@ -1282,18 +2433,9 @@ pub fn retry_pending_missions(&mut self) {
 
             println!("{RED}Roundhouse {}: Only {} engine(s) available. Cannot fulfill request from Station {} without risking own operations.{RESET}", self.id, total_engines_available, requester_id);
 
-            if ttl > 0 {
+            if gossip.ttl > 0 {
                 //ttl -= 1; // Decrement TTL before forwarding
-                self.forward_engine_request(
-                    requester_id,
-                    request_id,
-                    mission_id,
-                    min_capacity,
-                    mission_max_hop,
-                    ttl,
-                    branch_notified,
-                    notified_count,
-                );
+                self.forward_engine_request(requester_id, request_id, mission_id, needs, gossip);
             } else {
                 println!("{RED}[Station {} Roundhouse]: TTL expired for engine request from Station {}.{RESET}", self.id, requester_id);
             }
@@ -1303,17 +2445,59 @@ pub fn retry_pending_missions(&mut self) {
     }
 
 
-    fn initiate_engine_request(&mut self, requester_id: u32, request_id: u32, mission_id: Option<u32>, min_capacity: f64, mission_max_hop: f64, ttl: u32) {
+    fn initiate_engine_request(&mut self, requester_id: u32, request_id: u32, mission_id: Option<u32>, needs: EngineNeeds, ttl: u32) {
         // We initialize branch_notified with the ID of the requester to prevent the request from being forwarded back to the requester and creating loops right from the start. We also initialize notified_count to 1 since we have already "notified" the requester by receiving the request in the first place.
         println!("{YELLOW}Roundhouse {}: Initiating engine request for Station {} with request ID {} for mission ID {:?}.{RESET}", self.id, requester_id, request_id, mission_id);
-        let branch_notified = [requester_id; 64]; // We can use this array to keep track of which stations have been or will be notified of this request. Before forwarding this request, the station will place its id, as well the target stations' ids, into the array to prevent those stations from forwarding the request back to this station and creating loops. We initialize it with the requester_id to prevent loops right from the start.
-        let notified_count = 1; // We start with 1 because we have already "notified" the requester by receiving the request in the first place.
+        // branch_notified keeps track of which stations have been or will be notified of this request. Before forwarding this request, the station will place its id, as well the target stations' ids, into the array to prevent those stations from forwarding the request back to this station and creating loops. It starts with the requester_id (and a count of 1) to prevent loops right from the start.
+        let gossip = Gossip::new(requester_id, ttl);
         
-        self.forward_engine_request(requester_id, request_id, mission_id, min_capacity, mission_max_hop, ttl, branch_notified, notified_count);
+        self.forward_engine_request(requester_id, request_id, mission_id, needs, gossip);
     }
 
     // Copilot, let's make a helper method for forwarding engine_requests to neighbors. We'll need to do it for the origin of the request, and we will need it for multiple arms of handle_engine_request when we have to forward due to insufficient engines or when we have to fan out due to TTL. This method will take care of stamping the branch_notified array and forwarding the request to the appropriate neighbors based on the TTL and the number of valid candidates. As well as incrementing the notified_count and ensuring we don't forward to neighbors that have already been notified. You got it, Copilot!
-    fn forward_engine_request(&self, requester_id: u32, request_id: u32, mission_id: Option<u32>, min_capacity: f64, mission_max_hop: f64, ttl: u32, branch_notified: [u32; 64], notified_count: usize) {
+    fn forward_engine_request(&self, requester_id: u32, request_id: u32, mission_id: Option<u32>, needs: EngineNeeds, gossip: Gossip) {
+        let Some((assignments, stamped)) = self.plan_gossip(gossip) else {
+            println!("{RED}[Station {} Roundhouse]: No valid neighbors to forward engine request for Station {}. Cannot fulfill request without risking own operations.{RESET}", self.id, requester_id);
+            return;
+        };
+
+        for (chosen_id, assigned_ttl) in assignments {
+            println!("{YELLOW} [{}]::Station{}: Forwarding engine request to neighbor {} with assigned TTL {} for request from Station {}.{RESET}", self.name, self.id, chosen_id, assigned_ttl, requester_id);
+            match self.neighbors.get(&chosen_id) {
+                Some(neighbor) => {
+                    match neighbor.send(StationCommand::EngineRequest { 
+                        requester_id, 
+                        request_id,
+                        mission_id, // We also need to forward the mission_id in case we need to correlate this engine request with a specific mission at the requester station.
+                        min_capacity: needs.min_capacity,
+                        paired_capacity: needs.paired_capacity,
+                        mission_max_hop: needs.max_hop, 
+                        mission_max_gradient: needs.max_gradient,
+                        class: needs.class,
+                        ttl: assigned_ttl,
+                        branch_notified: stamped.branch_notified, // We forward the stamped branch_notified array to prevent loops.
+                        notified_count: stamped.notified_count, // We also forward the updated count of how many neighbors have been notified so far.
+                    }) {
+                        Ok(_) => println!("{YELLOW}[{}] Forwarded engine request {} for mission ID {:?} to neighbor {} for request from Station {}.{RESET}", self.name, request_id, mission_id, chosen_id, requester_id),
+                        Err(e) => println!("{RED}[{}] DEAD-LETTER: Failed to forward engine request {} for mission ID {:?} to neighbor {} for Station {}. Error: {:?}{RESET}", self.name, request_id, mission_id, chosen_id, requester_id, e),
+                    }
+                },
+                None => println!("{RED}Network Error: Neighbor {} not found in neighbors list of Station {}. Cannot forward engine request {} for mission ID {:?}.{RESET}", chosen_id, self.name, request_id, mission_id),
+            }
+
+        }
+
+
+    }
+
+
+
+
+    // Copilot, the fan-out half of forward_engine_request lives here now, so every kind of gossip (engines, empty cars) spreads the same way.
+    // Picks which neighbours hear about a request and how much TTL each gets, and stamps branch_notified for the next hop.
+    // None if there's nobody left to tell.
+    fn plan_gossip(&self, gossip: Gossip) -> Option<(Vec<(u32, u32)>, Gossip)> {
+        let Gossip { ttl, branch_notified, notified_count } = gossip;
         use rand::seq::SliceRandom; // We can use this to randomly select neighbors to forward the request to if we have to fan out due to TTL and number of candidates.
         
 
@@ -1333,8 +2517,7 @@ pub fn retry_pending_missions(&mut self) {
         let fan_out = std::cmp::min(ttl as usize, valid_candidates.len()); // We can only forward to as many neighbors as the TTL allows, and we also need to make sure we don't try to forward to more neighbors than we have available, so we take the minimum of TTL and the number of valid candidates.
 
         if fan_out == 0 {
-            println!("{RED}[Station {} Roundhouse]: No valid neighbors to forward engine request for Station {}. Cannot fulfill request without risking own operations.{RESET}", self.id, requester_id);
-            return;
+            return None;
         }
         //3. Selection. We can randomly select neighbors from the valid candidates to forward the request to, up to the number allowed by the fan_out calculation. This random selection helps distribute the requests more evenly across the network and prevents certain stations from being overwhelmed with requests.
         let mut rng = rand::thread_rng();
@@ -1358,157 +2541,1616 @@ pub fn retry_pending_missions(&mut self) {
         let base_ttl = ttl/ chosen_candidates.len() as u32; // First, we calculate the base TTL for each neighbor.
         let ttl_remainder = ttl % chosen_candidates.len() as u32; // We also calculate the remainder of the TTL division, which we will distribute to the first few neighbors of the shuffled valid candidates to simulate randomness in TTL assignment.
 
-        // for chosen_id in chosen_candidates {
-        //     println!("{YELLOW} [{}]: Forwarding engine request to neighbor {} with base TTL {} and remainder TTL {} for request from Station {}.{RESET}", self.name, chosen_id, base_ttl, ttl_remainder, requester_id);
-        //     let assigned_ttl = if ttl_remainder > 0 {
-        //         ttl_remainder -= 1;
-        //         base_ttl + 1
-        //     } else {
-        //         base_ttl
-        //     };
-
-        //     match self.neighbors.get(&chosen_id) {
-        //         Some(neighbor) => {
-        //             match neighbor.send(StationCommand::EngineRequest { 
-        //                 requester_id, 
-        //                 request_id,
-        //                 min_capacity, 
-        //                 mission_max_hop, 
-        //                 ttl: assigned_ttl,
-        //                 branch_notified: next_notified, // We forward the stamped branch_notified array to prevent loops.
-        //                 notified_count: next_notified_count, // We also forward the updated count of how many neighbors have been notified so far.
-        //             }) {
-        //                 Ok(_) => println!("{YELLOW}[{}] Forwarded engine request to neighbor {} due to insufficient engines for request from Station {}.{RESET}", self.name, chosen_id, requester_id),
-        //                 Err(e) => println!("{RED}[{}] DEAD-LETTER: Failed to forward engine request to neighbor {} for Station {}. Error: {:?}{RESET}", self.name, chosen_id, requester_id, e),
-        //             }
-        //         },
-        //         None => println!("{RED}Network Error: Neighbor {} not found in neighbors list of Station {}. Cannot forward engine request.{RESET}", chosen_id, self.name),
-        //     }       
-        // }
-
-
-        for (i, &chosen_id) in chosen_candidates.iter().enumerate() {
-            //First, everyone gets the same base TTL, and then we distribute the remainder TTL to the first few neighbors in the shuffled list to add some randomness to the TTL assignment.
-            let assigned_ttl = if i < ttl_remainder as usize {
-                base_ttl + 1
-            } else {
-                base_ttl
-            };
-            println!("{YELLOW} [{}]::Station{}: Forwarding engine request to neighbor {} with assigned TTL {} for request from Station {}.{RESET}", self.name, self.id, chosen_id, assigned_ttl, requester_id);
-            match self.neighbors.get(&chosen_id) {
-                Some(neighbor) => {
-                    match neighbor.send(StationCommand::EngineRequest { 
-                        requester_id, 
-                        request_id,
-                        mission_id, // We also need to forward the mission_id in case we need to correlate this engine request with a specific mission at the requester station.
-                        min_capacity,
-                        mission_max_hop, 
-                        ttl: assigned_ttl,
-                        branch_notified: next_notified, // We forward the stamped branch_notified array to prevent loops.
-                        notified_count: next_notified_count, // We also forward the updated count of how many neighbors have been notified so far.
-                    }) {
-                        Ok(_) => println!("{YELLOW}[{}] Forwarded engine request {} for mission ID {:?} to neighbor {} for request from Station {}.{RESET}", self.name, request_id, mission_id, chosen_id, requester_id),
-                        Err(e) => println!("{RED}[{}] DEAD-LETTER: Failed to forward engine request {} for mission ID {:?} to neighbor {} for Station {}. Error: {:?}{RESET}", self.name, request_id, mission_id, chosen_id, requester_id, e),
-                    }
-                },
-                None => println!("{RED}Network Error: Neighbor {} not found in neighbors list of Station {}. Cannot forward engine request {} for mission ID {:?}.{RESET}", chosen_id, self.name, request_id, mission_id),
-            }
-
-        }
-
+        //First, everyone gets the same base TTL, and then we distribute the remainder TTL to the first few neighbors in the shuffled list to add some randomness to the TTL assignment.
+        let assignments = chosen_candidates.iter().enumerate()
+            .map(|(i, &chosen_id)| (chosen_id, if i < ttl_remainder as usize { base_ttl + 1 } else { base_ttl }))
+            .collect();
 
+        Some((assignments, Gossip { ttl, branch_notified: next_notified, notified_count: next_notified_count }))
     }
 
+    // Foreign engines we don't need go home as light engine moves: an engine-only train with no mission.
+    // Heartbeat duty. An engine from another depot would only run home light, or sit here until somebody needs it. So first
+    // we look on the ledger for an order from here going its way: one whose destination is nearer the engine's home than
+    // we are. If the cargo's in, the yard has the empty cars and the engine can pull it, we claim the order ourselves and
+    // the engine takes it as a backhaul, and we watch for the report the way a Producer would.
+    // Only engines that would be sent home anyway are used: a station still waiting on power keeps what it has.
+    // "Its way" is towards the engine's home depot rather than the last station it came from. An engine is only ever
+    // lent out from home, so that's the area it came from, and it's where send_engines_home would take it next anyway.
+    pub fn find_backhauls(&mut self) {
+        let strangers: Vec<(u32, u32)> = self.roundhouse.homeward_engine_ids(self.needs_its_engines()).into_iter()
+            .filter_map(|id| self.roundhouse.stalls.values().flatten().find(|engine| engine.id == id))
+            .filter_map(|engine| engine.home.map(|home| (engine.id, home)))
+            .collect();
+        for (engine_id, home) in strangers {
+            let Some((mission, distance, route)) = self.claim_backhaul(engine_id, home) else {
+                continue;
+            };
+            let Some(mut engine) = self.roundhouse.take(engine_id) else {
+                continue;
+            };
+            engine.refuel();
+            let cars = match self.warehouse.get_cargo_by_ids(&mission.cargo_ids).and_then(|cargo| self.yard.assemble_cars(cargo)) {
+                Ok(cars) => cars,
+                Err(e) => {
+                    println!("{RED}Yard Error: Failed to make up backhaul Mission {}: {:?}.{RESET}", mission.id, e);
+                    self.house_engine(engine);
+                    self.report_mission_failure(&mission, &format!("Failed to assemble cars for a backhaul: {:?}", e));
+                    continue;
+                }
+            };
+            let train = Train {
+                id: self.yard.generate_new_train_id(),
+                engines: vec![engine],
+                cars,
+                mission_id: Some(mission.id),
+                destination: mission.destination,
+                report_to: mission.reply_channel.clone(),
+                service: false,
+                schedule: None,
+                riders: Vec::new(),
+                highpriority: mission.highpriority,
+                priority: mission.priority,
+                deadline: mission.deadline,
+                block: None,
+            };
+            self.plan_arrival(mission.id, distance, &train);
+            println!("{BOLD}{GREEN}[{}] 🔁 Engine {} takes Mission {} ({} car(s)) to Station {} as a backhaul, on its way home to Station {}.{RESET}", self.name, engine_id, mission.id, train.cars.len(), mission.destination, home);
+            self.dispatch_train(train, route);
+        }
+    }
 
+    // The most urgent order on the ledger that this engine could take from here towards its home, taken off the ledger
+    // and made into a mission. Everything is checked before the order is claimed, so a claimed order always gets its train.
+    fn claim_backhaul(&mut self, engine_id: u32, home: u32) -> Option<(Mission, f64, Vec<u32>)> {
+        let engine = self.roundhouse.stalls.values().flatten().find(|engine| engine.id == engine_id)?;
+        let mut candidates: Vec<_> = self.ledger.lock().unwrap().pending_cargo.iter()
+            .filter(|order| order.origin == self.id && order.destination != self.id)
+            .map(|order| (order.id, order.destination, order.cargo_ids.clone(), order.priority, order.deadline, order.not_before))
+            .collect();
+        candidates.sort_by(|a, b| by_urgency((a.3, a.4), (b.3, b.4)));
+
+        let map = self.map.read().unwrap();
+        let (from_here, _) = map.find_shortest_path(self.id, home)?;
+        let (order_id, distance, route) = candidates.into_iter().find_map(|(order_id, destination, cargo_ids, _, _, not_before)| {
+            let (from_there, _) = map.find_shortest_path(destination, home)?;
+            if from_there >= from_here {
+                return None; // Further from home, or no nearer
+            }
+            let manifest: Vec<&Cargo> = cargo_ids.iter().filter_map(|id| self.warehouse.inventory.get(id)).collect();
+            if manifest.len() < cargo_ids.len() {
+                return None; // Not in yet, or spoiled
+            }
+            let (tare, _, unmatched) = self.yard.plan_loading(&manifest);
+            let weight = tare + manifest.iter().map(|item| item.actual_weight).sum::<u32>();
+            let (distance, route) = map.find_shortest_path(self.id, destination)?;
+            if window_departure(not_before, distance).is_some_and(|leave_at| sim_hours_now() < leave_at) {
+                return None; // Too soon. It would get there before the customer can take it.
+            }
+            let (max_hop, max_gradient) = map.route_profile(&route);
+            (unmatched.is_empty() && engine.can_complete_mission(weight as f64, max_hop, max_gradient)).then_some((order_id, distance, route))
+        })?;
+        drop(map);
+
+        // Claim it, the way a Producer would. Somebody may have beaten us to it while we were looking.
+        let (tx_report, rx_report) = mpsc::channel();
+        let mut ledger = self.ledger.lock().unwrap();
+        let index = ledger.pending_cargo.iter().position(|pending| pending.id == order_id)?;
+        let order = ledger.pending_cargo.remove(index);
+        ledger.deliveries.entry(order.id).or_insert_with(|| DeliveryRecord::new(&order));
+        ledger.backhauls_in_flight += 1; // Off the ledger, but not done with. The Producers mustn't clock out on it.
+        let mission = Mission::from_order(&order, tx_report);
+        drop(ledger);
+        self.backhauls.push((rx_report, order));
+        Some((mission, distance, route))
+    }
 
-
-    pub fn check_pending_missions(&mut self) {
-        if !self.pending_missions.is_empty() {
-            println!("{YELLOW} Heartbeat Check: Station {} has {} pending missions waiting for resources. Attempting to retry... {RESET}", self.name, self.pending_missions.len());
-            self.retry_pending_missions();
+    // Heartbeat duty. How did the backhauls we claimed get on? Same as a Producer: a failed one goes back on the ledger
+    // while it has tries left, and anything else is the end of it. Either way it's no longer in flight, and the ledger
+    // hears both in the same breath, so a Producer never sees it as neither pending nor in flight.
+    pub fn check_backhaul_reports(&mut self) {
+        for (rx, mut order) in std::mem::take(&mut self.backhauls) {
+            let requeue = match rx.try_recv() {
+                Ok(MissionReport::Success(details)) => { println!("{GREEN}[{}] Backhaul success: {}{RESET}", self.name, details); false },
+                Ok(MissionReport::Late(details)) => { println!("{YELLOW}[{}] Backhaul delivered late: {}{RESET}", self.name, details); false },
+                Ok(MissionReport::PartialFailure(details)) => { println!("{YELLOW}[{}] Backhaul partial failure: {}{RESET}", self.name, details); false },
+                Ok(MissionReport::Failure(details)) => {
+                    println!("{RED}[{}] Backhaul failure: {}{RESET}", self.name, details);
+                    order.ttl -= 1;
+                    order.ttl > 0
+                },
+                Err(mpsc::TryRecvError::Empty) => {
+                    self.backhauls.push((rx, order));
+                    continue;
+                },
+                Err(mpsc::TryRecvError::Disconnected) => { println!("{RED}[{}] Lost track of backhaul order {}.{RESET}", self.name, order.id); false },
+            };
+            let mut ledger = self.ledger.lock().unwrap();
+            ledger.backhauls_in_flight -= 1;
+            if requeue {
+                ledger.pending_cargo.push(order);
+            }
         }
     }
 
+    // A train (or a rider on it) bound for a station that closed while it was on the line goes on to the heir instead.
+    fn readdress_for_closures(&self, train: &mut Train) {
+        let mut stops: Vec<u32> = std::iter::once(train.destination).chain(train.riders.iter().map(|rider| rider.destination)).collect();
+        stops.sort_unstable();
+        stops.dedup();
+        let ledger = self.ledger.lock().unwrap();
+        for stop in stops {
+            if let Some(heir) = ledger.forwarding_address(stop) {
+                println!("{YELLOW}[{}] Station {} has closed. Train {} takes its freight for there on to Station {}.{RESET}", self.name, stop, train.id, heir);
+                train.readdress(stop, heir);
+            }
+        }
+    }
 
+    // Missions or runs still waiting for power, or cuts on the classification tracks waiting for an engine out. Until
+    // they've gone, no stranger is sent home (or off on a backhaul), and no engine goes away to the shed.
+    fn needs_its_engines(&self) -> bool {
+        !self.pending_missions.is_empty() || !self.held_runs.is_empty() || !self.yard.classification.is_empty()
+    }
 
-
-    pub fn print_status(&self) {
-        println!("{BOLD}{CYAN}--- Station Status: {} ---{RESET}", self.name);
-        self.yard.print_report(&self.roundhouse);
-        println!("{BOLD}{YELLOW}Warehouse Inventory ({}){RESET}", self.warehouse.inventory.len());
-        for (id, cargo) in &self.warehouse.inventory {
-            println!("  -id: {}, item: {} ({}kg)", id, cargo.item, cargo.actual_weight);
+    // Heartbeat duty, for stations without a shed. An engine that's due would only get more likely to break down the longer
+    // it keeps working from here, so it goes light to the nearest shed. Once it's serviced it can find its way home from there.
+    pub fn send_engines_for_service(&mut self) {
+        if self.roundhouse.has_shed || self.needs_its_engines() {
+            return; // Work waiting on power comes first. The engines go once it has gone.
+        }
+        let way_to_shed = self.map.read().unwrap().nearest_shed(self.id);
+        let Some((_, route)) = way_to_shed else {
+            return; // No shed we can reach. They soldier on, and take their chances.
+        };
+        let Some(&shed) = route.last() else { return };
+        let (max_hop, max_gradient) = self.map.read().unwrap().route_profile(&route);
+        for engine in self.roundhouse.take_due_engines(|engine| engine.can_complete_mission(0.0, max_hop, max_gradient)) {
+            println!("{CYAN}[{}] Engine {} is due for service. Sending it light to the shed at Station {} via {:?}.{RESET}", self.name, engine.id, shed, route);
+            let light_engine = Train {
+                id: self.yard.generate_new_train_id(),
+                engines: vec![engine],
+                cars: Vec::new(),
+                mission_id: None,
+                destination: shed,
+                report_to: None,
+                service: false,
+                schedule: None,
+                riders: Vec::new(),
+                highpriority: false,
+                priority: Priority::default(),
+                deadline: None,
+                block: None,
+            };
+            self.dispatch_train(light_engine, route.clone());
         }
     }
-        
 
+    pub fn send_engines_home(&mut self) {
+        let homeward = self.roundhouse.take_homeward_engines(self.needs_its_engines());
+        for engine in homeward {
+            let Some(home) = engine.home else { continue };
+            let route = self.map.read().unwrap().find_shortest_path(self.id, home).map(|(_, route)| route);
+            let Some(route) = route else {
+                println!("{YELLOW}[{}] No way home to Station {} for Engine {}. It stays with us for now.{RESET}", self.name, home, engine.id);
+                self.house_engine(engine);
+                continue;
+            };
+            // Engines refuel at every station, so it only has to manage the worst single hop on its own.
+            let (max_hop, max_gradient) = self.map.read().unwrap().route_profile(&route);
+            if !engine.can_complete_mission(0.0, max_hop, max_gradient) {
+                self.house_engine(engine);
+                continue;
+            }
 
+            println!("{CYAN}[{}] Sending Engine {} ({:?}) home to Station {} via {:?}.{RESET}", self.name, engine.id, engine.engine_type, home, route);
+            let light_engine = Train {
+                id: self.yard.generate_new_train_id(),
+                engines: vec![engine],
+                cars: Vec::new(),
+                mission_id: None,
+                destination: home,
+                report_to: None,
+                service: false,
+                schedule: None,
+                riders: Vec::new(),
+                highpriority: false,
+                priority: Priority::default(),
+                deadline: None,
+                block: None,
+            };
+            self.dispatch_train(light_engine, route);
+        }
+    }
 
-    fn send_mission_failure(&self, mission_id: u32, error: TrainError, reply_to: Sender<Result<Train, TrainError>>) {
-        if reply_to.send(Err(error)).is_err() {
-            println!("{RED}Network Error: Failed to send mission failure report for Mission {}.{RESET}", mission_id);
+    // The other half of TargetMix. For each class we're short of, ask the neighbours to lend one, the same way a
+    // mission asks for power. One engine per ask, and we give the first one a day to turn up before asking again.
+    pub fn top_up_target_mix(&mut self) {
+        if self.roundhouse.rebalance != RebalancePolicy::TargetMix {
+            return;
+        }
+        let now = sim_hours_now();
+        let mut short = Vec::new();
+        for (class, target) in self.roundhouse.target_mix.iter() {
+            let in_stalls = self.roundhouse.stalls.get(class).map_or(0, |queue| queue.len());
+            let in_shed = self.roundhouse.shed.iter().filter(|(engine, _)| engine.engine_type == *class).count();
+            if in_stalls + in_shed >= *target {
+                self.engines_requested_at.remove(class);
+                continue;
+            }
+            match self.engines_requested_at.get(class) {
+                Some(asked) if now - asked < TOP_UP_PATIENCE_HOURS => {}
+                _ => short.push(*class),
+            }
+        }
+        for class in short {
+            println!("{CYAN}[{}] Short of {:?} against our target mix. Asking the neighbours to lend one.{RESET}", self.name, class);
+            let request_id = self.yard.generate_new_request_id();
+            let needs = EngineNeeds { min_capacity: 0.0, paired_capacity: 0.0, max_hop: 0.0, max_gradient: 0.0, class: Some(class) };
+            self.initiate_engine_request(self.id, request_id, None, needs, 8);
+            self.engines_requested_at.insert(class, now);
         }
     }
 
+    pub fn handle_passengers_arrive(&mut self, passengers: Vec<Passenger>) {
+        println!("{BOLD}{CYAN}[{}] {} passenger(s) arrive on the platform.{RESET}", self.name, passengers.len());
+        for passenger in passengers {
+            if passenger.destination == self.id {
+                self.ledger.lock().unwrap().journeys.push(passenger.finish_journey(sim_hours_now())); // Already here. Shortest trip on the railway.
+            } else {
+                self.platform.push(passenger);
+            }
+        }
+    }
 
-    // This is a helper method for processing incoming cars, both from train arrivals and from external sources. It attempts to receive each car into the yard, and if the car contains cargo, it moves the cargo into the warehouse. If any issues arise during this process (such as contraband detection or other intake errors), it logs the issue, moves the car to purgatory, and collects the IDs of any cars that failed intake to include in the MissionReport for transparency.
-    pub fn process_cars (&mut self, cars: Vec<TrainCar>, mission_id: Option<u32>) -> Vec<u32> {
-        let mut failed_ids = Vec::new(); // We can fill this with any issues that arise during processing, and then include it in the MissionReport for transparency and debugging. For now, we'll just keep it empty to represent a perfect process.
-        for car in cars {
-            let car_id_we_just_received = car.id; // Store the ID before we potentially move the car into purgatory
-            match self.yard.receive_car(car) {
-                Ok(Some(cargo)) => { self.warehouse.store(cargo); },
-                Ok(None) => {}, // Car is empty but safely in the yard
-                Err((homeless_car, e)) => {
-                    println!("{RED}Failed to process Car {} during intake: {:?}. Moving to purgatory.{RESET}", car_id_we_just_received, e);
-                    failed_ids.push(homeless_car.id); // Log the ID of the car that caused issues for transparency
-                    let rejected_asset = RejectedAsset::new(homeless_car, e, mission_id);
-                    self.yard.purgatory.push(rejected_asset);
+    // The timetable. Any of our passenger services that's due leaves now, with whoever on the platform is going its way.
+    // A departure that can't run (no coaches, no engine, no route) is cancelled; the next one is still on the board.
+    pub fn run_passenger_services(&mut self) {
+        let now = sim_hours_now();
+        let mut due = Vec::new();
+        for (service, next_departure) in self.services.iter_mut() {
+            if *next_departure <= now {
+                due.push(service.clone());
+                while *next_departure <= now {
+                    *next_departure += service.every_hours;
                 }
             }
         }
-
-        failed_ids
-
+        for service in due {
+            self.start_passenger_service(service);
+        }
     }
 
-    // helper method for the "dispatch train" phase of the mission. This is where we spawn a thread to simulate the train's journey to the next station, and we handle the logic for potential derailments during transit.
-    pub fn dispatch_train(&self, mut train: Train, route: Vec<u32>) {
-        let final_destination = train.destination;
-        let station_tx_clone = self.tx.clone(); // Clone the station's own Sender for use in this method, so we can send SOS if needed
+    fn start_passenger_service(&mut self, service: PassengerService) {
+        let Some((_, route)) = self.map.read().unwrap().find_shortest_path(self.id, service.terminus) else {
+            println!("{RED}[{}] The service to Station {} is cancelled: no way through.{RESET}", self.name, service.terminus);
+            return;
+        };
 
-        let next_stop = route.get(1).cloned().unwrap_or_else(|| final_destination); // The next stop is the second element in the route (index 1), or the final destination if the route is just one stop
-        let next_stop_handle = self.neighbors.get(&next_stop).expect("Next stop must be a neighbor").clone(); // Get the Sender for the next stop
-        let distance_to_next_stop = self.map.get_distance(self.id, next_stop).expect("Distance to next stop must be calculable");
+        let coaches = self.yard.take_empty_cars(CarClass::Coach, service.coaches as usize);
+        if coaches.is_empty() {
+            println!("{RED}[{}] The service to Station {} is cancelled: no coaches in the yard. Asking the neighbours.{RESET}", self.name, service.terminus);
+            let request_id = self.yard.generate_new_request_id();
+            self.initiate_empty_car_request(request_id, CarClass::Coach, service.coaches, 8);
+            return;
+        }
+
+        // People get on all along the line, so the engine has to be good for a full train.
+        let full_weight: u32 = coaches.iter().map(|coach| coach.class.tare() + coach.class.seats() as u32 * PASSENGER_WEIGHT).sum();
+        let (max_hop, max_gradient) = self.map.read().unwrap().route_profile(&route);
+        let engines = match self.roundhouse.find_suitable_consist(full_weight as f64, max_hop, max_gradient) {
+            Ok(engines) => engines,
+            Err(e) => {
+                println!("{RED}[{}] The service to Station {} is cancelled: no engine for it ({:?}).{RESET}", self.name, service.terminus, e);
+                for coach in coaches {
+                    self.yard.cars.insert(coach.id, coach);
+                }
+                return;
+            }
+        };
+
+        let mut train = Train {
+            id: self.yard.generate_new_train_id(),
+            engines,
+            cars: coaches,
+            mission_id: None,
+            destination: service.terminus,
+            report_to: None,
+            service: true,
+            schedule: None,
+            riders: Vec::new(),
+            highpriority: true,
+            priority: Priority::default(),
+            deadline: None,
+            block: None,
+        };
+        self.board_passengers(&mut train, &route);
+        println!("{BOLD}{GREEN}[{}] 🚃 Passenger train {} departs for Station {}, calling at {:?}.{RESET}", self.name, train.id, service.terminus, &route[1..]);
+        self.dispatch_train(train, route);
+    }
+
+    // Whoever on the platform is going somewhere this train calls at gets on, while there are seats.
+    fn board_passengers(&mut self, train: &mut Train, route: &[u32]) {
+        let now = sim_hours_now();
+        let calls_at: HashSet<u32> = route.iter().skip(1).copied().collect();
+        let mut boarded = 0;
+        for car in train.cars.iter_mut() {
+            let mut i = 0;
+            while i < self.platform.len() && car.passengers.len() < car.class.seats() {
+                if calls_at.contains(&self.platform[i].destination) {
+                    let mut passenger = self.platform.remove(i);
+                    passenger.boarded_at.get_or_insert(now); // Changing trains doesn't reset the clock
+                    car.passengers.push(passenger);
+                    boarded += 1;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        if boarded > 0 {
+            println!("{CYAN}[{}] {} passenger(s) board Train {}. {} left on the platform.{RESET}", self.name, boarded, train.id, self.platform.len());
+        }
+    }
+
+    // Passengers for this station step off, and their journey goes in the ledger. With `everyone`, the rest get off too
+    // (the train goes no further) and wait on our platform for another one.
+    fn alight_passengers(&mut self, cars: &mut [TrainCar], everyone: bool) {
+        let now = sim_hours_now();
+        for car in cars.iter_mut() {
+            let (arrived, staying): (Vec<Passenger>, Vec<Passenger>) = car.passengers.drain(..).partition(|passenger| passenger.destination == self.id);
+            for passenger in arrived {
+                println!("{GREEN}[{}] {} steps off, {:.1}h after turning up at Station {}.{RESET}", self.name, passenger.name, now - passenger.arrived_at, passenger.origin);
+                self.ledger.lock().unwrap().journeys.push(passenger.finish_journey(now));
+            }
+            if everyone {
+                self.platform.extend(staying);
+            } else {
+                car.passengers = staying;
+            }
+        }
+    }
+
+    // --- Timetabled services ---
+
+    /// The soonest timetabled service that calls here and then at the mission's destination, leaves its origin within
+    /// the booking window, and has room for this much freight at all. Returns the service ID and when it leaves.
+    fn find_service_for(&self, mission: &Mission) -> Option<(String, f64)> {
+        let manifest: Vec<&Cargo> = mission.cargo_ids.iter().filter_map(|id| self.warehouse.inventory.get(id)).collect();
+        if manifest.len() < mission.cargo_ids.len() {
+            return None; // Let the usual assembly report what's missing
+        }
+        let cars_needed = manifest.iter()
+            .map(|item| CarClass::for_load(item.category, item.actual_weight).map_or(u32::MAX, |(_, count)| count))
+            .fold(0, u32::saturating_add);
+
+        let now = sim_hours_now();
+        self.timetable.iter()
+            .filter(|service| service.serves(self.id, mission.destination) && cars_needed <= service.max_cars)
+            .filter_map(|service| service.next_departure(now).map(|departs| (service.id.clone(), departs)))
+            .filter(|(_, departs)| departs - now <= BOOKING_WINDOW_HOURS)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    // A freight train passing through picks up waiting freight for stations further down its way (`ahead`): missions
+    // still in the marshalling sidings, and missions parked for want of an engine. Most urgent first. Each is coupled
+    // on in cars of its own, labelled for where they come off, and reported when it's set down. Trains run to order
+    // and consolidated trains stop for it; passenger trains, light engines and timetabled runs (which have their bookings) don't.
+    fn pick_up_along_the_way(&mut self, train: &mut Train, ahead: &[u32]) {
+        let takes_freight = match &train.schedule {
+            Some(run) => run.service_id == CONSOLIDATED_SERVICE,
+            None => train.mission_id.is_some() && !train.service,
+        };
+        if !takes_freight {
+            return;
+        }
+        let mut waiting: Vec<(Mission, Option<f64>)> = Vec::new(); // With when it went into the marshalling sidings, if it's from there
+        for (mission, since) in self.marshalling.drain(..).collect::<Vec<_>>() {
+            if ahead.contains(&mission.destination) {
+                waiting.push((mission, Some(since)));
+            } else {
+                self.marshalling.push((mission, since));
+            }
+        }
+        for mission in self.pending_missions.drain(..).collect::<Vec<_>>() {
+            if ahead.contains(&mission.destination) {
+                waiting.push((mission, None));
+            } else {
+                self.pending_missions.push(mission);
+            }
+        }
+        waiting.sort_by(|a, b| by_urgency((a.0.priority, a.0.deadline), (b.0.priority, b.0.deadline)));
+
+        for (mission, since) in waiting {
+            match self.load_rider(train, &mission) {
+                Ok(cars) => {
+                    println!("{GREEN}[{}] Mission {} is coupled on to Train {} ({} car(s)), which is passing through for Station {}.{RESET}", self.name, mission.id, train.id, cars, mission.destination);
+                    self.no_longer_short(mission.id);
+                },
+                Err(reason) => {
+                    println!("{YELLOW}[{}] Mission {} can't ride on Train {}: {}.{RESET}", self.name, mission.id, train.id, reason);
+                    match since {
+                        Some(since) => self.marshalling.push((mission, since)),
+                        None => self.pending_missions.push(mission),
+                    }
+                },
+            }
+        }
+    }
+
+    // Off the service (or out of the marshalling sidings) and onto a train of its own. The attempt counter stops us booking
+    // or marshalling it straight back again.
+    fn assemble_alone(&mut self, mut mission: Mission) {
+        mission.attempts += 1;
+        self.handle_assemble_mission(mission);
+    }
+
+    // The Scheduler says a service that starts here is due away. It goes whether or not anyone has booked on it, behind an
+    // engine of the timetabled class. If we haven't got one in, the run is held until one turns up (see check_timetable).
+    pub fn handle_run_service(&mut self, service_id: String, run_id: u32) {
+        let Some(service) = self.timetable.iter().find(|service| service.id == service_id).cloned() else {
+            println!("{RED}[{}] Service {} isn't in the timetable. Ignoring run {}.{RESET}", self.name, service_id, run_id);
+            return;
+        };
+        match self.roundhouse.dispatch(service.engine_class) {
+            Some(engine) => self.start_service_run(service, run_id, engine),
+            None => {
+                println!("{YELLOW}[{}] No {:?} in for Service {} (run {}). Holding it until one turns up.{RESET}", self.name, service.engine_class, service.id, run_id);
+                self.held_runs.push((service, run_id, sim_hours_now()));
+            },
+        }
+    }
+
+    fn start_service_run(&mut self, service: ScheduledService, run_id: u32, mut engine: Engine) {
+        // Plan the whole run up front, so we know the worst hop the engine will ever face with whatever it picks up.
+        let legs: Option<Vec<(f64, f64)>> = {
+            let map = self.map.read().unwrap();
+            service.route.windows(2)
+                .map(|leg| map.find_shortest_path(leg[0], leg[1]).map(|(_, path)| map.route_profile(&path)))
+                .collect()
+        };
+        let Some(legs) = legs.filter(|legs| !legs.is_empty()) else {
+            println!("{RED}[{}] Service {} (run {}) is cancelled: no way through {:?}.{RESET}", self.name, service.id, run_id, service.route);
+            self.house_engine(engine);
+            self.cancel_run(run_id);
+            return;
+        };
+        let max_hop = legs.iter().map(|(hop, _)| *hop).fold(0.0, f64::max);
+        let max_gradient = legs.iter().map(|(_, gradient)| *gradient).fold(0.0, f64::max);
+
+        engine.refuel();
+        if !engine.can_complete_mission(0.0, max_hop, max_gradient) {
+            println!("{RED}[{}] Service {} (run {}) is cancelled: Engine {} can't manage the line even running light.{RESET}", self.name, service.id, run_id, engine.id);
+            self.house_engine(engine);
+            self.cancel_run(run_id);
+            return;
+        }
+
+        let mut train = Train {
+            id: self.yard.generate_new_train_id(),
+            engines: vec![engine],
+            cars: Vec::new(),
+            mission_id: None,
+            destination: service.route[service.route.len() - 1],
+            report_to: None,
+            service: false,
+            schedule: Some(ScheduledRun {
+                run_id,
+                service_id: service.id.clone(),
+                calls: service.route.iter().skip(1).copied().collect(),
+                max_cars: service.max_cars,
+                max_hop,
+                max_gradient,
+            }),
+            riders: Vec::new(),
+            highpriority: true, // It has a timetable to keep
+            priority: Priority::default(),
+            deadline: None,
+            block: None,
+        };
+        self.pick_up_bookings(&mut train);
+
+        let now = sim_hours_now();
+        if let Some(record) = self.ledger.lock().unwrap().service_runs.get_mut(&run_id) {
+            record.departed_at = Some(now);
+            let late = now - record.scheduled_at;
+            if late > 0.5 {
+                println!("{YELLOW}[{}] Service {} (run {}) is away {:.1}h late.{RESET}", self.name, service.id, run_id, late);
+            }
+        }
+        println!("{BOLD}{GREEN}[{}] 🕑 Service {} (run {}) departs as Train {} with {} car(s), calling at {:?}.{RESET}", self.name, service.id, run_id, train.id, train.cars.len(), &service.route[1..]);
+        self.send_on_to_next_call(train);
+    }
+
+    // A timetabled train pulls in. If this is one of its calls, riders for here get off and booked freight gets on.
+    // Otherwise it's just passing through on its way to the next call.
+    fn handle_scheduled_arrival(&mut self, mut train: Train) {
+        let Some(run) = train.schedule.as_mut() else {
+            return;
+        };
+        if run.calls.front() != Some(&self.id) {
+            let calls_ahead: Vec<u32> = run.calls.iter().copied().collect();
+            self.pick_up_along_the_way(&mut train, &calls_ahead); // A consolidated train will stop for freight going its way
+            self.send_on_to_next_call(train);
+            return;
+        }
+        run.calls.pop_front();
+        let terminus = run.calls.is_empty();
+        println!("{GREEN}[{}] Service {} (run {}) calls here{}.{RESET}", self.name, run.service_id, run.run_id, if terminus { " and terminates" } else { "" });
+
+        self.set_down_riders(&mut train, terminus);
+        if terminus {
+            self.end_service_run(train, true);
+            return;
+        }
+        self.pick_up_bookings(&mut train);
+        let calls_ahead: Vec<u32> = train.schedule.as_ref().map(|run| run.calls.iter().copied().collect()).unwrap_or_default();
+        self.pick_up_along_the_way(&mut train, &calls_ahead);
+        self.send_on_to_next_call(train);
+    }
+
+    // Off to the next calling point by the shortest path. A call we can no longer reach (track closed, station gone) is skipped.
+    fn send_on_to_next_call(&mut self, mut train: Train) {
+        loop {
+            let Some(next_call) = train.schedule.as_ref().and_then(|run| run.calls.front().copied()) else {
+                println!("{RED}[{}] Train {} has no calls left that it can reach. The run ends here.{RESET}", self.name, train.id);
+                self.end_service_run(train, false);
+                return;
+            };
+            let path = self.map.read().unwrap().find_shortest_path(self.id, next_call).map(|(_, route)| route);
+            if let Some(route) = path {
+                self.dispatch_train(train, route);
+                return;
+            }
+            println!("{YELLOW}[{}] No way through to Station {} for Train {}. Skipping that call.{RESET}", self.name, next_call, train.id);
+            if let Some(run) = train.schedule.as_mut() {
+                run.calls.pop_front();
+            }
+        }
+    }
+
+    // Booked freight whose destination the train still calls at gets on, while there's room and the engine can pull it.
+    // Anything that doesn't fit gets bumped onto a train of its own.
+    fn pick_up_bookings(&mut self, train: &mut Train) {
+        let Some(run) = train.schedule.as_ref() else {
+            return;
+        };
+        let (service_id, run_id) = (run.service_id.clone(), run.run_id);
+        let calls: Vec<u32> = run.calls.iter().copied().collect();
+        let (boarding, waiting): (Vec<_>, Vec<_>) = self.booked.drain(..).partition(|(id, _, mission)| *id == service_id && calls.contains(&mission.destination));
+        self.booked = waiting;
+
+        for (_, _, mission) in boarding {
+            match self.load_rider(train, &mission) {
+                Ok(cars) => {
+                    println!("{GREEN}[{}] Mission {} joins Service {} (Train {}) with {} car(s).{RESET}", self.name, mission.id, service_id, train.id, cars);
+                    if let Some(record) = self.ledger.lock().unwrap().service_runs.get_mut(&run_id) {
+                        record.cars_carried += cars as u32;
+                    }
+                },
+                Err(reason) => {
+                    println!("{YELLOW}[{}] Mission {} can't ride Service {}: {}. It gets a train of its own.{RESET}", self.name, mission.id, service_id, reason);
+                    self.assemble_alone(mission);
+                },
+            }
+        }
+    }
+
+    // Loads one mission's cargo and couples it on. Checks everything before touching the warehouse, so a refusal leaves nothing half-loaded.
+    // A timetabled run has its own limits. A train run to order can be as long as it likes, so long as the engines can
+    // manage it the rest of the way.
+    fn load_rider(&mut self, train: &mut Train, mission: &Mission) -> Result<usize, String> {
+        let (max_cars, max_hop, max_gradient) = match train.schedule.as_ref() {
+            Some(run) => (run.max_cars as usize, run.max_hop, run.max_gradient),
+            None => {
+                let map = self.map.read().unwrap();
+                let rest_of_the_way = map.find_shortest_path(self.id, train.destination).map(|(_, route)| route).unwrap_or_default();
+                let (max_hop, max_gradient) = map.route_profile(&rest_of_the_way);
+                (usize::MAX, max_hop, max_gradient)
+            },
+        };
+
+        let manifest: Vec<&Cargo> = mission.cargo_ids.iter().filter_map(|id| self.warehouse.inventory.get(id)).collect();
+        if manifest.len() < mission.cargo_ids.len() {
+            return Err("some of its cargo isn't in the warehouse".to_string());
+        }
+        let (tare, cars_needed, unmatched) = self.yard.plan_loading(&manifest);
+        if !unmatched.is_empty() {
+            return Err("not enough empty cars in the yard".to_string());
+        }
+        if train.cars.len() + cars_needed > max_cars {
+            return Err(format!("no room ({} car(s) on, {} wanted, {} allowed)", train.cars.len(), cars_needed, max_cars));
+        }
+        let cargo_weight: u32 = manifest.iter().map(|item| item.actual_weight).sum();
+        let weight = train.calculate_trailing_weight() + (tare + cargo_weight) as f64;
+        if !consist_can_complete_mission(&train.engines.iter().collect::<Vec<&Engine>>(), weight, max_hop, max_gradient) {
+            return Err(format!("too heavy for the engine at {}kg", weight));
+        }
+
+        let cargo = self.warehouse.get_cargo_by_ids(&mission.cargo_ids).map_err(|e| format!("{:?}", e))?;
+        let car_ids = self.yard.load_for_pickup(cargo, mission.destination).map_err(|e| format!("{:?}", e))?;
+        let count = car_ids.len();
+        for car_id in car_ids {
+            self.yard.couple_by_id(train, car_id);
+        }
+        let distance = self.map.read().unwrap().find_shortest_path(self.id, mission.destination).map(|(distance, _)| distance);
+        if let Some(distance) = distance {
+            self.plan_arrival(mission.id, distance, train);
+        }
+        train.riders.push(Rider { mission_id: mission.id, destination: mission.destination, cargo_ids: mission.cargo_ids.clone(), report_to: mission.reply_channel.clone(), priority: mission.priority, deadline: mission.deadline });
+        Ok(count)
+    }
+
+    // Riders for this station (with `everyone`, all of them) are uncoupled into the yard and unloaded, and their producers told.
+    // Freight that was going further than this train got is re-ordered from here, like salvage after a breakdown.
+    fn set_down_riders(&mut self, train: &mut Train, everyone: bool) {
+        let (alighting, staying): (Vec<Rider>, Vec<Rider>) = train.riders.drain(..).partition(|rider| everyone || rider.destination == self.id);
+        train.riders = staying;
+        let carrier = match &train.schedule {
+            Some(run) if run.service_id == CONSOLIDATED_SERVICE => "consolidated",
+            Some(_) => "timetabled",
+            None => "passing",
+        };
+
+        for rider in alighting {
+            let theirs: Vec<&TrainCar> = train.cars.iter().filter(|car| car.cargo.as_ref().is_some_and(|cargo| rider.cargo_ids.contains(&cargo.id))).collect();
+            let car_ids: Vec<u32> = theirs.iter().map(|car| car.id).collect();
+            let split_ids = split_cargo_ids(theirs);
+            let mut failed_ids = Vec::new();
+            let mut spoiled = Vec::new();
+            for car_id in car_ids {
+                match self.yard.decouple_by_id(train, car_id) {
+                    Ok(Some(cargo)) if cargo.is_spoiled() => { // The yard brought its shelf life up to date on the way in
+                        if !spoiled.contains(&cargo.id) {
+                            spoiled.push(cargo.id);
+                        }
+                        self.warehouse.dispose(cargo);
+                    },
+                    Ok(Some(cargo)) => self.store_cargo(cargo),
+                    Ok(None) => {},
+                    Err(car_id) => failed_ids.push(car_id),
+                }
+            }
+            let cargo_ids: Vec<u32> = rider.cargo_ids.iter().copied().filter(|id| !spoiled.contains(id)).collect();
+            let incomplete: Vec<u32> = split_ids.into_iter().filter(|id| self.warehouse.is_incomplete(*id)).collect();
+            let late_by = if rider.destination == self.id && !cargo_ids.is_empty() {
+                self.awaiting_collection.extend(cargo_ids.iter().copied());
+                self.record_delivery(rider.mission_id)
+            } else {
+                None
+            };
+
+            if rider.destination != self.id && cargo_ids.is_empty() {
+                let details = format!("Cargo {:?} spoiled on the {} train and has gone for disposal at {}.", spoiled, carrier, self.name);
+                self.send_partial_failure_report(rider.mission_id, &details, &failed_ids, rider.report_to);
+            } else if rider.destination != self.id {
+                self.ledger.lock().unwrap().pending_cargo.push(FreightOrder {
+                    id: rider.mission_id,
+                    cargo_ids: cargo_ids.clone(),
+                    destination: rider.destination,
+                    origin: self.id,
+                    ttl: 5,
+                    priority: rider.priority,
+                    not_before: None,
+                    deadline: rider.deadline,
+                });
+                let details = format!("The {} train went no further than {}. Cargo {:?} is waiting here for another train.", carrier, self.name, cargo_ids);
+                self.send_partial_failure_report(rider.mission_id, &details, &failed_ids, rider.report_to);
+            } else if !spoiled.is_empty() {
+                let details = format!("Cargo {:?} had spoiled by the time it arrived and has gone for disposal.", spoiled);
+                self.send_partial_failure_report(rider.mission_id, &details, &failed_ids, rider.report_to);
+            } else if failed_ids.is_empty() && incomplete.is_empty() {
+                let details = format!("Delivered by {} train. All cargo processed without issues.", carrier);
+                match late_by {
+                    Some(hours_late) => self.send_late_report(rider.mission_id, &details, hours_late, rider.report_to),
+                    None => self.send_success_report(rider.mission_id, &details, rider.report_to),
+                }
+            } else if !incomplete.is_empty() {
+                let details = format!("Consignment(s) {:?} arrived with parts missing and can't be rejoined.", incomplete);
+                self.send_partial_failure_report(rider.mission_id, &details, &failed_ids, rider.report_to);
+            } else {
+                self.send_partial_failure_report(rider.mission_id, "Partial success during train disassembly. Some items in purgatory.", &failed_ids, rider.report_to);
+            }
+        }
+    }
+
+    // The run is over, at the terminus or short of it. The engine and any empty cars stay here, and the ledger gets the time.
+    fn end_service_run(&mut self, mut train: Train, arrived: bool) {
+        self.release_block(&mut train);
+        self.set_down_riders(&mut train, true);
+        for engine in train.engines.drain(..) {
+            self.house_engine(engine);
+        }
+        self.process_cars(train.cars, None);
+
+        if let Some(run) = train.schedule {
+            if !arrived {
+                self.cancel_run(run.run_id);
+            } else if let Some(record) = self.ledger.lock().unwrap().service_runs.get_mut(&run.run_id) {
+                let now = sim_hours_now();
+                record.arrived_at = Some(now);
+                let colour = if record.is_on_time() { GREEN } else { YELLOW };
+                println!("{colour}[{}] Service {} (run {}) in at {:.1}h, due {:.1}h.{RESET}", self.name, run.service_id, run.run_id, now, record.due_at);
+            }
+        }
+        self.retry_pending_missions(); // We've an engine more than we had
+    }
+
+    fn cancel_run(&self, run_id: u32) {
+        if let Some(record) = self.ledger.lock().unwrap().service_runs.get_mut(&run_id) {
+            record.cancelled = true;
+        }
+    }
+
+    // Heartbeat duty for the timetable: held runs try again for an engine (or give up), and freight whose train never came gets one of its own.
+    pub fn check_timetable(&mut self) {
+        let now = sim_hours_now();
+        for (service, run_id, held_since) in self.held_runs.drain(..).collect::<Vec<_>>() {
+            match self.roundhouse.dispatch(service.engine_class) {
+                Some(engine) => self.start_service_run(service, run_id, engine),
+                None if now - held_since > HOLD_LIMIT_HOURS => {
+                    println!("{RED}[{}] Still no {:?} for Service {} (run {}). It's cancelled.{RESET}", self.name, service.engine_class, service.id, run_id);
+                    self.cancel_run(run_id);
+                },
+                None => self.held_runs.push((service, run_id, held_since)),
+            }
+        }
+
+        let (stale, fresh): (Vec<_>, Vec<_>) = self.booked.drain(..).partition(|(_, departs, _)| now - departs > BOOKING_GRACE_HOURS);
+        self.booked = fresh;
+        for (service_id, _, mission) in stale {
+            println!("{YELLOW}[{}] Service {} never came for Mission {}. Finding it a train of its own.{RESET}", self.name, service_id, mission.id);
+            self.assemble_alone(mission);
+        }
+    }
+
+    // Perishables that went off on the shelf go in the skip. Anyone waiting on them hears about it: a parked or booked
+    // mission loses the spoiled cargo and the rest is sent back to the ledger under the same mission, and orders nobody
+    // has picked up yet just get shorter.
+    pub fn check_perishables(&mut self) {
+        let spoiled = self.warehouse.clear_spoiled();
+        if spoiled.is_empty() {
+            return;
+        }
+        println!("{RED}[{}]::Station {}: Cargo {:?} spoiled in the warehouse.{RESET}", self.name, self.id, spoiled);
+
+        let waiting: Vec<Mission> = self.pending_missions.drain(..).collect();
+        let marshalling: Vec<(Mission, f64)> = self.marshalling.drain(..).collect();
+        let booked: Vec<BookedMission> = self.booked.drain(..).collect();
+        let held: Vec<(Mission, f64)> = self.held_for_window.drain(..).collect();
+        let mut affected = Vec::new();
+        for mission in waiting {
+            if mission.cargo_ids.iter().any(|id| spoiled.contains(id)) {
+                affected.push(mission);
+            } else {
+                self.pending_missions.push(mission);
+            }
+        }
+        for (mission, since) in marshalling {
+            if mission.cargo_ids.iter().any(|id| spoiled.contains(id)) {
+                affected.push(mission);
+            } else {
+                self.marshalling.push((mission, since));
+            }
+        }
+        for (service_id, departs, mission) in booked {
+            if mission.cargo_ids.iter().any(|id| spoiled.contains(id)) {
+                affected.push(mission);
+            } else {
+                self.booked.push((service_id, departs, mission));
+            }
+        }
+        for (mission, leave_at) in held {
+            if mission.cargo_ids.iter().any(|id| spoiled.contains(id)) {
+                affected.push(mission);
+            } else {
+                self.held_for_window.push((mission, leave_at));
+            }
+        }
+
+        for mission in affected {
+            self.no_longer_short(mission.id); // If it's re-ordered, the order comes back as a new mission and asks again
+            let (lost, good): (Vec<u32>, Vec<u32>) = mission.cargo_ids.iter().partition(|id| spoiled.contains(id));
+            let details = format!("Cargo {:?} spoiled while waiting at {} and has gone for disposal.", lost, self.name);
+            self.send_partial_failure_report(mission.id, &details, &lost, mission.reply_channel.clone());
+            if !good.is_empty() {
+                println!("{YELLOW}[{}]::Station {}: Mission {} goes on without the spoiled cargo. Re-ordering {:?}.{RESET}", self.name, self.id, mission.id, good);
+                self.ledger.lock().unwrap().pending_cargo.push(FreightOrder {
+                    id: mission.id,
+                    cargo_ids: good,
+                    origin: self.id,
+                    destination: mission.destination,
+                    ttl: 5,
+                    priority: mission.priority,
+                    not_before: None,
+                    deadline: mission.deadline,
+                });
+            }
+        }
+
+        // Orders still on the ledger that haven't been picked up. There's no mission yet, so nobody to tell.
+        let mut ledger = self.ledger.lock().unwrap();
+        for order in ledger.pending_cargo.iter_mut().filter(|order| order.origin == self.id) {
+            order.cargo_ids.retain(|id| !spoiled.contains(id));
+        }
+        ledger.pending_cargo.retain(|order| order.origin != self.id || !order.cargo_ids.is_empty());
+    }
+
+    // Checks each arriving load before it's unloaded. Anything that went off on the way goes straight in the skip, and
+    // its car is left empty. Returns the IDs of the spoiled consignments.
+    fn dispose_spoiled_loads(&mut self, cars: &mut [TrainCar]) -> Vec<u32> {
+        let mut spoiled = Vec::new();
+        for car in cars.iter_mut() {
+            let chilled = car.class == CarClass::Reefer;
+            if car.cargo.as_mut().is_some_and(|cargo| cargo.age(chilled)) && let Some(cargo) = car.cargo.take() {
+                if !spoiled.contains(&cargo.id) {
+                    spoiled.push(cargo.id);
+                }
+                self.warehouse.dispose(cargo);
+            }
+        }
+        spoiled
+    }
+
+    // Heartbeat duty for the marshalling sidings. Once a mission has waited out the window, everything waiting is made up
+    // into trains: missions whose routes lie along one another share one, and each is set down at its own stop.
+    // Heartbeat duty. Missions held for their delivery window go on their way once they can leave without arriving early.
+    pub fn release_held_for_window(&mut self) {
+        let now = sim_hours_now();
+        let (due, waiting): (Vec<_>, Vec<_>) = self.held_for_window.drain(..).partition(|(_, leave_at)| now >= *leave_at);
+        self.held_for_window = waiting;
+        for (mission, _) in due {
+            println!("{CYAN}[{}] Mission {} can set out now and still not be early.{RESET}", self.name, mission.id);
+            self.handle_assemble_mission(mission);
+        }
+    }
+
+    pub fn marshal_trains(&mut self) {
+        let now = sim_hours_now();
+        if !self.marshalling.iter().any(|(_, since)| now - since >= CONSOLIDATION_WINDOW_HOURS) {
+            return;
+        }
+        let mut waiting: Vec<(Mission, f64, Vec<u32>)> = Vec::new();
+        for (mission, _) in self.marshalling.drain(..).collect::<Vec<_>>() {
+            let path = self.map.read().unwrap().find_shortest_path(self.id, mission.destination);
+            match path {
+                Some((distance, route)) if route.len() > 1 => waiting.push((mission, distance, route)),
+                _ => self.assemble_alone(mission), // Nowhere to go, or already here. The usual assembly can sort it out.
+            }
+        }
+
+        for members in along_one_line(waiting, |(_, _, route)| route) {
+            self.assemble_consolidated(members);
+        }
+    }
+
+    // One train for several missions going the same way. If the yard and roundhouse can't manage the lot, the least urgent
+    // mission is taken off to go on its own, and so on until they can. A mission left on its own just goes the usual way.
+    fn assemble_consolidated(&mut self, members: Vec<(Mission, f64, Vec<u32>)>) {
+        // Missing cargo is for the usual assembly to report.
+        let (mut members, missing): (Vec<_>, Vec<_>) = members.into_iter()
+            .partition(|(mission, _, _)| mission.cargo_ids.iter().all(|id| self.warehouse.inventory.contains_key(id)));
+        for (mission, _, _) in missing {
+            self.assemble_alone(mission);
+        }
+        members.sort_by(|a, b| by_urgency((a.0.priority, a.0.deadline), (b.0.priority, b.0.deadline)));
+
+        let (engines, trunk, max_hop, max_gradient) = loop {
+            if members.len() < 2 {
+                for (mission, _, _) in members {
+                    self.assemble_alone(mission);
+                }
+                return;
+            }
+            let trunk = members.iter().map(|(_, _, route)| route).max_by_key(|route| route.len()).cloned().unwrap_or_default();
+            let manifest: Vec<&Cargo> = members.iter().flat_map(|(mission, _, _)| &mission.cargo_ids).filter_map(|id| self.warehouse.inventory.get(id)).collect();
+            let (tare, _, unmatched) = self.yard.plan_loading(&manifest);
+            let weight = tare + manifest.iter().map(|item| item.actual_weight).sum::<u32>();
+            let (max_hop, max_gradient) = self.map.read().unwrap().route_profile(&trunk);
+            if unmatched.is_empty() && let Ok(engines) = self.roundhouse.find_suitable_consist(weight as f64, max_hop, max_gradient) {
+                break (engines, trunk, max_hop, max_gradient);
+            }
+            if let Some((mission, _, _)) = members.pop() {
+                println!("{YELLOW}[{}] Mission {} is one too many for a shared train ({}kg all told). It goes on its own.{RESET}", self.name, mission.id, weight);
+                self.assemble_alone(mission);
+            }
+        };
+
+        let cargo_ids: Vec<u32> = members.iter().flat_map(|(mission, _, _)| mission.cargo_ids.iter().copied()).collect();
+        let cars = match self.warehouse.get_cargo_by_ids(&cargo_ids).and_then(|cargo| self.yard.assemble_cars(cargo)) {
+            Ok(cars) => cars,
+            Err(e) => {
+                println!("{RED}Yard Error: Failed to make up a shared train for Missions {:?}: {:?}.{RESET}", members.iter().map(|(mission, _, _)| mission.id).collect::<Vec<_>>(), e);
+                for engine in engines {
+                    self.house_engine(engine);
+                }
+                for (mission, _, _) in &members {
+                    self.report_mission_failure(mission, &format!("Failed to assemble cars for a shared train: {:?}", e));
+                }
+                return;
+            }
+        };
+
+        // It calls wherever somebody gets off, in the order it comes to them. The last call is the end of the line.
+        let calls: VecDeque<u32> = trunk.iter().skip(1).copied().filter(|stop| members.iter().any(|(mission, _, _)| mission.destination == *stop)).collect();
+        let mut train = Train {
+            id: self.yard.generate_new_train_id(),
+            engines,
+            mission_id: None,
+            destination: calls.back().copied().unwrap_or(self.id),
+            report_to: None,
+            service: false,
+            schedule: Some(ScheduledRun {
+                run_id: next_run_id(),
+                service_id: CONSOLIDATED_SERVICE.to_string(),
+                calls: calls.clone(),
+                max_cars: u32::MAX, // It isn't a timetabled train with a booked length. Only the engines limit what it picks up.
+                max_hop,
+                max_gradient,
+            }),
+            riders: Vec::new(),
+            cars,
+            highpriority: members.iter().any(|(mission, _, _)| mission.highpriority),
+            priority: Priority::default(), // Its freight all rides with its own terms
+            deadline: None,
+            block: None,
+        };
+        for (mission, distance, _) in &members {
+            self.plan_arrival(mission.id, *distance, &train);
+            for car in train.cars.iter_mut().filter(|car| car.cargo.as_ref().is_some_and(|cargo| mission.cargo_ids.contains(&cargo.id))) {
+                car.destination = Some(mission.destination);
+            }
+        }
+        train.riders = members.into_iter().map(|(mission, _, _)| Rider {
+            mission_id: mission.id,
+            destination: mission.destination,
+            cargo_ids: mission.cargo_ids,
+            report_to: mission.reply_channel,
+            priority: mission.priority,
+            deadline: mission.deadline,
+        }).collect();
+        let missions: Vec<u32> = train.riders.iter().map(|rider| rider.mission_id).collect();
+        println!("{BOLD}{GREEN}[{}] 🚂 Missions {:?} share Train {} ({} car(s)), calling at {:?}.{RESET}", self.name, missions, train.id, train.cars.len(), calls);
+        self.send_on_to_next_call(train);
+    }
+
+    // A hub breaks up freight trains run to order, and consolidated trains, that are going on past it. Passenger trains,
+    // timetabled services, light engines and empty-car trains go through as usual, and so does anything that ends here.
+    fn breaks_up(&self, train: &Train) -> bool {
+        if !self.classification_hub || train.service {
+            return false;
+        }
+        match &train.schedule {
+            Some(run) => run.service_id == CONSOLIDATED_SERVICE && run.calls.back() != Some(&self.id),
+            None => train.destination != self.id && (train.mission_id.is_some() || !train.riders.is_empty()),
+        }
+    }
+
+    // In at the hub. Cars for here are set down as usual and the engines go in the roundhouse. Every other consignment is
+    // filed on the classification tracks under the next station on its way, still in its cars, to wait for a train out.
+    fn break_up_at_hub(&mut self, mut train: Train) {
+        self.release_block(&mut train);
+        self.set_down_riders(&mut train, false);
+
+        // The train's own freight is one more consignment, like the ones it picked up on the way.
+        if let Some(mission_id) = train.mission_id.take() {
+            let mut cargo_ids: Vec<u32> = train.cars.iter()
+                .filter_map(|car| car.cargo.as_ref().map(|cargo| cargo.id))
+                .filter(|id| !train.riders.iter().any(|rider| rider.cargo_ids.contains(id)))
+                .collect();
+            cargo_ids.sort_unstable();
+            cargo_ids.dedup(); // The parts of a split consignment share an ID, and needn't ride next to each other
+            train.riders.push(Rider { mission_id, destination: train.destination, cargo_ids, report_to: train.report_to.take(), priority: train.priority, deadline: train.deadline });
+        }
+
+        let now = sim_hours_now();
+        let mut filed = Vec::new();
+        for rider in train.riders.drain(..).collect::<Vec<_>>() {
+            let next_hop = self.map.read().unwrap().find_shortest_path(self.id, rider.destination).and_then(|(_, route)| route.get(1).copied());
+            let Some(next_hop) = next_hop else {
+                train.riders.push(rider); // No way on from here. It's set down and re-ordered with the rest below.
+                continue;
+            };
+            let (mut theirs, rest): (Vec<TrainCar>, Vec<TrainCar>) = train.cars.drain(..).partition(|car| car.cargo.as_ref().is_some_and(|cargo| rider.cargo_ids.contains(&cargo.id)));
+            train.cars = rest;
+            for car in theirs.iter_mut() {
+                car.destination = Some(rider.destination);
+            }
+            filed.push((rider.mission_id, next_hop));
+            self.yard.classification.entry(next_hop).or_default().push(Cut { rider, cars: theirs, arrived_at: now });
+        }
+        self.set_down_riders(&mut train, true);
+        for engine in train.engines.drain(..) {
+            self.house_engine(engine);
+        }
+        self.process_cars(train.cars, None); // Any empties go into the yard
+
+        println!("{BOLD}{CYAN}[{}] 🔀 Train {} is broken up at the hub. Missions (and next hops) {:?} are on the classification tracks.{RESET}", self.name, train.id, filed);
+        self.retry_pending_missions(); // We've an engine or two more than we had
+    }
+
+    // Heartbeat duty for a hub. The cars for a next hop go out once there are enough of them to be worth a train, or the
+    // first of them has waited long enough. They're made up into one consolidated train for each line of route beyond it.
+    pub fn make_up_outbound_trains(&mut self) {
+        let now = sim_hours_now();
+        let due: Vec<u32> = self.yard.classification.iter()
+            .filter(|(_, cuts)| {
+                cuts.iter().map(|cut| cut.cars.len()).sum::<usize>() >= self.hub_departure_cars
+                    || cuts.iter().any(|cut| now - cut.arrived_at >= self.hub_max_dwell_hours)
+            })
+            .map(|(next_hop, _)| *next_hop)
+            .collect();
+
+        for next_hop in due {
+            let mut waiting = Vec::new();
+            for cut in self.yard.classification.remove(&next_hop).unwrap_or_default() {
+                let route = self.map.read().unwrap().find_shortest_path(self.id, cut.rider.destination).map(|(_, route)| route);
+                match route {
+                    Some(route) if route.get(1) == Some(&next_hop) => waiting.push((cut, route)),
+                    Some(route) if route.len() > 1 => self.yard.classification.entry(route[1]).or_default().push(cut), // The line's been closed under it. It goes the other way.
+                    _ => self.give_up_on_cut(cut, "There's no way on from the hub any more."),
+                }
+            }
+            for members in along_one_line(waiting, |(_, route)| route) {
+                self.send_out_cuts(members);
+            }
+        }
+    }
+
+    // One train out of the hub for cuts going along the same line. If no engine here can take them all, the least urgent
+    // cut goes back on the classification tracks, and so on. Whatever's left there waits for the next engine in.
+    fn send_out_cuts(&mut self, mut members: Vec<(Cut, Vec<u32>)>) {
+        members.sort_by(|a, b| by_urgency((a.0.rider.priority, a.0.rider.deadline), (b.0.rider.priority, b.0.rider.deadline)));
+        let (engines, trunk, max_hop, max_gradient) = loop {
+            let Some(trunk) = members.iter().map(|(_, route)| route).max_by_key(|route| route.len()).cloned() else {
+                return;
+            };
+            let weight: u32 = members.iter().flat_map(|(cut, _)| &cut.cars).map(|car| car.gross_weight()).sum();
+            let (max_hop, max_gradient) = self.map.read().unwrap().route_profile(&trunk);
+            if let Ok(engines) = self.roundhouse.find_suitable_consist(weight as f64, max_hop, max_gradient) {
+                break (engines, trunk, max_hop, max_gradient);
+            }
+            if let Some((cut, route)) = members.pop() {
+                if members.is_empty() {
+                    println!("{YELLOW}[{}] No engine here can take Mission {} towards Station {} yet ({}kg). Its {} car(s) stay on the classification tracks.{RESET}", self.name, cut.rider.mission_id, route[1], weight, cut.cars.len());
+                } else {
+                    println!("{YELLOW}[{}] Mission {} is one too many for the engines here ({}kg all told). Its {} car(s) wait for the next train towards Station {}.{RESET}", self.name, cut.rider.mission_id, weight, cut.cars.len(), route[1]);
+                }
+                self.yard.classification.entry(route[1]).or_default().push(cut);
+            }
+        };
+
+        // It calls wherever somebody gets off, in the order it comes to them, and every car's stay goes in the ledger.
+        let now = sim_hours_now();
+        let calls: VecDeque<u32> = trunk.iter().skip(1).copied().filter(|stop| members.iter().any(|(cut, _)| cut.rider.destination == *stop)).collect();
+        let mut cars = Vec::new();
+        let mut riders = Vec::new();
+        {
+            let mut ledger = self.ledger.lock().unwrap();
+            for (cut, route) in members {
+                for car in &cut.cars {
+                    ledger.hub_dwell.push(DwellRecord { hub: self.id, car_id: car.id, next_hop: route[1], arrived_at: cut.arrived_at, departed_at: now });
+                }
+                cars.extend(cut.cars);
+                riders.push(cut.rider);
+            }
+        }
+        let train = Train {
+            id: self.yard.generate_new_train_id(),
+            engines,
+            cars,
+            mission_id: None,
+            destination: calls.back().copied().unwrap_or(self.id),
+            report_to: None,
+            service: false,
+            schedule: Some(ScheduledRun {
+                run_id: next_run_id(),
+                service_id: CONSOLIDATED_SERVICE.to_string(),
+                calls: calls.clone(),
+                max_cars: u32::MAX,
+                max_hop,
+                max_gradient,
+            }),
+            highpriority: riders.iter().any(|rider| rider.priority >= Priority::High),
+            priority: Priority::default(), // Its freight all rides with its own terms
+            deadline: None,
+            riders,
+            block: None,
+        };
+        let missions: Vec<u32> = train.riders.iter().map(|rider| rider.mission_id).collect();
+        println!("{BOLD}{GREEN}[{}] 🔀 Missions {:?} leave the hub on Train {} ({} car(s)), calling at {:?}.{RESET}", self.name, missions, train.id, train.cars.len(), calls);
+        self.send_on_to_next_call(train);
+    }
+
+    // A consignment that can't go on from the hub after all is unloaded here and re-ordered, like freight whose train went no further.
+    fn give_up_on_cut(&mut self, cut: Cut, reason: &str) {
+        let Cut { rider, cars, .. } = cut;
+        let failed_ids = self.process_cars(cars, Some(rider.mission_id));
+        self.ledger.lock().unwrap().pending_cargo.push(FreightOrder {
+            id: rider.mission_id,
+            cargo_ids: rider.cargo_ids.clone(),
+            destination: rider.destination,
+            origin: self.id,
+            ttl: 5,
+            priority: rider.priority,
+            not_before: None,
+            deadline: rider.deadline,
+        });
+        let details = format!("{} Cargo {:?} was unloaded at {} to wait for another train.", reason, rider.cargo_ids, self.name);
+        self.send_partial_failure_report(rider.mission_id, &details, &failed_ids, rider.report_to);
+    }
+
+    pub fn check_pending_missions(&mut self) {
+        if !self.pending_missions.is_empty() {
+            println!("{YELLOW} Heartbeat Check: Station {} has {} pending missions waiting for resources. Attempting to retry... {RESET}", self.name, self.pending_missions.len());
+            self.retry_pending_missions();
+        }
+    }
+
+
+
+
+    pub fn print_status(&self) {
+        println!("{BOLD}{CYAN}--- Station Status: {} ---{RESET}", self.name);
+        self.yard.print_report(&self.roundhouse);
+        println!("{BOLD}{YELLOW}Warehouse Inventory ({}){RESET}", self.warehouse.inventory.len());
+        for (id, cargo) in &self.warehouse.inventory {
+            println!("  -id: {}, item: {} ({}kg)", id, cargo.item, cargo.actual_weight);
+        }
+        for (id, parts) in &self.warehouse.partials {
+            println!("  -id: {}, item: {} ({} part(s) in, waiting on the rest)", id, parts[0].item, parts.len());
+        }
+        if !self.warehouse.disposal.is_empty() {
+            println!("{BOLD}{RED}Disposal ({} spoiled){RESET}", self.warehouse.disposal.len());
+            for cargo in &self.warehouse.disposal {
+                println!("  -id: {}, item: {} ({}kg)", cargo.id, cargo.item, cargo.actual_weight);
+            }
+        }
+        if !self.pending_missions.is_empty() {
+            let now = sim_hours_now();
+            println!("{BOLD}{YELLOW}Parked Missions ({}){RESET}", self.pending_missions.len());
+            for mission in &self.pending_missions {
+                let deadline = mission.deadline.map(|hour| format!(", wanted by {:.1}h", hour)).unwrap_or_default();
+                println!("  -Mission {} to Station {}: {:?} priority (now {:?}){}", mission.id, mission.destination, mission.priority, aged_priority(mission, now), deadline);
+            }
+        }
+        if !self.held_for_window.is_empty() {
+            println!("{BOLD}{YELLOW}Held for Their Delivery Window ({}){RESET}", self.held_for_window.len());
+            for (mission, leave_at) in &self.held_for_window {
+                println!("  -Mission {} to Station {}, can leave at {:.1}h", mission.id, mission.destination, leave_at);
+            }
+        }
+        if !self.marshalling.is_empty() {
+            println!("{BOLD}{YELLOW}Marshalling ({}){RESET}", self.marshalling.len());
+            for (mission, since) in &self.marshalling {
+                println!("  -Mission {} to Station {}, waiting for company since {:.1}h", mission.id, mission.destination, since);
+            }
+        }
+        if !self.yard.classification.is_empty() {
+            let now = sim_hours_now();
+            println!("{BOLD}{YELLOW}Classification Tracks ({} car(s)){RESET}", self.yard.cars_on_classification_tracks());
+            for (next_hop, cuts) in &self.yard.classification {
+                for cut in cuts {
+                    println!("  -Mission {}: {} car(s) for Station {} via Station {}, waiting {:.1}h", cut.rider.mission_id, cut.cars.len(), cut.rider.destination, next_hop, now - cut.arrived_at);
+                }
+            }
+        }
+        if !self.signal_queue.is_empty() {
+            println!("{BOLD}{YELLOW}Held at the Signal ({}){RESET}", self.signal_queue.len());
+            for (train, route, since) in &self.signal_queue {
+                println!("  -Train {} for Station {}, waiting since {:.1}h", train.id, route.get(1).copied().unwrap_or(train.destination), since);
+            }
+        }
+        if !self.held_outside.is_empty() {
+            println!("{BOLD}{YELLOW}Held Outside the Station ({}){RESET}", self.held_outside.len());
+            for (train, since) in &self.held_outside {
+                println!("  -Train {} ({} car(s)), waiting for room since {:.1}h", train.id, train.cars.len(), since);
+            }
+        }
+        if !self.booked.is_empty() {
+            println!("{BOLD}{YELLOW}Booked on Timetabled Services ({}){RESET}", self.booked.len());
+            for (service_id, departs, mission) in &self.booked {
+                println!("  -Mission {} to Station {} on Service {} ({:.1}h)", mission.id, mission.destination, service_id, departs);
+            }
+        }
+        if !self.platform.is_empty() {
+            println!("{BOLD}{YELLOW}Platform ({} waiting){RESET}", self.platform.len());
+            for passenger in &self.platform {
+                println!("  -{} for Station {}", passenger.name, passenger.destination);
+            }
+        }
+    }
+        
+
+
+
+    fn send_mission_failure(&self, mission_id: u32, error: TrainError, reply_to: Sender<Result<Train, TrainError>>) {
+        if reply_to.send(Err(error)).is_err() {
+            println!("{RED}Network Error: Failed to send mission failure report for Mission {}.{RESET}", mission_id);
+        }
+    }
+
+
+    // This is a helper method for processing incoming cars, both from train arrivals and from external sources. It attempts to receive each car into the yard, and if the car contains cargo, it moves the cargo into the warehouse. If any issues arise during this process (such as contraband detection or other intake errors), it logs the issue, moves the car to purgatory, and collects the IDs of any cars that failed intake to include in the MissionReport for transparency.
+    pub fn process_cars (&mut self, mut cars: Vec<TrainCar>, mission_id: Option<u32>) -> Vec<u32> {
+        self.alight_passengers(&mut cars, true); // Nobody gets locked in the yard overnight
+        let mut failed_ids = Vec::new(); // We can fill this with any issues that arise during processing, and then include it in the MissionReport for transparency and debugging. For now, we'll just keep it empty to represent a perfect process.
+        for car in cars {
+            let car_id_we_just_received = car.id; // Store the ID before we potentially move the car into purgatory
+            match self.yard.squeeze_in(car) { // It's here now: a full yard can't send it back out onto the main line
+                Ok(Some(cargo)) => { self.store_cargo(cargo); },
+                Ok(None) => {}, // Car is empty but safely in the yard
+                Err((homeless_car, e)) => {
+                    println!("{RED}Failed to process Car {} during intake: {:?}. Moving to purgatory.{RESET}", car_id_we_just_received, e);
+                    failed_ids.push(homeless_car.id); // Log the ID of the car that caused issues for transparency
+                    let rejected_asset = RejectedAsset::new(*homeless_car, e, mission_id);
+                    self.yard.purgatory.push(rejected_asset);
+                }
+            }
+        }
+
+        failed_ids
+
+    }
+
+    // helper method for the "dispatch train" phase of the mission. This is where we spawn a thread to simulate the train's journey to the next station, and we handle the logic for potential derailments during transit.
+    pub fn dispatch_train(&mut self, train: Train, route: Vec<u32>) {
+        self.send_train(train, route, None);
+    }
+
+    pub fn handle_block_cleared(&mut self, from: u32, to: u32) {
+        if !self.signal_queue.is_empty() {
+            println!("{CYAN}[{}] Block {} - {} is clear. {} train(s) at the signal try again.{RESET}", self.name, from, to, self.signal_queue.len());
+            self.retry_signal_queue();
+        }
+    }
+
+    // The signal box says this train, held at our signal, is in a deadlock and must give way. Under the reroute policy it
+    // tries another way round that keeps off the block it was waiting for. Otherwise (or if there's no other way) it
+    // gives up the block it's standing in and sets back to the station at the other end of it.
+    pub fn handle_resolve_deadlock(&mut self, train_id: u32) {
+        if let Some(index) = self.held_outside.iter().position(|(train, _)| train.id == train_id) {
+            self.back_off_from_outside(index);
+            return;
+        }
+        let Some(index) = self.signal_queue.iter().position(|(train, _, _)| train.id == train_id) else {
+            // Got the road (or went elsewhere) before the message arrived. Nothing to give way.
+            let mut signal_box = self.signal_box.lock().unwrap();
+            signal_box.stop_holding(train_id);
+            signal_box.resolved(train_id, "had already moved on".to_string());
+            return;
+        };
+        let Some((mut train, route, since)) = self.signal_queue.remove(index) else {
+            return;
+        };
+        let wanted = route.get(1).copied().unwrap_or(train.destination);
+        let policy = self.signal_box.lock().unwrap().policy;
+
+        let back_to = train.block.map(|(from, to)| if to == self.id { from } else { to });
+        if policy == DeadlockPolicy::Reroute {
+            // Going back the way we came isn't a detour, it's backing off, and that's done below.
+            let detour = self.map.read().unwrap().find_shortest_path_avoiding(self.id, train.destination, (self.id, wanted)).map(|(_, detour)| detour);
+            if let Some(detour) = detour.filter(|detour| detour.len() > 1 && Some(detour[1]) != back_to) {
+                println!("{BOLD}{YELLOW}[{}] Deadlock: Train {} gives up on the block to Station {} and goes round via {:?}.{RESET}", self.name, train.id, wanted, detour);
+                let mut signal_box = self.signal_box.lock().unwrap();
+                signal_box.stop_holding(train.id);
+                signal_box.resolved(train.id, format!("was rerouted via {:?}", detour));
+                drop(signal_box);
+                self.send_train(train, detour, Some(since));
+                return;
+            }
+            println!("{YELLOW}[{}] Deadlock: No other way round for Train {}. It backs off instead.{RESET}", self.name, train.id);
+        }
+
+        let back_tx = back_to.and_then(|station| self.neighbors.get(&station).cloned());
+        let (Some(back_to), Some(back_tx)) = (back_to, back_tx) else {
+            // Not standing in a block we can back out of. It stands aside, to the back of the queue.
+            println!("{YELLOW}[{}] Deadlock: Train {} has nowhere to set back to. It goes to the back of the queue.{RESET}", self.name, train.id);
+            let mut signal_box = self.signal_box.lock().unwrap();
+            signal_box.stop_holding(train.id);
+            signal_box.resolved(train.id, "went to the back of the queue".to_string());
+            drop(signal_box);
+            self.signal_queue.push_back((train, route, since));
+            return;
+        };
+
+        println!("{BOLD}{YELLOW}[{}] Deadlock: Train {} backs off to Station {} and gives up the block.{RESET}", self.name, train.id, back_to);
+        let mut signal_box = self.signal_box.lock().unwrap();
+        signal_box.stop_holding(train.id);
+        signal_box.resolved(train.id, format!("set back to Station {}", back_to));
+        drop(signal_box);
+        self.release_block(&mut train);
+        let (ack_tx, _ack_rx) = mpsc::channel();
+        if let Err(mpsc::SendError(StationCommand::ReceiveTrain { train, .. })) = back_tx.send(StationCommand::ReceiveTrain { train, reply_to: ack_tx }) {
+            println!("{RED}[{}] Station {} is closed. Train {} stays here after all.{RESET}", self.name, back_to, train.id);
+            self.signal_queue.push_back((train, route, since));
+        }
+    }
+
+    // A deadlock victim waiting outside for room. There's no detour from the home signal: it sets back to the station it
+    // came from, or if it can't, stays put and waits for room like before.
+    fn back_off_from_outside(&mut self, index: usize) {
+        let Some((mut train, since)) = self.held_outside.remove(index) else {
+            return;
+        };
+        let back_to = train.block.map(|(from, to)| if to == self.id { from } else { to });
+        let back_tx = back_to.and_then(|station| self.neighbors.get(&station).cloned());
+        let (Some(back_to), Some(back_tx)) = (back_to, back_tx) else {
+            println!("{YELLOW}[{}] Deadlock: Train {} has nowhere to set back to. It keeps waiting outside.{RESET}", self.name, train.id);
+            self.signal_box.lock().unwrap().resolved(train.id, "kept waiting outside".to_string());
+            self.held_outside.insert(index, (train, since));
+            return;
+        };
+
+        println!("{BOLD}{YELLOW}[{}] Deadlock: Train {} gives up waiting outside and backs off to Station {}.{RESET}", self.name, train.id, back_to);
+        let mut signal_box = self.signal_box.lock().unwrap();
+        signal_box.stop_holding(train.id);
+        signal_box.resolved(train.id, format!("set back to Station {} from outside", back_to));
+        drop(signal_box);
+        self.release_block(&mut train);
+        let (ack_tx, _ack_rx) = mpsc::channel();
+        if let Err(mpsc::SendError(StationCommand::ReceiveTrain { train, .. })) = back_tx.send(StationCommand::ReceiveTrain { train, reply_to: ack_tx }) {
+            println!("{RED}[{}] Station {} is closed. Train {} stays outside after all.{RESET}", self.name, back_to, train.id);
+            self.held_outside.push_back((train, since));
+        }
+    }
+
+    // Puts an engine away, in a stall if there's one free. There's nowhere else for it to go, so when there isn't it's squeezed in anyway.
+    // Arrivals are gated on free stalls before they get here, so this only overflows for engines already on our hands.
+    fn house_engine(&mut self, engine: Engine) {
+        if let Err(engine) = self.roundhouse.house(engine) {
+            println!("{YELLOW}[{}] Every stall is taken! Engine {} is squeezed in on the turntable road.{RESET}", self.name, engine.id);
+            self.roundhouse.squeeze_in(engine);
+        }
+    }
+
+    // The same for cargo: the warehouse if there's space, the aisles if there isn't.
+    fn store_cargo(&mut self, cargo: Cargo) {
+        if let Err(cargo) = self.warehouse.store(cargo) {
+            println!("{YELLOW}[{}] The warehouse is full! Cargo {} is stacked in the aisles.{RESET}", self.name, cargo.id);
+            self.warehouse.squeeze_in(cargo);
+        }
+    }
+
+    // Gives back the block the train last got the road for, if it still has it, and tells both ends.
+    fn release_block(&mut self, train: &mut Train) {
+        let Some((from, to)) = train.block.take() else {
+            return;
+        };
+        self.signal_box.lock().unwrap().release(from, to, train.id);
+        for end in [from, to] {
+            let end_tx = if end == self.id { Some(&self.tx) } else { self.neighbors.get(&end) };
+            if let Some(end_tx) = end_tx {
+                let _ = end_tx.send(StationCommand::BlockCleared { from, to });
+            }
+        }
+    }
+
+    // Trains held at the signal try again, oldest first. Anyone still blocked goes back in the queue in the same order.
+    pub fn retry_signal_queue(&mut self) {
+        let waiting: Vec<HeldAtSignal> = self.signal_queue.drain(..).collect();
+        for (train, route, since) in waiting {
+            self.send_train(train, route, Some(since));
+        }
+    }
+
+    // What the train would leave here: engines for the stalls, cars for the yard, loads for the warehouse. Says what we're short of, if anything.
+    // Through trains leave only the cars labelled for here. Timetabled trains do the same at a call, and leave everything at the terminus.
+    fn no_room_for(&self, train: &Train) -> Option<String> {
+        let labelled_for_here = || train.cars.iter().filter(|car| car.destination == Some(self.id)).collect();
+        let breaking_up = self.breaks_up(train); // Every car comes off, but only the ones for here are unloaded
+        let (engines, cars): (usize, Vec<&TrainCar>) = match &train.schedule {
+            _ if breaking_up => (train.engines.len(), train.cars.iter().collect()),
+            Some(run) if run.calls.front() != Some(&self.id) => return None,
+            Some(run) if run.calls.len() > 1 => (0, labelled_for_here()),
+            Some(_) => (train.engines.len(), train.cars.iter().collect()),
+            None if train.destination != self.id => (0, labelled_for_here()),
+            None => (train.engines.len(), train.cars.iter().collect()),
+        };
+        // Engines due a service go straight into the shed, if we have one, and don't need a stall.
+        let engines = if self.roundhouse.has_shed { engines.saturating_sub(train.engines.iter().filter(|engine| engine.service_due()).count()) } else { engines };
+        let loads = cars.iter().filter(|car| car.cargo.is_some() && (!breaking_up || car.destination == Some(self.id))).count();
+        let free_tracks = self.yard.free_tracks();
+
+        let mut short = Vec::new();
+        if engines > self.roundhouse.free_stalls() {
+            short.push(format!("{} stall(s) wanted, {} free", engines, self.roundhouse.free_stalls()));
+        }
+        if cars.len() > free_tracks {
+            short.push(format!("{} yard track(s) wanted, {} free", cars.len(), free_tracks));
+        }
+        if loads > self.warehouse.free_space() {
+            short.push(format!("{} warehouse space(s) wanted, {} free", loads, self.warehouse.free_space()));
+        }
+        if short.is_empty() { None } else { Some(short.join(", ")) }
+    }
+
+    // Trains held outside come in once there's room, oldest first. One that has waited too long is squeezed in anyway:
+    // better an overfull yard than a main line blocked for good.
+    pub fn admit_held_trains(&mut self) {
+        let now = sim_hours_now();
+        let (reply_to, _reply_rx) = mpsc::channel(); // The transit thread got its answer when the train first pulled up
+        let waiting: Vec<(Train, f64)> = self.held_outside.drain(..).collect();
+        for (train, since) in waiting {
+            let shortfall = self.no_room_for(&train);
+            if shortfall.is_none() || now - since >= HOLD_OUTSIDE_LIMIT_HOURS {
+                self.signal_box.lock().unwrap().stop_holding(train.id); // Coming in off the main line
+            }
+            match shortfall {
+                None => {
+                    println!("{GREEN}[{}] Room at last for Train {} after {:.1}h outside. Bringing it in.{RESET}", self.name, train.id, now - since);
+                    self.admit_train(train, reply_to.clone());
+                },
+                Some(shortfall) if now - since >= HOLD_OUTSIDE_LIMIT_HOURS => {
+                    println!("{YELLOW}[{}] Train {} has been outside for {:.1}h and there's still no room ({}). Squeezing it in.{RESET}", self.name, train.id, now - since, shortfall);
+                    self.admit_train(train, reply_to.clone());
+                },
+                Some(_) => self.held_outside.push_back((train, since)),
+            }
+        }
+    }
+
+    // Writes down when we expect this mission's freight in, from how far it has to go and how fast this train can take it.
+    // Only the first plan counts: a consignment sent on again after a wreck is measured against what we first said.
+    fn plan_arrival(&self, mission_id: u32, distance: f64, train: &Train) {
+        let speed = train.consist_speed();
+        if !speed.is_finite() || speed <= 0.0 {
+            return;
+        }
+        if let Some(record) = self.ledger.lock().unwrap().deliveries.get_mut(&mission_id) {
+            record.planned_at.get_or_insert(sim_hours_now() + distance / speed);
+        }
+    }
+
+    // Writes down that this mission's freight is here. Says how late it is, if it missed its deadline.
+    fn record_delivery(&self, mission_id: u32) -> Option<f64> {
+        let mut ledger = self.ledger.lock().unwrap();
+        let record = ledger.deliveries.get_mut(&mission_id)?;
+        record.delivered_at.get_or_insert(sim_hours_now());
+        match record.punctuality()? {
+            Punctuality::Late(hours) => Some(hours),
+            Punctuality::Early(hours) => {
+                println!("{YELLOW}[{}] Mission {} is {:.1}h early. The consignee wasn't ready for it yet.{RESET}", self.name, mission_id, hours);
+                None
+            },
+            Punctuality::InWindow => None,
+        }
+    }
+
+    // The consignees call round for what's been delivered to them, freeing up the warehouse.
+    // Split consignments still waiting on parts stay put until they're whole.
+    pub fn collect_deliveries(&mut self) {
+        if self.awaiting_collection.is_empty() {
+            return;
+        }
+        let collected = self.warehouse.hand_over(&self.awaiting_collection);
+        self.awaiting_collection.retain(|id| self.warehouse.is_incomplete(*id));
+        if collected > 0 {
+            println!("{CYAN}[{}] Consignees collected {} delivered consignment(s) from the warehouse.{RESET}", self.name, collected);
+        }
+    }
+
+    // Does the actual sending. `waiting_since` is set for a train that has been held at our signal.
+    fn send_train(&mut self, mut train: Train, route: Vec<u32>, waiting_since: Option<f64>) {
+        let final_destination = train.destination;
+        let station_tx_clone = self.tx.clone(); // Clone the station's own Sender for use in this method, so we can send SOS if needed
+
+        let next_stop = route.get(1).cloned().unwrap_or_else(|| final_destination); // The next stop is the second element in the route (index 1), or the final destination if the route is just one stop
+
+        // The route was planned a moment ago, but the Fat Controller may have closed the track since then.
+        // If the next hop has vanished, we hand the train back to our own mailbox as an arrival, which re-runs Dijkstra against the current map.
+        let hop = self.neighbors.get(&next_stop).cloned().zip(self.map.read().unwrap().get_distance(self.id, next_stop));
+        let (next_stop_handle, distance_to_next_stop) = match hop {
+            Some(hop) => hop,
+            None => {
+                println!("{YELLOW}[{}] Track to {} is no longer available for Train {}. Re-routing...{RESET}", self.name, next_stop, train.id);
+                let (reroute_tx, _reroute_rx) = mpsc::channel();
+                self.tx.send(StationCommand::ReceiveTrain { train, reply_to: reroute_tx }).expect("Station mailbox must be open to re-route a train");
+                return;
+            }
+        };
+
+        // Block signalling. We don't overtake trains already waiting for the same block, and we don't go until the signal box gives us the road.
+        // A held train goes into the wait-for graph, and every hold is a chance that we've just closed a cycle.
+        let queued_ahead = self.signal_queue.iter().rev().find(|(_, queued_route, _)| queued_route.get(1) == Some(&next_stop)).map(|(ahead, _, _)| ahead.id);
+        let mut signal_box = self.signal_box.lock().unwrap();
+        if queued_ahead.is_some() || !signal_box.try_reserve(self.id, next_stop, train.id) {
+            let since = waiting_since.unwrap_or_else(sim_hours_now);
+            signal_box.hold(train.id, HeldTrain { station: self.id, wants: (self.id, next_stop), holds: train.block, behind: queued_ahead, highpriority: train.highpriority, since, station_tx: self.tx.clone() });
+            if let Some((deadlock, victim_tx)) = signal_box.detect_deadlock() {
+                let _ = victim_tx.send(StationCommand::ResolveDeadlock { train_id: deadlock.victim });
+            }
+            drop(signal_box);
+            if waiting_since.is_none() {
+                println!("{YELLOW}[{}] Signal at danger: Train {} waits for the block to Station {}.{RESET}", self.name, train.id, next_stop);
+            }
+            self.signal_queue.push_back((train, route, since));
+            return;
+        }
+        signal_box.stop_holding(train.id);
+        if let Some(since) = waiting_since {
+            let hours = sim_hours_now() - since;
+            println!("{GREEN}[{}] Signal clear: Train {} gets the road to Station {} after {:.1}h at the signal.{RESET}", self.name, train.id, next_stop, hours);
+            signal_box.waits.push(BlockWait { train_id: train.id, station: self.id, next_stop, hours });
+        }
+        drop(signal_box);
+        // Off the block we were standing in (only still ours if there's no passing loop here), unless that's the way we're going.
+        let turning_back = train.block.is_some_and(|block| block == (next_stop, self.id) || block == (self.id, next_stop));
+        if !turning_back {
+            self.release_block(&mut train);
+        }
+        train.block = Some((self.id, next_stop));
+
+        let gradient_to_next_stop = self.map.read().unwrap().get_gradient(self.id, next_stop).unwrap_or(0.0);
 
         let train_id = train.id; // Store the train ID for logging inside the thread
         let station_name_clone = self.name.clone(); // Clone the station name for use in this thread
         let station_id_clone = self.id.clone();
         let (transit_tx, transit_rx) = mpsc::channel();
 
+        // If the hop goes wrong (wrecked, towed back, turned away), the train gives the block back from out here and both ends hear about it.
+        // If it goes right, the station at the far end gives it back once it knows whether the train is staying in the block.
+        let signal_box = Arc::clone(&self.signal_box);
+        let (block_home_tx, block_end_tx) = (self.tx.clone(), next_stop_handle.clone());
+        let from = self.id;
+        let clear_block = move || {
+            signal_box.lock().unwrap().release(from, next_stop, train_id);
+            let _ = block_home_tx.send(StationCommand::BlockCleared { from, to: next_stop });
+            let _ = block_end_tx.send(StationCommand::BlockCleared { from, to: next_stop });
+        };
+
         thread::spawn(move || {
-            let time = train.dispatch(distance_to_next_stop).expect("Failed to dispatch");
-            println!("{BOLD}{YELLOW}[{}::Station {}: Train {} is en route on Mission {} to next stop [Station {}]. Estimated time: {:.2} seconds.{RESET}", station_name_clone, station_id_clone, train_id, train.mission_id.unwrap_or(0), next_stop, time);
-            thread::sleep(std::time::Duration::from_secs_f64(time)); // Simulate travel time to the next station. In a real implementation, this would be based on distance and train speed.
+            let hop = match train.dispatch(distance_to_next_stop, gradient_to_next_stop) {
+                Ok(hop) => hop,
+                Err(e) => {
+                    clear_block();
+                    panic!("Failed to dispatch: {:?}", e);
+                }
+            };
+            println!("{BOLD}{YELLOW}[{}::Station {}: Train {} is en route on Mission {} to next stop [Station {}]. Estimated time: {:.2} seconds.{RESET}", station_name_clone, station_id_clone, train_id, train.mission_id.unwrap_or(0), next_stop, hop.hours);
+            // An engine that gave up did so part way along the line (dispatch has already charged it for the distance it made).
+            if let Some(engine_id) = hop.broke_down {
+                thread::sleep(std::time::Duration::from_secs_f64(hop.hours));
+                println!("{RED}🔧 BREAKDOWN: Engine {} on Train {}!{RESET}", engine_id, train_id);
+                clear_block();
+                if station_tx_clone.send(StationCommand::HandleBreakdown { train, engine_id }).is_err() {
+                    println!("{RED}[{}] DEAD-LETTER: Train {} broke down and its home station is gone.{RESET}", station_name_clone, train_id);
+                }
+                return;
+            }
+            thread::sleep(std::time::Duration::from_secs_f64(hop.hours)); // Simulate travel time to the next station. In a real implementation, this would be based on distance and train speed.
 
             // Using rand, simulate the train crashing with a 10% chance during transit. If it crashes, we issue a Derailment report back to transit_rx and skip the rest of the transit logic. The train is lost, so we don't send it to the next station. However, we return the salvaged TrainCars back to the yard for processing, and we send a MissionReport::Failure back to the mission's reply channel with details of the crash.
             let tree_falls = rand::thread_rng().gen_bool(0.1);
             if tree_falls {
                 println!("{RED}🚨 DERAILMENT: Train {}!{RESET}", train_id);
+                clear_block(); // The breakdown gang clears the wreck
 
                 // We send an SOS command BACK to the Station's main mailbox!
                 // (You will need to pass a clone of the Station's own Sender into the thread)
-                station_tx_clone.send(StationCommand::HandleEmergencySOS {
-                    mission_id: train.mission_id.unwrap_or(0),
-                    destination: train.destination,
-                    surviving_cars: train.cars, // The train dies, but the cars live!
-                    report_to: train.report_to,
-                }).expect("SOS failed");
+                // One per consignment aboard: a timetabled run may be carrying freight for several missions.
+                for (rider, surviving_cars) in train.split_for_salvage() {
+                    station_tx_clone.send(StationCommand::HandleEmergencySOS {
+                        mission_id: rider.mission_id,
+                        destination: rider.destination,
+                        priority: rider.priority,
+                        deadline: rider.deadline,
+                        surviving_cars, // The train dies, but the cars live!
+                        report_to: rider.report_to,
+                    }).expect("SOS failed");
+                }
 
                 return; // Thread ends. Engine drops. The cars are now in limbo until the station processes the SOS and returns them to the yard or purgatory.
             } else {
                 println!("{GREEN}{BOLD}[{}] Train {} has successfully arrived at next stop {}. Sending receive command...{RESET}", station_name_clone, train_id, next_stop);
-                next_stop_handle.send(StationCommand::ReceiveTrain { train, reply_to: transit_tx }).expect("Failed to forward train to next station");
+                if let Err(mpsc::SendError(command)) = next_stop_handle.send(StationCommand::ReceiveTrain { train, reply_to: transit_tx }) {
+                    // The station closed while we were on our way. Reverse back home and let our own station re-route us.
+                    println!("{RED}[{}] Station {} is closed! Train {} is returning to {}.{RESET}", station_name_clone, next_stop, train_id, station_name_clone);
+                    clear_block();
+                    if station_tx_clone.send(command).is_err() {
+                        println!("{RED}[{}] DEAD-LETTER: Train {} has nowhere left to go.{RESET}", station_name_clone, train_id);
+                    }
+                    return;
+                }
             }
 
             match transit_rx.recv() {
@@ -1518,6 +4160,7 @@ pub fn retry_pending_missions(&mut self) {
                 },
                 Err(e) => {
                     println!("{RED}[{}] ERROR receiving transit confirmation for Train {}: {:?}{RESET}", station_name_clone, train_id, e);
+                    clear_block(); // Nobody at the far end to do it
                 }
 
             }
@@ -1525,6 +4168,15 @@ pub fn retry_pending_missions(&mut self) {
     }
     
     //helper method for sending failure reports to the mission's reply channel, to avoid repeating this logic in multiple places.
+    // A mission has stopped waiting on empty cars, one way or another: it got them, it failed, it spoiled, or it went elsewhere.
+    // Once nobody is waiting any more, the clock on our request stops too.
+    fn no_longer_short(&mut self, mission_id: u32) {
+        self.short_of_cars.remove(&mission_id);
+        if self.short_of_cars.is_empty() {
+            self.empty_cars_requested_at = None;
+        }
+    }
+
     pub fn report_mission_failure(&self, mission: &Mission, error_details: &str) {
         match &mission.reply_channel {
             Some(sender) => {
@@ -1544,3 +4196,70 @@ pub fn retry_pending_missions(&mut self) {
 }
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ShelfLife;
+
+    fn cargo(id: u32, used: f64) -> Cargo {
+        Cargo {
+            id,
+            item: String::from("Ice cream"),
+            actual_weight: 100,
+            contraband: None,
+            category: CargoCategory::Chilled,
+            part: None,
+            shelf_life: Some(ShelfLife { hours: 10.0, ambient_rate: 3.0, used, checked_at: sim_hours_now() }),
+        }
+    }
+
+    fn parked_mission(priority: Priority, parked_since: Option<f64>) -> Mission {
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        let order = FreightOrder { id: 1, cargo_ids: vec![1], origin: 0, destination: 1, ttl: 5, priority, not_before: None, deadline: None };
+        Mission { parked_since, ..Mission::from_order(&order, reply_tx) }
+    }
+
+    #[test]
+    fn clear_spoiled_throws_out_bad_consignments_and_whole_splits() {
+        let mut warehouse = Warehouse::new(0, 10);
+        warehouse.store(cargo(1, 0.0)).unwrap();
+        warehouse.store(cargo(2, 12.0)).unwrap();
+        let mut parts = cargo(3, 0.0).split(&[50, 50]);
+        parts[0].shelf_life.as_mut().unwrap().used = 11.0;
+        warehouse.store(parts.remove(0)).unwrap(); // Only one part is in, so it waits with the partials
+
+        let mut spoiled = warehouse.clear_spoiled();
+        spoiled.sort_unstable();
+        assert_eq!(spoiled, vec![2, 3]);
+        assert!(warehouse.inventory.contains_key(&1));
+        assert!(warehouse.partials.is_empty());
+        assert_eq!(warehouse.disposal.len(), 2);
+    }
+
+    #[test]
+    fn parked_missions_climb_the_ladder() {
+        let now = 100.0;
+        assert_eq!(aged_priority(&parked_mission(Priority::Low, None), now), Priority::Low);
+        assert_eq!(aged_priority(&parked_mission(Priority::Low, Some(now - PRIORITY_AGING_HOURS)), now), Priority::Normal);
+        assert_eq!(aged_priority(&parked_mission(Priority::Low, Some(now - 10.0 * PRIORITY_AGING_HOURS)), now), Priority::Urgent);
+    }
+
+    #[test]
+    fn window_departure_leaves_in_time_to_arrive_as_the_window_opens() {
+        let top_speed = EngineType::roster().iter().map(|class| class.speed()).max().unwrap() as f64;
+        assert_eq!(window_departure(None, 100.0), None);
+        assert_eq!(window_departure(Some(10.0), 2.0 * top_speed), Some(8.0));
+    }
+
+    #[test]
+    fn along_one_line_puts_stops_on_the_way_in_the_same_train() {
+        let routes: Vec<Vec<u32>> = vec![vec![0, 1], vec![0, 1, 2, 3], vec![0, 4], vec![0, 1, 2]];
+        let trains = along_one_line(routes, |route| route.as_slice());
+        assert_eq!(trains, vec![
+            vec![vec![0, 1, 2, 3], vec![0, 1, 2], vec![0, 1]],
+            vec![vec![0, 4]],
+        ]);
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::config::{CargoSeed, CarSeed, Config, EngineSeed, SeedConfig, StationConfig, StationSeed, TrackConfig};
+use crate::models::{CarClass, CargoCategory, EngineType, Priority};
+use crate::selection::{EnginePolicyKind, RebalancePolicy};
+use crate::signalling::{DeadlockPolicy, TrackWorking};
+
+// The Island of Sodor has seven stations. That's lovely for a story, but it doesn't tell us much about how the
+// engine-request gossip or Dijkstra behave when there are hundreds of them. This module builds bigger islands.
+// Everything is driven by a seeded RNG, so the same options always produce the same network.
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    Grid,            // Manhattan-style: every station connects to the station to its right and the station below it.
+    RandomGeometric, // Stations scattered at random, connected to everyone within a radius (then stitched together so nobody is stranded).
+    HubAndSpoke,     // A ring of big junctions, each with a cluster of branch-line stations hanging off it.
+    RingWithChords,  // A circle line with a few random cross-country shortcuts.
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "grid" => Ok(Topology::Grid),
+            "random" | "geometric" | "random-geometric" => Ok(Topology::RandomGeometric),
+            "hub" | "hub-and-spoke" => Ok(Topology::HubAndSpoke),
+            "ring" | "ring-with-chords" => Ok(Topology::RingWithChords),
+            other => Err(format!("Unknown topology '{}'. Try grid, random-geometric, hub-and-spoke or ring-with-chords.", other)),
+        }
+    }
+}
+
+
+pub struct GeneratorOptions {
+    pub stations: usize,
+    pub topology: Topology,
+    pub rng_seed: u64,
+    pub spacing: f64, // Typical distance between neighboring stations, in km.
+}
+
+impl GeneratorOptions {
+    pub fn new(stations: usize, topology: Topology, rng_seed: u64) -> Self {
+        GeneratorOptions {
+            stations,
+            topology,
+            rng_seed,
+            spacing: 100.0,
+        }
+    }
+}
+
+
+/// Builds a network map with `options.stations` stations laid out according to `options.topology`.
+/// The result is always connected, so every station can reach every other one.
+pub fn generate_network(options: &GeneratorOptions) -> Config {
+    let mut rng = StdRng::seed_from_u64(options.rng_seed);
+    let n = options.stations;
+    let spacing = options.spacing;
+
+    let (positions, mut edges) = match options.topology {
+        Topology::Grid => grid(n, spacing),
+        Topology::RandomGeometric => random_geometric(n, spacing, &mut rng),
+        Topology::HubAndSpoke => hub_and_spoke(n, spacing, &mut rng),
+        Topology::RingWithChords => ring_with_chords(n, spacing, &mut rng),
+    };
+    stitch_components(&positions, &mut edges);
+
+    let stations = positions.iter().enumerate()
+        .map(|(i, (x, y))| StationConfig {
+            id: i as u32,
+            name: format!("{:?} {}", options.topology, i),
+            x: *x,
+            y: *y,
+            elevation: None,
+            maintenance_shed: i % 5 == 0, // Every fifth station has a shed, so nobody is too far from one.
+            service_hours: None,
+            engine_policy: EnginePolicyKind::default(),
+            rebalance: RebalancePolicy::default(),
+            target_mix: HashMap::new(),
+            car_budget: None,
+            passenger_services: Vec::new(),
+            passing_loop: true,
+            yard_capacity: None,
+            roundhouse_capacity: None,
+            warehouse_capacity: None,
+            classification_hub: false,
+            hub_departure_cars: None,
+            hub_max_dwell_hours: None,
+        })
+        .collect();
+
+    let tracks = edges.into_iter()
+        .map(|(a, b)| TrackConfig { origin: a as u32, destination: b as u32, length: None, waypoints: Vec::new(), working: TrackWorking::default(), capacity: None })
+        .collect();
+
+    Config { stations, tracks, projection: None, services: Vec::new(), deadlock_policy: DeadlockPolicy::default() }
+}
+
+
+/// Builds a starting inventory to go with a generated network: a few engines, some empty cars and a little cargo at every
+/// station, with cargo destinations drawn from the other stations on the map.
+pub fn generate_seed(config: &Config, rng_seed: u64) -> SeedConfig {
+    // A different stream from the network generator, so changing the inventory doesn't reshuffle the map.
+    let mut rng = StdRng::seed_from_u64(rng_seed.wrapping_add(0x5EED));
+    let roster = EngineType::roster();
+    let goods = [
+        ("Coal", CargoCategory::Bulk), ("Timber", CargoCategory::Bulk), ("Steel Girders", CargoCategory::Oversize),
+        ("Crates of Apples", CargoCategory::General), ("China Clay", CargoCategory::Bulk), ("Milk", CargoCategory::Liquid),
+        ("Mail Sacks", CargoCategory::General), ("Fish", CargoCategory::Chilled),
+    ];
+    // Mostly boxcars, like a real yard, with a few of the specialists mixed in.
+    let car_classes = [CarClass::Boxcar, CarClass::Boxcar, CarClass::Boxcar, CarClass::Flatbed, CarClass::Tanker, CarClass::Reefer];
+    // A consignment rides in one car (they all carry more than 8000kg), so the heaviest haul is the cargo plus the heaviest car.
+    // Nothing heavier than the strongest class on the roster can pull is put out, in case engines.json has only small engines.
+    let heaviest_car = car_classes.iter().map(|class| class.tare()).max().unwrap_or(0);
+    let heaviest_cargo = (roster.last().map_or(0.0, |class| class.max_capacity()) as u32).saturating_sub(heaviest_car).clamp(1, 8000);
+
+    let station_ids: Vec<u32> = config.stations.iter().map(|s| s.id).collect();
+    let mut next_engine_id = 1;
+    let mut next_car_id = 1_000_000; // Well clear of the ids the yards hand out when they build cars.
+    let mut next_cargo_id = 1;
+
+    let stations = config.stations.iter().map(|station| {
+        let mut engines: Vec<EngineSeed> = (0..rng.gen_range(1..=3)).map(|_| {
+            let engine_type = roster[rng.gen_range(0..roster.len())];
+            next_engine_id += 1;
+            EngineSeed { id: next_engine_id - 1, engine_type, fuel: engine_type.max_fuel_capacity(), km_since_service: 0.0, home: None }
+        }).collect();
+
+        let cars = (0..rng.gen_range(2..=4)).map(|_| {
+            next_car_id += 1;
+            CarSeed { id: next_car_id - 1, class: car_classes[rng.gen_range(0..car_classes.len())] }
+        }).collect();
+
+        let cargo = if station_ids.len() < 2 { Vec::new() } else {
+            (0..rng.gen_range(0..=2)).map(|_| {
+                let destination = loop {
+                    let candidate = station_ids[rng.gen_range(0..station_ids.len())];
+                    if candidate != station.id { break candidate; }
+                };
+                next_cargo_id += 1;
+                let (description, category) = goods[rng.gen_range(0..goods.len())];
+                CargoSeed {
+                    id: next_cargo_id - 1,
+                    destination,
+                    weight: rng.gen_range(500.min(heaviest_cargo)..=heaviest_cargo),
+                    description: description.to_string(),
+                    category,
+                    priority: [Priority::Low, Priority::Normal, Priority::Normal, Priority::High][rng.gen_range(0..4)],
+                    not_before: None,
+                    deadline: None, // Generated runs are for load, not punctuality
+                    shelf_life_hours: (category == CargoCategory::Chilled).then_some(48.0),
+                    ambient_spoil_rate: None,
+                }
+            }).collect()
+        };
+
+        // Every station can move its own freight. If none of its engines could pull the heaviest load, it gets the weakest class
+        // that can. Otherwise that load waits on a neighbour lending the right engine, which may never happen.
+        let heaviest_haul = cargo.iter().map(|item: &CargoSeed| (item.weight + heaviest_car) as f64).fold(0.0, f64::max);
+        if !engines.iter().any(|engine| engine.engine_type.max_capacity() >= heaviest_haul)
+            && let Some(engine_type) = roster.iter().copied().find(|class| class.max_capacity() >= heaviest_haul) {
+            next_engine_id += 1;
+            engines.push(EngineSeed { id: next_engine_id - 1, engine_type, fuel: engine_type.max_fuel_capacity(), km_since_service: 0.0, home: None });
+        }
+
+        StationSeed { id: station.id, name: station.name.clone(), engines, cars, cargo, passengers: Vec::new() }
+    }).collect();
+
+    SeedConfig { stations }
+}
+
+
+type Layout = (Vec<(f64, f64)>, Vec<(usize, usize)>);
+
+fn grid(n: usize, spacing: f64) -> Layout {
+    let side = (n as f64).sqrt().ceil().max(1.0) as usize;
+    let positions = (0..n).map(|i| ((i % side) as f64 * spacing, (i / side) as f64 * spacing)).collect();
+
+    let mut edges = Vec::new();
+    for i in 0..n {
+        if (i + 1) % side != 0 && i + 1 < n { edges.push((i, i + 1)); } // Right
+        if i + side < n { edges.push((i, i + side)); }                  // Down
+    }
+    (positions, edges)
+}
+
+fn random_geometric(n: usize, spacing: f64, rng: &mut StdRng) -> Layout {
+    // Scale the square with the station count so the density (and so the average degree) stays roughly constant.
+    let side = spacing * (n as f64).sqrt();
+    let radius = spacing * 1.5;
+    let positions: Vec<(f64, f64)> = (0..n).map(|_| (rng.gen_range(0.0..=side), rng.gen_range(0.0..=side))).collect();
+
+    let mut edges = Vec::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if distance(positions[i], positions[j]) <= radius {
+                edges.push((i, j));
+            }
+        }
+    }
+    (positions, edges)
+}
+
+fn hub_and_spoke(n: usize, spacing: f64, rng: &mut StdRng) -> Layout {
+    let hubs = (n / 10).max(1).min(n);
+    let hub_ring_radius = spacing * hubs as f64 / std::f64::consts::PI;
+
+    let mut positions: Vec<(f64, f64)> = (0..hubs).map(|h| {
+        let angle = std::f64::consts::TAU * h as f64 / hubs as f64;
+        (hub_ring_radius * angle.cos(), hub_ring_radius * angle.sin())
+    }).collect();
+
+    // The hubs form a ring (a single hub is just a hub).
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    if hubs > 1 {
+        for h in 0..hubs {
+            let next = (h + 1) % hubs;
+            if !edges.contains(&(next, h)) { edges.push((h, next)); }
+        }
+    }
+
+    // Every other station is a spoke off one of the hubs, round-robin.
+    for i in hubs..n {
+        let hub = i % hubs;
+        let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+        let reach = rng.gen_range(spacing * 0.3..=spacing);
+        let (hx, hy) = positions[hub];
+        positions.push((hx + reach * angle.cos(), hy + reach * angle.sin()));
+        edges.push((hub, i));
+    }
+    (positions, edges)
+}
+
+fn ring_with_chords(n: usize, spacing: f64, rng: &mut StdRng) -> Layout {
+    let radius = spacing * n as f64 / std::f64::consts::TAU;
+    let positions = (0..n).map(|i| {
+        let angle = std::f64::consts::TAU * i as f64 / n as f64;
+        (radius * angle.cos(), radius * angle.sin())
+    }).collect();
+
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+    if n > 1 {
+        for i in 0..n {
+            let j = (i + 1) % n;
+            edges.insert((i.min(j), i.max(j)));
+        }
+    }
+
+    // A quarter as many chords as stations, between stations that aren't already next to each other.
+    if n > 3 {
+        for _ in 0..(n / 4) {
+            let a = rng.gen_range(0..n);
+            let b = rng.gen_range(0..n);
+            if a != b && (a + 1) % n != b && (b + 1) % n != a {
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+    }
+    let mut edges: Vec<(usize, usize)> = edges.into_iter().collect();
+    edges.sort();
+    (positions, edges)
+}
+
+
+// Makes sure every station can reach every other one: any island that isn't connected to station 0 gets a track from its
+// closest station to the closest station already connected. Cheap and cheerful, O(n^2) per island, fine for hundreds of stations.
+fn stitch_components(positions: &[(f64, f64)], edges: &mut Vec<(usize, usize)>) {
+    let n = positions.len();
+    if n == 0 { return; }
+
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (a, b) in edges.iter() {
+        adjacency[*a].push(*b);
+        adjacency[*b].push(*a);
+    }
+
+    let mut connected = vec![false; n];
+    flood(0, &adjacency, &mut connected);
+
+    while let Some(stranded) = connected.iter().position(|c| !c) {
+        // Find the island the stranded station belongs to.
+        let mut island = vec![false; n];
+        flood(stranded, &adjacency, &mut island);
+
+        let mut best: Option<(usize, usize, f64)> = None;
+        for i in (0..n).filter(|i| island[*i]) {
+            for j in (0..n).filter(|j| connected[*j]) {
+                let d = distance(positions[i], positions[j]);
+                if best.is_none_or(|(_, _, best_d)| d < best_d) {
+                    best = Some((i, j, d));
+                }
+            }
+        }
+
+        let (i, j, _) = best.expect("There is always at least one connected station");
+        edges.push((j.min(i), j.max(i)));
+        adjacency[i].push(j);
+        adjacency[j].push(i);
+        flood(i, &adjacency, &mut connected);
+    }
+}
+
+fn flood(start: usize, adjacency: &[Vec<usize>], visited: &mut [bool]) {
+    let mut stack = vec![start];
+    while let Some(station) = stack.pop() {
+        if visited[station] { continue; }
+        visited[station] = true;
+        stack.extend(adjacency[station].iter().filter(|next| !visited[**next]));
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
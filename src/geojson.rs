@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::config::{Config, GeoReference, StationConfig, TrackConfig};
+use crate::selection::{EnginePolicyKind, RebalancePolicy};
+use crate::signalling::{DeadlockPolicy, TrackWorking};
+
+// Our planners draw networks in GIS tools, which speak GeoJSON and latitude/longitude. The railway speaks planar x/y in km.
+// This module translates between the two:
+//   - Point features are stations (properties: "id", "name" and "maintenance_shed", all optional). A third coordinate is the elevation in metres.
+//   - LineString features are tracks (properties: "origin" and "destination", optional; otherwise we snap each end to the nearest station.
+//     "length_km", also optional, is the surveyed length; we write it on export so a round trip keeps it).
+// We use a simple equirectangular projection centred on the middle of the network. It's plenty accurate for an island
+// (or a county), which is all a railway simulation needs. Track length is measured along the polyline, not as the crow flies.
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+// What we pull out of the features before projecting: (id, name, [lon, lat], elevation, shed) and (origin, destination, polyline, length).
+type RawStation = (Option<u32>, Option<String>, [f64; 2], Option<f64>, bool);
+type RawTrack = (Option<u32>, Option<u32>, Vec<[f64; 2]>, Option<f64>);
+
+
+/// Reads a GeoJSON FeatureCollection into our network Config.
+pub fn import_geojson(text: &str) -> Result<Config, String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| format!("Not valid JSON: {}", e))?;
+    let features = root.get("features").and_then(Value::as_array).ok_or("Expected a FeatureCollection with a \"features\" array")?;
+
+    // 1. Gather the stations (in degrees for now) and the raw track polylines.
+    let mut points: Vec<RawStation> = Vec::new();
+    let mut lines: Vec<RawTrack> = Vec::new();
+
+    for (i, feature) in features.iter().enumerate() {
+        let geometry = feature.get("geometry").ok_or(format!("Feature {} has no geometry", i))?;
+        let properties = feature.get("properties").cloned().unwrap_or(Value::Null);
+        let kind = geometry.get("type").and_then(Value::as_str).unwrap_or("");
+        let coordinates = geometry.get("coordinates").ok_or(format!("Feature {} has no coordinates", i))?;
+
+        match kind {
+            "Point" => {
+                let id = properties.get("id").and_then(Value::as_u64).map(|id| id as u32);
+                let name = properties.get("name").and_then(Value::as_str).map(String::from);
+                let elevation = coordinates.get(2).and_then(Value::as_f64);
+                let maintenance_shed = properties.get("maintenance_shed").and_then(Value::as_bool).unwrap_or(false);
+                points.push((id, name, lon_lat(coordinates).ok_or(format!("Feature {}: bad Point coordinates", i))?, elevation, maintenance_shed));
+            },
+            "LineString" => {
+                let origin = properties.get("origin").and_then(Value::as_u64).map(|id| id as u32);
+                let destination = properties.get("destination").and_then(Value::as_u64).map(|id| id as u32);
+                let length = properties.get("length_km").and_then(Value::as_f64);
+                let polyline = coordinates.as_array()
+                    .ok_or(format!("Feature {}: bad LineString coordinates", i))?
+                    .iter()
+                    .map(lon_lat)
+                    .collect::<Option<Vec<[f64; 2]>>>()
+                    .ok_or(format!("Feature {}: bad LineString coordinates", i))?;
+                if polyline.len() < 2 {
+                    return Err(format!("Feature {}: a track needs at least two points", i));
+                }
+                lines.push((origin, destination, polyline, length));
+            },
+            other => println!("GeoJSON: Skipping feature {} of type {:?}. Only Point and LineString are railway features.", i, other),
+        }
+    }
+
+    if points.is_empty() {
+        return Err("No stations (Point features) found".to_string());
+    }
+
+    // 2. Centre the projection on the middle of the stations.
+    let reference = GeoReference {
+        lat: points.iter().map(|(_, _, c, _, _)| c[1]).sum::<f64>() / points.len() as f64,
+        lon: points.iter().map(|(_, _, c, _, _)| c[0]).sum::<f64>() / points.len() as f64,
+    };
+
+    // 3. Stations. Unnumbered stations get the next id after the highest one we've seen.
+    let mut next_id = points.iter().filter_map(|(id, _, _, _, _)| *id).max().map_or(0, |max| max + 1);
+    let mut stations: Vec<StationConfig> = Vec::new();
+    for (id, name, coordinates, elevation, maintenance_shed) in points {
+        let id = id.unwrap_or_else(|| { next_id += 1; next_id - 1 });
+        if stations.iter().any(|s| s.id == id) {
+            return Err(format!("Station id {} appears twice", id));
+        }
+        let [x, y] = project(coordinates, reference);
+        stations.push(StationConfig { id, name: name.unwrap_or_else(|| format!("Station {}", id)), x, y, elevation, maintenance_shed, service_hours: None, engine_policy: EnginePolicyKind::default(), rebalance: RebalancePolicy::default(), target_mix: HashMap::new(), car_budget: None, passenger_services: Vec::new(), passing_loop: true, yard_capacity: None, roundhouse_capacity: None, warehouse_capacity: None, classification_hub: false, hub_departure_cars: None, hub_max_dwell_hours: None });
+    }
+    let station_index: HashMap<u32, usize> = stations.iter().enumerate().map(|(i, s)| (s.id, i)).collect();
+
+    // 4. Tracks. Ends without an explicit station id snap to whichever station is closest.
+    let mut tracks: Vec<TrackConfig> = Vec::new();
+    for (origin, destination, polyline, length) in lines {
+        let planar: Vec<[f64; 2]> = polyline.iter().map(|c| project(*c, reference)).collect();
+        let origin = origin.unwrap_or_else(|| nearest_station(&stations, planar[0]));
+        let destination = destination.unwrap_or_else(|| nearest_station(&stations, planar[planar.len() - 1]));
+
+        if !station_index.contains_key(&origin) || !station_index.contains_key(&destination) {
+            return Err(format!("Track {} - {} refers to a station that isn't on the map", origin, destination));
+        }
+        if origin == destination {
+            println!("GeoJSON: Skipping a track that starts and ends at Station {}.", origin);
+            continue;
+        }
+
+        // A length we were given wins. Otherwise we measure it along the polyline.
+        let length = length.unwrap_or_else(|| polyline.windows(2).map(|pair| haversine_km(pair[0], pair[1])).sum());
+        tracks.push(TrackConfig {
+            origin,
+            destination,
+            length: Some(length),
+            waypoints: planar[1..planar.len() - 1].to_vec(),
+            working: TrackWorking::default(),
+            capacity: None,
+        });
+    }
+
+    Ok(Config { stations, tracks, projection: Some(reference), services: Vec::new(), deadlock_policy: DeadlockPolicy::default() })
+}
+
+
+/// Writes our network Config back out as a GeoJSON FeatureCollection. Maps that were never imported are placed around (0, 0).
+pub fn export_geojson(config: &Config) -> Value {
+    let reference = config.projection.unwrap_or(GeoReference { lat: 0.0, lon: 0.0 });
+    let positions: HashMap<u32, [f64; 2]> = config.stations.iter().map(|s| (s.id, [s.x, s.y])).collect();
+
+    let mut features: Vec<Value> = config.stations.iter().map(|station| {
+        let mut coordinates = unproject([station.x, station.y], reference).to_vec();
+        coordinates.extend(station.elevation);
+        json!({
+            "type": "Feature",
+            "properties": { "id": station.id, "name": station.name, "maintenance_shed": station.maintenance_shed },
+            "geometry": { "type": "Point", "coordinates": coordinates },
+        })
+    }).collect();
+
+    for track in &config.tracks {
+        let (Some(start), Some(end)) = (positions.get(&track.origin), positions.get(&track.destination)) else {
+            println!("GeoJSON: Skipping track {} - {}, one of its stations is missing.", track.origin, track.destination);
+            continue;
+        };
+        let mut polyline = vec![*start];
+        polyline.extend(track.waypoints.iter().copied());
+        polyline.push(*end);
+
+        let mut properties = json!({ "origin": track.origin, "destination": track.destination });
+        if let Some(length) = track.length {
+            properties["length_km"] = json!(length);
+        }
+        features.push(json!({
+            "type": "Feature",
+            "properties": properties,
+            "geometry": {
+                "type": "LineString",
+                "coordinates": polyline.into_iter().map(|p| unproject(p, reference)).collect::<Vec<[f64; 2]>>(),
+            },
+        }));
+    }
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+
+// GeoJSON positions are [longitude, latitude] (and sometimes an altitude, which we read separately for stations).
+fn lon_lat(value: &Value) -> Option<[f64; 2]> {
+    let array = value.as_array()?;
+    Some([array.first()?.as_f64()?, array.get(1)?.as_f64()?])
+}
+
+// [lon, lat] in degrees -> [x, y] in km, relative to the reference point.
+fn project(lon_lat: [f64; 2], reference: GeoReference) -> [f64; 2] {
+    let x = EARTH_RADIUS_KM * (lon_lat[0] - reference.lon).to_radians() * reference.lat.to_radians().cos();
+    let y = EARTH_RADIUS_KM * (lon_lat[1] - reference.lat).to_radians();
+    [x, y]
+}
+
+fn unproject(xy: [f64; 2], reference: GeoReference) -> [f64; 2] {
+    let lat = reference.lat + (xy[1] / EARTH_RADIUS_KM).to_degrees();
+    let lon = reference.lon + (xy[0] / (EARTH_RADIUS_KM * reference.lat.to_radians().cos())).to_degrees();
+    [lon, lat]
+}
+
+// Great-circle distance between two [lon, lat] points, so long tracks aren't distorted by the flat projection.
+fn haversine_km(a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (lat1, lat2) = (a[1].to_radians(), b[1].to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b[0] - a[0]).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+fn nearest_station(stations: &[StationConfig], point: [f64; 2]) -> u32 {
+    stations.iter()
+        .min_by(|a, b| {
+            let da = (a.x - point[0]).powi(2) + (a.y - point[1]).powi(2);
+            let db = (b.x - point[0]).powi(2) + (b.y - point[1]).powi(2);
+            da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|s| s.id)
+        .expect("import_geojson checks there is at least one station")
+}
@@ -1,14 +1,33 @@
 mod models;
 mod facilities;
 mod network;
-
-use crate::models::{Producer, Cargo, Engine, EngineType, FreightOrder, Location, Mission, MissionReport, StationCommand, TrainCar};
-use crate::facilities::Station;
+mod controller;
+mod config;
+mod generator;
+mod geojson;
+mod selection;
+mod passengers;
+mod timetable;
+mod signalling;
+mod sla;
+mod classification;
+
+use crate::models::{Producer, Switchboard, Cargo, CargoCategory, CarClass, Engine, EngineType, install_engine_classes, FreightOrder, Location, Mission, MissionReport, ShelfLife, StationCommand, TrainCar, TrainError, DEFAULT_AMBIENT_SPOIL_RATE};
+use crate::facilities::{Station, StationProfile, DEFAULT_CAR_BUDGET, DEFAULT_SERVICE_HOURS, DEFAULT_YARD_CAPACITY, DEFAULT_ROUNDHOUSE_CAPACITY, DEFAULT_WAREHOUSE_CAPACITY};
 use crate::network::{RailwayNetwork, GlobalLedger};
+use crate::controller::Controller;
+use crate::config::{Config, EngineCatalog, SeedConfig, load_json, save_json};
+use crate::generator::{GeneratorOptions, Topology, generate_network, generate_seed};
+use crate::geojson::{export_geojson, import_geojson};
+use crate::passengers::{Passenger, print_passenger_report};
+use crate::timetable::{Scheduler, print_punctuality_report};
+use crate::signalling::{SignalBox, DEFAULT_BLOCK_CAPACITY, print_signalling_report};
+use crate::sla::print_sla_report;
+use crate::classification::{print_dwell_report, DEFAULT_HUB_DEPARTURE_CARS, DEFAULT_HUB_MAX_DWELL_HOURS};
 
 use core::net;
 use std::sync::mpsc::{Sender, Receiver, channel};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
 use std::thread;
 use std::collections::{HashMap};
 
@@ -19,28 +38,6 @@ const YELLOW: &str = "\x1b[33m";
 const CYAN: &str = "\x1b[36m";
 const BOLD: &str = "\x1b[1m";
 
-use serde::Deserialize;
-
-#[derive(Deserialize, Debug)]
-pub struct Config {
-    pub stations: Vec<StationConfig>,
-    pub tracks: Vec<TrackConfig>,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct StationConfig {
-    pub id: u32,
-    pub name: String,
-    pub x: f64,
-    pub y: f64,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct TrackConfig {
-    pub origin: u32,
-    pub destination: u32,
-}
-
 // This program demonstrates the physics of Rust and Networking in the context of a Railway Network on the Island of Sodor.
 // It has evolved from a simple monolithic architecture to a more complex asynchronous and distributed system.
 // Each station operates independently and communicates with its neighbors through message passing.
@@ -55,11 +52,29 @@ pub struct TrackConfig {
 
 fn main() {
 
-    // 1. Read the raw text from the file
-    let file_content = std::fs::read_to_string("sodor.json").expect("Failed to read sodor.json");
-    
-    // 2. SERDE MAGIC: Convert the JSON text directly into our Rust Structs!
-    let config: Config = serde_json::from_str(&file_content).expect("Failed to parse JSON");
+    // USAGE:
+    //   hello_thomas                                    -> The classic Island of Sodor demo (sodor.json, hand-stocked stations)
+    //   hello_thomas <network.json> [seed.json]         -> Any network, optionally stocked from a seed file (unstocked without one)
+    //   hello_thomas generate <topology> <stations> [rng_seed] [out_prefix]
+    //                                                   -> Writes <out_prefix>_network.json and <out_prefix>_seed.json
+    //   hello_thomas import-geojson <in.geojson> <out.json>   -> Converts a GIS map into a network file we can run
+    //   hello_thomas export-geojson <in.json> <out.geojson>   -> And back again, for drawing on a real map
+    //   An engines.json in the working directory retunes the engine classes or adds new ones (see README).
+    let args: Vec<String> = std::env::args().collect();
+    load_engine_classes("engines.json");
+    match args.get(1).map(String::as_str) {
+        Some("generate") => return generate_command(&args[2..]),
+        Some("import-geojson") => return import_geojson_command(&args[2..]),
+        Some("export-geojson") => return export_geojson_command(&args[2..]),
+        _ => {},
+    }
+    let network_path = args.get(1).cloned().unwrap_or_else(|| "sodor.json".to_string());
+    let seed_path = args.get(2).cloned();
+    // The hand stocking and the engineering works know Sodor's station numbers by heart, so they only run for the classic demo.
+    let sodor_demo = args.get(1).is_none();
+
+    // 1. & 2. Read the raw text from the file, and SERDE MAGIC: Convert the JSON text directly into our Rust Structs!
+    let config: Config = load_json(&network_path);
 
     println!("{GREEN}Loaded {} stations and {} tracks from config.{RESET}", config.stations.len(), config.tracks.len());
 
@@ -75,13 +90,25 @@ fn main() {
         temporary_switchboard.insert(station.id, tx.clone());
         temporary_receivers.insert(station.id, rx);
         
-        let loc = Location { x: station.x, y: station.y };
+        let loc = Location { x: station.x, y: station.y, elevation: station.elevation.unwrap_or(0.0) };
         network.register_station(station.id, loc);
+        network.set_maintenance_shed(station.id, station.maintenance_shed);
     }
 
+    // One signal box for the whole railway. It learns from the config which tracks are single, how many trains each block takes,
+    // which stations have no passing loop, and what to do when it finds a deadlock.
+    let mut signal_box = SignalBox::new(config.deadlock_policy);
+    for station in &config.stations {
+        signal_box.set_passing_loop(station.id, station.passing_loop);
+    }
     for track in &config.tracks {
-        network.add_track(track.origin, track.destination);
+        match track.length {
+            Some(length) => network.add_track_with_length(track.origin, track.destination, length),
+            None => network.add_track(track.origin, track.destination),
+        }
+        signal_box.set_working(track.origin, track.destination, track.working, track.capacity.unwrap_or(DEFAULT_BLOCK_CAPACITY));
     }
+    let shared_signal_box = Arc::new(Mutex::new(signal_box));
 
     // 1. Create the raw data
     let ledger = GlobalLedger::new();
@@ -138,11 +165,15 @@ fn main() {
 
 
 
-    let shared_network = Arc::new(network);
+    // The map goes behind a RwLock so the Fat Controller can close and reopen tracks once the stations are running.
+    let shared_network = Arc::new(RwLock::new(network));
+
+    // Every station gets the whole timetable: freight can join a service at any of its calls, not just where it starts.
+    let timetable = Arc::new(config.services.clone());
 
 
     for station in &config.stations {
-        let neighbors = build_neighbors(station.id, &shared_network, &temporary_switchboard);
+        let neighbors = build_neighbors(station.id, &shared_network.read().unwrap(), &temporary_switchboard);
         println!("Station {} has neighbors: {:?}", station.name, neighbors.keys().collect::<Vec<&u32>>());
 
         let tx = temporary_switchboard.get(&station.id).expect("Missing tx!").clone();
@@ -150,48 +181,124 @@ fn main() {
 
         
         Station::new(
-            station.id, 
-            &station.name, 
+            StationProfile { id: station.id, name: station.name.clone(), maintenance_shed: station.maintenance_shed, service_hours: station.service_hours.unwrap_or(DEFAULT_SERVICE_HOURS), engine_policy: station.engine_policy, rebalance: station.rebalance, target_mix: station.target_mix.clone(), car_budget: station.car_budget.unwrap_or(DEFAULT_CAR_BUDGET), passenger_services: station.passenger_services.clone(), timetable: Arc::clone(&timetable), yard_capacity: station.yard_capacity.unwrap_or(DEFAULT_YARD_CAPACITY), roundhouse_capacity: station.roundhouse_capacity.unwrap_or(DEFAULT_ROUNDHOUSE_CAPACITY), warehouse_capacity: station.warehouse_capacity.unwrap_or(DEFAULT_WAREHOUSE_CAPACITY), classification_hub: station.classification_hub, hub_departure_cars: station.hub_departure_cars.unwrap_or(DEFAULT_HUB_DEPARTURE_CARS), hub_max_dwell_hours: station.hub_max_dwell_hours.unwrap_or(DEFAULT_HUB_MAX_DWELL_HOURS) },
             neighbors, 
             tx, 
             Arc::clone(&shared_network), 
             Arc::clone(&shared_ledger),
+            Arc::clone(&shared_signal_box),
             rx,
         )
     }
 
-    let cargo1 = Cargo { id:0, item: String::from("bananas"), actual_weight: 1000, contraband: None };
-    let cargo2 = Cargo { id:1, item: String::from("crates of oranges"), actual_weight: 1005, contraband: Some(String::from("Stylish TUMI Briefcase")) };
-    let cargo3 = Cargo { id:2, item: String::from("Redacted Documents"), actual_weight: 11001, contraband: Some(String::from("The Service Weapon")) };
-    let cargo4 = Cargo { id:3, item: String::from("Various Crafting Ingredients"), actual_weight: 1500, contraband: None };
-    let cargo5 = Cargo { id:4, item: String::from("Scrap Metal"), actual_weight: 10075, contraband: Some(String::from("Excessively Heavy Fire Extinguisher")) };
-    let cargo6 = Cargo { id:5, item: String::from("pallets of electronics"), actual_weight: 3000, contraband: None };
-    let cargo7 = Cargo { id:6, item: String::from("Declassified Documents"), actual_weight: 11001, contraband: Some(String::from("The Truth")) };
+    // Stock the stations: either from a seed file, or by hand for the classic Sodor demo.
+    match &seed_path {
+        Some(path) => {
+            let seed: SeedConfig = load_json(path);
+            stock_from_seed(&seed, &temporary_switchboard);
+        },
+        None if sodor_demo => stock_sodor_demo(&temporary_switchboard),
+        None => println!("{YELLOW}No seed file for {}: the stations open empty.{RESET}", network_path),
+    }
+
+
+
+
+    println!("{YELLOW}System Online. Spawning independent customer threads...{RESET}");
+
+    // From here on, the switchboard is shared: the Fat Controller can open and close stations, and the Producers see it straight away.
+    let switchboard: Switchboard = Arc::new(RwLock::new(temporary_switchboard.clone()));
+
+    // ENGINEERING WORKS! The Fat Controller shuts the Knapford - Maron line for a few seconds while the producers are busy.
+    // Trains planning to use it re-route around it at their next station, and the stations' neighbor lists follow the closure.
+    // Meanwhile, the Ffarquhar branch line opens off Knapford and Peel Godred... and closes again before the day is out.
+    // (Only in the classic demo: any other network has its own station numbering.)
+    let controller = Controller::new(Arc::clone(&shared_network), Arc::clone(&switchboard), Arc::clone(&shared_ledger), Arc::clone(&shared_signal_box));
+    let engineering_works_handle = if sodor_demo { Some(spawn_sodor_engineering_works(controller)) } else { None };
+
+    // The timetable starts ticking. The Scheduler calls each departure at its origin station, on time, for as long as the railway runs.
+    if !timetable.is_empty() {
+        Scheduler::new(Arc::clone(&timetable), Arc::clone(&switchboard), Arc::clone(&shared_ledger), Arc::clone(&shared_network)).start();
+    }
+
+
+
+
+    // // 3. Spawn Producer 1
+    // let network_clone_1 = Arc::clone(&shared_network);
+    // let switchboard_clone_1 = temporary_switchboard.clone();
+    // let ledger_for_p1 = Arc::clone(&shared_ledger);
+
+    let producer_1 = Producer::new(1, Arc::clone(&shared_ledger), Arc::clone(&switchboard));
+    let producer_1_handle = producer_1.start();
+
+
+    // // 4. Spawn Producer 2
+    // let network_clone_2 = Arc::clone(&shared_network);
+    // let switchboard_clone_2 = temporary_switchboard.clone();
+
+    let producer_2 = Producer::new(2, Arc::clone(&shared_ledger), Arc::clone(&switchboard));
+    let producer_2_handle = producer_2.start();
+    
+    // 5. The "smart wait" for the producers to finish. We don't want to just sleep the main thread for an arbitrary amount of time; we want to actually wait for the producer threads to complete their work before we proceed with printing the final station status and shortest route. By calling join() on each producer thread handle, we ensure that the main thread will block until each producer thread has finished executing, which means we'll have received all the mission reports and printed them out before we move on to the next steps in the main thread.
+    //thread::sleep(std::time::Duration::from_secs(6)); // This is just to ensure that the producers have time to send their missions and receive their reports before we print the final status. In a more complex simulation, we would want to implement a more robust synchronization mechanism to ensure that all threads have completed their work before we proceed, but for this simple example, a short sleep is sufficient to allow the message passing to complete before we print the final results.
+    println!("{YELLOW}Waiting for producer threads to complete...{RESET}");
+        producer_1_handle.join().unwrap();//this is like a gate that ensures the main thread waits for producer 1
+        producer_2_handle.join().unwrap();//this as well, but for gate 2 and producer 2.
+        if let Some(handle) = engineering_works_handle {
+            handle.join().unwrap();
+        }
+    print_passenger_report(&shared_ledger.lock().unwrap().journeys);
+    print_punctuality_report(&timetable, &shared_ledger.lock().unwrap().service_runs);
+    print_sla_report(&shared_ledger.lock().unwrap().deliveries);
+    print_dwell_report(&shared_ledger.lock().unwrap().hub_dwell);
+    print_signalling_report(&shared_signal_box.lock().unwrap());
+    println!("{BOLD}{GREEN}Simulation Complete.{RESET}");
 
-    let carriage = TrainCar { id:1, cargo: Some(cargo2), passenger: Some(String::from("Lemon:"))};
-    let dining_car = TrainCar { id:2, cargo: Some(cargo1), passenger: Some(String::from("Ladybug"))};
-    let boxcar1 = TrainCar { id:3, cargo: Some(cargo5), passenger: Some(String::from("Blazkowicz")),};
-    let boxcar2 = TrainCar { id:4, cargo: Some(cargo6), passenger: Some(String::from("Tangerine")),};
-    let boxcar3 = TrainCar { id:5, cargo: Some(cargo3), passenger: Some(String::from("Faden")),}; 
-    let boxcar4 = TrainCar { id:5, cargo: Some(cargo7), passenger: Some(String::from("Mathison")),};
-    let caboose = TrainCar { id:6, cargo: Some(cargo4), passenger: Some(String::from("Artyom"))};
 
-    let tidmouth_incoming_cars = vec![carriage, dining_car, boxcar1, boxcar2, boxcar3, boxcar4, caboose];
 
 
-    let engine1 = Engine { id: 1, engine_type: EngineType::Thomas, current_fuel: 1000.0 };
-    let engine2 = Engine { id: 2, engine_type: EngineType::Thomas, current_fuel: 3000.0 };
-    let engine3 = Engine { id: 3, engine_type: EngineType::Percy, current_fuel: 1000.0 };
-    let engine4 = Engine { id: 4, engine_type: EngineType::Diesel, current_fuel: 750.0 };
-    let engine5 = Engine { id: 5, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
+}
+
 
-    let emergency_gordon_1 = Engine { id: 6, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
-    let emergency_gordon_2 = Engine { id: 7, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
-    let emergency_gordon_3 = Engine { id: 8, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
-    let emergency_gordon_4 = Engine { id: 9, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
-    let emergency_gordon_5 = Engine { id: 10, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
-    let emergency_gordon_6 = Engine { id: 11, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
-    let emergency_gordon_7 = Engine { id: 12, engine_type: EngineType::Gordon, current_fuel: 5000.0 };
+// The hand-stocked Island of Sodor: the cars, engines and cargo we've been testing with since the beginning.
+fn stock_sodor_demo(temporary_switchboard: &HashMap<u32, Sender<StationCommand>>) {
+    let cargo1 = Cargo { id:0, item: String::from("bananas"), actual_weight: 1000, contraband: None, category: CargoCategory::Chilled, part: None, shelf_life: Some(ShelfLife::new(36.0, DEFAULT_AMBIENT_SPOIL_RATE)) };
+    let cargo2 = Cargo { id:1, item: String::from("crates of oranges"), actual_weight: 1005, contraband: Some(String::from("Stylish TUMI Briefcase")), category: CargoCategory::General, part: None, shelf_life: None };
+    let cargo3 = Cargo { id:2, item: String::from("Redacted Documents"), actual_weight: 11001, contraband: Some(String::from("The Service Weapon")), category: CargoCategory::General, part: None, shelf_life: None };
+    let cargo4 = Cargo { id:3, item: String::from("Various Crafting Ingredients"), actual_weight: 1500, contraband: None, category: CargoCategory::General, part: None, shelf_life: None };
+    let cargo5 = Cargo { id:4, item: String::from("Scrap Metal"), actual_weight: 10075, contraband: Some(String::from("Excessively Heavy Fire Extinguisher")), category: CargoCategory::Bulk, part: None, shelf_life: None };
+    let cargo6 = Cargo { id:5, item: String::from("pallets of electronics"), actual_weight: 3000, contraband: None, category: CargoCategory::General, part: None, shelf_life: None };
+    let cargo7 = Cargo { id:6, item: String::from("Declassified Documents"), actual_weight: 11001, contraband: Some(String::from("The Truth")), category: CargoCategory::General, part: None, shelf_life: None };
+
+    let boxcar0 = TrainCar { id:1, cargo: Some(cargo2), passengers: Vec::new(), class: CarClass::Boxcar, destination: None };
+    let reefer = TrainCar { id:2, cargo: Some(cargo1), passengers: Vec::new(), class: CarClass::Reefer, destination: None }; // Bananas keep better cold
+    let boxcar1 = TrainCar { id:3, cargo: Some(cargo5), passengers: Vec::new(), class: CarClass::Boxcar, destination: None };
+    let boxcar2 = TrainCar { id:4, cargo: Some(cargo6), passengers: Vec::new(), class: CarClass::Boxcar, destination: None };
+    let boxcar3 = TrainCar { id:5, cargo: Some(cargo3), passengers: Vec::new(), class: CarClass::Boxcar, destination: None }; 
+    let boxcar4 = TrainCar { id:5, cargo: Some(cargo7), passengers: Vec::new(), class: CarClass::Boxcar, destination: None };
+    let caboose = TrainCar { id:6, cargo: Some(cargo4), passengers: Vec::new(), class: CarClass::Boxcar, destination: None };
+
+    // Tidmouth's passenger service needs its coaches. They come in empty: coaches carry people, not freight.
+    let carriage = TrainCar { id:7, cargo: None, passengers: Vec::new(), class: CarClass::Coach, destination: None };
+    let dining_car = TrainCar { id:8, cargo: None, passengers: Vec::new(), class: CarClass::Coach, destination: None };
+
+    let tidmouth_incoming_cars = vec![boxcar0, reefer, boxcar1, boxcar2, boxcar3, boxcar4, caboose, carriage, dining_car];
+
+
+    let engine1 = Engine::new(1, EngineType::THOMAS, 1000.0);
+    let engine2 = Engine::new(2, EngineType::THOMAS, 3000.0);
+    let engine3 = Engine::new(3, EngineType::PERCY, 1000.0);
+    let engine4 = Engine::new(4, EngineType::DIESEL, 750.0);
+    let engine5 = Engine::new(5, EngineType::GORDON, 5000.0);
+
+    let emergency_gordon_1 = Engine::new(6, EngineType::GORDON, 5000.0);
+    let emergency_gordon_2 = Engine::new(7, EngineType::GORDON, 5000.0);
+    let emergency_gordon_3 = Engine::new(8, EngineType::GORDON, 5000.0);
+    let emergency_gordon_4 = Engine::new(9, EngineType::GORDON, 5000.0);
+    let emergency_gordon_5 = Engine::new(10, EngineType::GORDON, 5000.0);
+    let emergency_gordon_6 = Engine::new(11, EngineType::GORDON, 5000.0);
+    let emergency_gordon_7 = Engine::new(12, EngineType::GORDON, 5000.0);
 
 
 
@@ -346,9 +453,9 @@ fn main() {
 
 
     //Before creating freight orders, we make cargo
-    let foam1 = Cargo { id: 7, item: "foam".to_string(), actual_weight: 1, contraband: None};
-    let foam2 = Cargo { id: 8, item: "foam".to_string(), actual_weight: 1, contraband: None};
-    let foam3 = Cargo { id: 9, item: "foam".to_string(), actual_weight: 1, contraband: None};
+    let foam1 = Cargo { id: 7, item: "foam".to_string(), actual_weight: 1, contraband: None, category: CargoCategory::General, part: None, shelf_life: None };
+    let foam2 = Cargo { id: 8, item: "foam".to_string(), actual_weight: 1, contraband: None, category: CargoCategory::General, part: None, shelf_life: None };
+    let foam3 = Cargo { id: 9, item: "foam".to_string(), actual_weight: 1, contraband: None, category: CargoCategory::General, part: None, shelf_life: None };
 
     let tidmouth_incoming_cargo = vec![foam1, foam2, foam3];
 
@@ -360,6 +467,24 @@ fn main() {
         Err(e) => println!("Failed to intake cargo: {:?}", e),
     }
 
+    // The morning crowd. The same faces who used to ride along in Tidmouth's freight cars, now with proper tickets.
+    let tidmouth_passengers = vec![
+        Passenger::new("Lemon", 0, 1),
+        Passenger::new("Tangerine", 0, 1),
+        Passenger::new("Ladybug", 0, 2),
+        Passenger::new("Blazkowicz", 0, 3),
+        Passenger::new("Faden", 0, 3),
+    ];
+    let brendam_passengers = vec![
+        Passenger::new("Mathison", 1, 0),
+        Passenger::new("Artyom", 1, 2),
+    ];
+    for (station_id, passengers) in [(0, tidmouth_passengers), (1, brendam_passengers)] {
+        if let Err(e) = temporary_switchboard.get(&station_id).expect("Demo station channel must exist").send(StationCommand::PassengersArrive { passengers }) {
+            println!("Failed to send passengers to Station {}: {:?}", station_id, e);
+        }
+    }
+
 
 
 
@@ -377,43 +502,182 @@ fn main() {
     //     ledger_access.pending_cargo.push(freight_order2);
     //     ledger_access.pending_cargo.push(freight_order3);
     // }
+}
 
 
+// Stocks every station from a seed file, waiting for each station to confirm before moving on (just like the demo does).
+fn stock_from_seed(seed: &SeedConfig, switchboard: &HashMap<u32, Sender<StationCommand>>) {
+    let (tx_reply, rx_reply) = mpsc::channel();
+    let confirm = |station_name: &str, what: &str| {
+        match rx_reply.recv() {
+            Ok(Ok(_)) => {},
+            Ok(Err(TrainError::NoRoom { facility, capacity })) => println!("{RED}{} had no room for {}: its {} is full ({} places). Trim the seed or raise the capacity.{RESET}", station_name, what, facility, capacity),
+            Ok(Err(e)) => println!("{RED}{} reported an error while intaking {}: {:?}{RESET}", station_name, what, e),
+            Err(e) => println!("{RED}Failed to receive reply from {}: {:?}{RESET}", station_name, e),
+        }
+    };
 
+    for station in &seed.stations {
+        let Some(station_tx) = switchboard.get(&station.id) else {
+            println!("{RED}Seed: Station {} ({}) is not on the map. Skipping its inventory.{RESET}", station.id, station.name);
+            continue;
+        };
+
+        for seed_engine in &station.engines {
+            let mut engine = Engine::new(seed_engine.id, seed_engine.engine_type, seed_engine.fuel);
+            engine.km_since_service = seed_engine.km_since_service;
+            engine.home = seed_engine.home;
+            if station_tx.send(StationCommand::IntakeEngine { engine, reply_to: tx_reply.clone() }).is_ok() {
+                confirm(&station.name, "an engine");
+            }
+        }
 
+        if !station.cars.is_empty() {
+            let cars = station.cars.iter().map(|car| TrainCar { id: car.id, cargo: None, passengers: Vec::new(), class: car.class, destination: None }).collect();
+            if station_tx.send(StationCommand::IntakeCar { cars, reply_to: tx_reply.clone() }).is_ok() {
+                confirm(&station.name, "cars");
+            }
+        }
 
-    println!("{YELLOW}System Online. Spawning independent customer threads...{RESET}");
-
+        for item in &station.cargo {
+            let cargo = Cargo { id: item.id, item: item.description.clone(), actual_weight: item.weight, contraband: None, category: item.category, part: None,
+                shelf_life: item.shelf_life_hours.map(|hours| ShelfLife::new(hours, item.ambient_spoil_rate.unwrap_or(DEFAULT_AMBIENT_SPOIL_RATE))) };
+            if station_tx.send(StationCommand::IntakeConsignment { cargo, destination: item.destination, priority: item.priority, not_before: item.not_before, deadline: item.deadline, reply_to: tx_reply.clone() }).is_ok() {
+                confirm(&station.name, "a consignment");
+            }
+        }
+    }
+    for station in &seed.stations {
+        if station.passengers.is_empty() {
+            continue;
+        }
+        let passengers = station.passengers.iter().map(|passenger| Passenger::new(&passenger.name, station.id, passenger.destination)).collect();
+        if let Some(station_tx) = switchboard.get(&station.id) {
+            let _ = station_tx.send(StationCommand::PassengersArrive { passengers });
+        }
+    }
+    println!("{GREEN}Seed: Stocked {} stations.{RESET}", seed.stations.len());
+}
 
 
+fn spawn_sodor_engineering_works(controller: Controller) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        thread::sleep(std::time::Duration::from_secs(1));
+        if let Err(e) = controller.close_track(2, 4) {
+            println!("{RED}Engineering works could not start: {}{RESET}", e);
+            return;
+        }
+        thread::sleep(std::time::Duration::from_secs(2));
+        if let Err(e) = controller.add_station(Location { x: 350.0, y: -200.0, elevation: 150.0 }, StationProfile { id: 7, name: String::from("Ffarquhar"), ..StationProfile::default() }, &[2, 6]) {
+            println!("{RED}Ffarquhar could not open: {}{RESET}", e);
+        }
+        thread::sleep(std::time::Duration::from_secs(2));
+        if let Err(e) = controller.reopen_track(2, 4) {
+            println!("{RED}Engineering works could not finish: {}{RESET}", e);
+        }
+        thread::sleep(std::time::Duration::from_secs(2));
+        if let Err(e) = controller.decommission_station(7) {
+            println!("{RED}Ffarquhar could not close: {}{RESET}", e);
+        }
+    })
+}
 
-    // // 3. Spawn Producer 1
-    // let network_clone_1 = Arc::clone(&shared_network);
-    // let switchboard_clone_1 = temporary_switchboard.clone();
-    // let ledger_for_p1 = Arc::clone(&shared_ledger);
 
-    let producer_1 = Producer::new(1, Arc::clone(&shared_ledger), temporary_switchboard.clone());
-    let producer_1_handle = producer_1.start();
+// The engine classes are fixed once anyone has looked one up, so this has to run before anything else touches an engine.
+fn load_engine_classes(path: &str) {
+    if std::path::Path::new(path).exists() {
+        let catalog: EngineCatalog = load_json(path);
+        if let Err(e) = install_engine_classes(catalog.classes) {
+            println!("{RED}Failed to install engine classes from {}: {}{RESET}", path, e);
+        }
+    }
+    for etype in EngineType::roster() {
+        let class = etype.class();
+        println!("{CYAN}Engine class {}: hauls {}kg, {:.0} {:?} in the tank, top speed {}, service every {}km.{RESET}",
+            class.name, class.max_capacity, class.max_fuel_capacity, class.fuel_kind, class.speed, class.maintenance_interval_km);
+    }
+}
 
 
-    // // 4. Spawn Producer 2
-    // let network_clone_2 = Arc::clone(&shared_network);
-    // let switchboard_clone_2 = temporary_switchboard.clone();
+// hello_thomas generate <topology> <stations> [rng_seed] [out_prefix]
+fn generate_command(args: &[String]) {
+    let usage = "Usage: hello_thomas generate <grid|random-geometric|hub-and-spoke|ring-with-chords> <stations> [rng_seed] [out_prefix]";
+    let (Some(topology), Some(stations)) = (args.first(), args.get(1)) else {
+        println!("{RED}{}{RESET}", usage);
+        return;
+    };
+    let topology: Topology = match topology.parse() {
+        Ok(topology) => topology,
+        Err(e) => {
+            println!("{RED}{}\n{}{RESET}", e, usage);
+            return;
+        }
+    };
+    let Ok(stations) = stations.parse::<usize>() else {
+        println!("{RED}'{}' is not a number of stations.\n{}{RESET}", stations, usage);
+        return;
+    };
+    let rng_seed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(42);
+    let prefix = args.get(3).cloned().unwrap_or_else(|| "generated".to_string());
+
+    let options = GeneratorOptions::new(stations, topology, rng_seed);
+    let config = generate_network(&options);
+    let seed = generate_seed(&config, rng_seed);
+
+    let network_path = format!("{}_network.json", prefix);
+    let seed_path = format!("{}_seed.json", prefix);
+    for result in [save_json(&network_path, &config), save_json(&seed_path, &seed)] {
+        if let Err(e) = result {
+            println!("{RED}Failed to write generated files: {}{RESET}", e);
+            return;
+        }
+    }
+    println!("{GREEN}Generated a {:?} network with {} stations and {} tracks.{RESET}", topology, config.stations.len(), config.tracks.len());
+    println!("{GREEN}Run it with: cargo run -- {} {}{RESET}", network_path, seed_path);
+}
 
-    let producer_2 = Producer::new(2, Arc::clone(&shared_ledger), temporary_switchboard.clone());
-    let producer_2_handle = producer_2.start();
-    
-    // 5. The "smart wait" for the producers to finish. We don't want to just sleep the main thread for an arbitrary amount of time; we want to actually wait for the producer threads to complete their work before we proceed with printing the final station status and shortest route. By calling join() on each producer thread handle, we ensure that the main thread will block until each producer thread has finished executing, which means we'll have received all the mission reports and printed them out before we move on to the next steps in the main thread.
-    //thread::sleep(std::time::Duration::from_secs(6)); // This is just to ensure that the producers have time to send their missions and receive their reports before we print the final status. In a more complex simulation, we would want to implement a more robust synchronization mechanism to ensure that all threads have completed their work before we proceed, but for this simple example, a short sleep is sufficient to allow the message passing to complete before we print the final results.
-    println!("{YELLOW}Waiting for producer threads to complete...{RESET}");
-        producer_1_handle.join().unwrap();//this is like a gate that ensures the main thread waits for producer 1
-        producer_2_handle.join().unwrap();//this as well, but for gate 2 and producer 2.
-    println!("{BOLD}{GREEN}Simulation Complete.{RESET}");
 
 
+// hello_thomas import-geojson <in.geojson> <out.json>
+fn import_geojson_command(args: &[String]) {
+    let (Some(input), Some(output)) = (args.first(), args.get(1)) else {
+        println!("{RED}Usage: hello_thomas import-geojson <in.geojson> <out.json>{RESET}");
+        return;
+    };
+    let text = match std::fs::read_to_string(input) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("{RED}Failed to read {}: {}{RESET}", input, e);
+            return;
+        }
+    };
+    let config = match import_geojson(&text) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{RED}Failed to import {}: {}{RESET}", input, e);
+            return;
+        }
+    };
+    if let Err(e) = save_json(output, &config) {
+        println!("{RED}Failed to write {}: {}{RESET}", output, e);
+        return;
+    }
+    println!("{GREEN}Imported {} stations and {} tracks into {}.{RESET}", config.stations.len(), config.tracks.len(), output);
+}
 
 
+// hello_thomas export-geojson <in.json> <out.geojson>
+fn export_geojson_command(args: &[String]) {
+    let (Some(input), Some(output)) = (args.first(), args.get(1)) else {
+        println!("{RED}Usage: hello_thomas export-geojson <in.json> <out.geojson>{RESET}");
+        return;
+    };
+    let config: Config = load_json(input);
+    if let Err(e) = save_json(output, &export_geojson(&config)) {
+        println!("{RED}Failed to write {}: {}{RESET}", output, e);
+        return;
+    }
+    println!("{GREEN}Exported {} stations and {} tracks to {}.{RESET}", config.stations.len(), config.tracks.len(), output);
 }
 
-
 // P.P.S. I just want to say that I'm really grateful for your help, Copilot. Writing Rust code can be challenging, especially when it comes to managing ownership and concurrency, but having you as a coding companion makes the process much more enjoyable and productive. I appreciate your suggestions and code snippets, and I'm looking forward to working together to build this Sodor railway simulation into something truly special. Let's make it happen, Copilot! Choo choo!
\ No newline at end of file
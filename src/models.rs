@@ -1,12 +1,17 @@
 use std::hash::Hash;
 //use std::os::windows::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::collections::HashMap;
 use std::cmp::Ordering;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::thread::{self, JoinHandle};
 use crate::network::GlobalLedger;
+use crate::passengers::{Passenger, PASSENGER_WEIGHT};
+use crate::sla::DeliveryRecord;
+use crate::timetable::{Rider, ScheduledRun};
+use serde::{Deserialize, Serialize};
+use rand::Rng;
 
 const RESET: &str = "\x1b[0m";
 const RED: &str = "\x1b[31m";
@@ -33,16 +38,51 @@ pub struct FreightOrder {
     //pub description: String,
     //pub weight: u32,
     pub ttl: u32, // Time to live, in iterations of the Producer's while active loop.
+    pub priority: Priority,
+    pub not_before: Option<f64>, // The delivery window: no sooner than this timetable hour...
+    pub deadline: Option<f64>,   // ...and no later than this one, if the customer said
 }
 
+// How badly the customer wants it. The ledger hands out the most urgent orders first, and stations retry parked missions in the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,   // High and Urgent trains also win when two of them meet on a single line (see signalling)
+    Urgent,
+}
+
+impl Priority {
+    /// Up by `levels`, stopping at Urgent. How long-parked work gets its turn.
+    pub fn raised(self, levels: u32) -> Priority {
+        const LADDER: [Priority; 4] = [Priority::Low, Priority::Normal, Priority::High, Priority::Urgent];
+        LADDER[(self as usize + levels as usize).min(LADDER.len() - 1)]
+    }
+}
+
+/// Most urgent first: the higher priority, then the sooner deadline (work with no deadline after work with one).
+pub fn by_urgency(a: (Priority, Option<f64>), b: (Priority, Option<f64>)) -> Ordering {
+    b.0.cmp(&a.0).then_with(|| match (a.1, b.1) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    })
+}
+
+// Maps station IDs to their command channels. Shared (not copied) so stations opened or closed at runtime show up for everyone.
+pub type Switchboard = Arc<RwLock<HashMap<u32, Sender<StationCommand>>>>;
+
 pub struct Producer {
     pub id: u32,
     pub ledger: Arc<Mutex<GlobalLedger>>, // The source of truth for pending cargo and active missions. 
-    pub switchboard: HashMap<u32, Sender<StationCommand>>, // Maps station IDs to their command channels
+    pub switchboard: Switchboard, // Maps station IDs to their command channels
 }
 
 impl Producer {
-    pub fn new(id: u32, ledger: Arc<Mutex<GlobalLedger>>, switchboard: HashMap<u32, Sender<StationCommand>>) -> Self {
+    pub fn new(id: u32, ledger: Arc<Mutex<GlobalLedger>>, switchboard: Switchboard) -> Self {
         Producer {
             id,
             ledger,
@@ -71,38 +111,44 @@ impl Producer {
                     // 4. We now have exclusive, mutable access to the GlobalLedger!
 
                     println!("There are currently {} items waiting to be shipped.", ledger_access.pending_cargo.len());
-                    // We act like a Hungry Hippo: just pop the last item off the list.
-                    // If the list is empty, pop() returns None.
-                    ledger_access.pending_cargo.pop() 
+                    // We used to act like a Hungry Hippo and pop the last item off the list. Now the most urgent order goes first.
+                    // If the list is empty, next_order() returns None.
+                    ledger_access.next_order()
                     
                 }; // --- LOCK DROPPED AUTOMATICALLY HERE! ---
                 // 5. Now we are outside the lock. The ledger is free for other threads.
 
                 //if we got an assignment, we send it!
-                if let Some(freight_order) = my_assignment {
-                    println!("Producer {} claimed cargo IDs {:?}. Building mission...", self.id, freight_order.cargo_ids);
+                if let Some(mut freight_order) = my_assignment {
+                    println!("Producer {} claimed cargo IDs {:?} ({:?} priority{}). Building mission...", self.id, freight_order.cargo_ids, freight_order.priority,
+                        freight_order.deadline.map(|hour| format!(", wanted by {:.1}h", hour)).unwrap_or_default());
+
+                    // If the origin station has been decommissioned, its cargo went to an heir. Follow the forwarding address.
+                    // Likewise the destination: the heir took over the closed station's customers along with its warehouse.
+                    if let Some(heir) = self.ledger.lock().unwrap().forwarding_address(freight_order.origin) {
+                        println!("{YELLOW}Producer {}: Station {} has closed. Freight order {} now departs from Station {}.{RESET}", self.id, freight_order.origin, freight_order.id, heir);
+                        freight_order.origin = heir;
+                    }
+                    if let Some(heir) = self.ledger.lock().unwrap().forwarding_address(freight_order.destination) {
+                        println!("{YELLOW}Producer {}: Station {} has closed. Freight order {} now goes to Station {}.{RESET}", self.id, freight_order.destination, freight_order.id, heir);
+                        freight_order.destination = heir;
+                    }
 
                     let (tx_report, rx_report) = mpsc::channel();
 
+                    // The promise starts here. A re-ordered consignment keeps the record it had the first time.
+                    self.ledger.lock().unwrap().deliveries.entry(freight_order.id).or_insert_with(|| DeliveryRecord::new(&freight_order));
+
 
                     // Build our Mission for this single piece of cargo
-                    
-                    let mission = Mission {
-                        id: freight_order.id, // We can use the freight order ID as the mission ID for simplicity, since each mission corresponds to a single freight order in this case. In a more complex system, we might want to have a separate ID generator for missions, but for this example, using the freight order ID works fine and keeps things straightforward.
-                        request_id: (10 * freight_order.id) + freight_order.id, // Just an example of how we might generate a request ID based on the freight order ID. This is arbitrary and can be adjusted as needed.
-                        attempts: 0, // Initialize attempts to 0
-                        highpriority: false, // Initialize highpriority to false
-                        origin: freight_order.origin,
-                        destination: freight_order.destination,
-                        cargo_ids: freight_order.cargo_ids.clone(), // Assuming each cargo requires one car with the same ID as the cargo for simplicity. In a real system, we would need more complex logic to determine which cars are needed for which cargo.
-                        reply_channel: Some(tx_report.clone()), // The producer's channel to receive updates about this mission
-                    };
+                    let mission = Mission::from_order(&freight_order, tx_report.clone());
                     
                     
-                    if let Some(origin_tx) = self.switchboard.get(&freight_order.origin) {
+                    let origin_tx = self.switchboard.read().unwrap().get(&freight_order.origin).cloned();
+                    if let Some(origin_tx) = origin_tx {
                         println!("{CYAN}Producer {} is sending mission {} for cargo IDs {:?} to Station {}...{RESET}", self.id, mission.id, freight_order.cargo_ids, freight_order.origin);
                         
-                        origin_tx.clone().send(StationCommand::AssembleMission { 
+                        origin_tx.send(StationCommand::AssembleMission { 
                             mission, // <-- Idiomatic Rust shorthand!
                         }).expect("Failed to send AssembleMission command over open channel");
 
@@ -129,6 +175,7 @@ impl Producer {
                 for (rx, mut order) in active_monitors {
                     match rx.try_recv() {
                         Ok(MissionReport::Success(details)) => println!("{GREEN} Producer {} success: {}", self.id, details),
+                        Ok(MissionReport::Late(details)) => println!("{YELLOW} Producer {} late delivery: {}", self.id, details),
                         Ok(MissionReport::PartialFailure(details)) => {
                             println!("{YELLOW} Producer {} partial failure: {}", self.id, details);
                         }
@@ -154,9 +201,10 @@ impl Producer {
                 active_monitors = still_monitoring; // Update our active monitors with the ones that are still pending
 
                 // Check the Global Ledger again to see if there are more pending cargo items to claim. If not, we can set active to false
+                // A backhaul a station claimed is still ours to worry about: if it fails, it comes back on the ledger for us to claim.
                 let ledger_is_empty = {
                     let ledger_access = self.ledger.lock().unwrap();
-                    ledger_access.pending_cargo.is_empty()
+                    ledger_access.pending_cargo.is_empty() && ledger_access.backhauls_in_flight == 0
                 };
 
                 if ledger_is_empty && active_monitors.is_empty() {
@@ -192,6 +240,146 @@ pub struct Cargo{
     pub item: String,
     pub actual_weight: u32,
     pub contraband: Option<String>,
+    pub category: CargoCategory, // Decides which kinds of car it can ride in
+    pub part: Option<CargoPart>, // Some when this is one piece of a consignment that was too heavy for a single car
+    pub shelf_life: Option<ShelfLife>, // Some for perishables. Everything else keeps forever.
+}
+
+
+pub const DEFAULT_AMBIENT_SPOIL_RATE: f64 = 3.0; // Out of the cold, perishables go off this many times as fast
+
+// How long a perishable load keeps, and how much of that is already gone. An hour in a reefer uses up an hour;
+// an hour anywhere else (an ordinary car, the warehouse) uses up `ambient_rate` hours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShelfLife {
+    pub hours: f64,        // How long it keeps chilled
+    pub ambient_rate: f64,
+    pub used: f64,         // Hours of shelf life used up so far
+    pub checked_at: f64,   // When `used` was last brought up to date
+}
+
+impl ShelfLife {
+    pub fn new(hours: f64, ambient_rate: f64) -> Self {
+        ShelfLife { hours, ambient_rate, used: 0.0, checked_at: sim_hours_now() }
+    }
+
+    /// Charges the time since the last check, at the chilled or ambient rate depending on where it's been.
+    pub fn age(&mut self, chilled: bool) {
+        let now = sim_hours_now();
+        let rate = if chilled { 1.0 } else { self.ambient_rate };
+        self.used += (now - self.checked_at).max(0.0) * rate;
+        self.checked_at = now;
+    }
+
+    pub fn is_spoiled(&self) -> bool {
+        self.used >= self.hours
+    }
+}
+
+
+// A consignment too heavy for any one car travels as several linked parts. Every part keeps the consignment's ID,
+// so the warehouse at the far end knows which pieces belong together (and when it has all of them).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CargoPart {
+    pub index: u32, // Counting from 1
+    pub of: u32,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CargoCategory {
+    #[default]
+    General,    // Crates, pallets, parcels. Anything that fits through a door.
+    Bulk,       // Coal, gravel, scrap
+    Liquid,     // Oil, milk, water for the engines
+    Chilled,    // Fish from Brendam, ice cream for the fête
+    Oversize,   // Girders, boilers, the odd boat
+    Passengers, // People, and their luggage
+}
+
+
+// The different kinds of car in the yard. Each has its own tare (what it weighs empty), how much it can carry,
+// which categories of cargo it's built for, and what the works charge to build one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CarClass {
+    #[default]
+    Boxcar,
+    Flatbed,
+    Tanker,
+    Reefer,
+    Coach,
+}
+
+impl CarClass {
+    pub const ALL: [CarClass; 5] = [CarClass::Boxcar, CarClass::Flatbed, CarClass::Tanker, CarClass::Reefer, CarClass::Coach];
+
+    pub fn tare(&self) -> u32 {
+        match self {
+            CarClass::Boxcar => 2000,
+            CarClass::Flatbed => 1800,
+            CarClass::Tanker => 2500,
+            CarClass::Reefer => 2800, // The cooling plant isn't light
+            CarClass::Coach => 3000,
+        }
+    }
+
+    pub fn max_payload(&self) -> u32 {
+        match self {
+            CarClass::Boxcar => 15000,
+            CarClass::Flatbed => 25000,
+            CarClass::Tanker => 20000,
+            CarClass::Reefer => 12000,
+            CarClass::Coach => 4000,
+        }
+    }
+
+    pub fn carries(&self, category: CargoCategory) -> bool {
+        match self {
+            CarClass::Boxcar => matches!(category, CargoCategory::General | CargoCategory::Bulk),
+            CarClass::Flatbed => matches!(category, CargoCategory::Oversize | CargoCategory::Bulk),
+            CarClass::Tanker => category == CargoCategory::Liquid,
+            CarClass::Reefer => matches!(category, CargoCategory::Chilled | CargoCategory::General),
+            CarClass::Coach => category == CargoCategory::Passengers,
+        }
+    }
+
+    pub fn build_cost(&self) -> u32 {
+        match self {
+            CarClass::Boxcar => 1000,
+            CarClass::Flatbed => 800,
+            CarClass::Tanker => 1500,
+            CarClass::Reefer => 2000,
+            CarClass::Coach => 2500,
+        }
+    }
+
+    /// Seats for passengers. Only coaches have any.
+    pub fn seats(&self) -> usize {
+        match self {
+            CarClass::Coach => 48,
+            _ => 0,
+        }
+    }
+
+    /// Can this much of this category ride in one car of this kind?
+    pub fn fits(&self, category: CargoCategory, weight: u32) -> bool {
+        self.carries(category) && weight <= self.max_payload()
+    }
+
+    /// The cheapest way to carry a load: one car of the cheapest class that takes it all, or failing that,
+    /// as few cars as possible of the roomiest class that carries it (the consignment gets split). None if nothing on the railway carries it.
+    pub fn for_load(category: CargoCategory, weight: u32) -> Option<(CarClass, u32)> {
+        let single = CarClass::ALL.into_iter().filter(|class| class.fits(category, weight)).min_by_key(|class| class.build_cost());
+        if let Some(class) = single {
+            return Some((class, 1));
+        }
+        CarClass::ALL.into_iter()
+            .filter(|class| class.carries(category))
+            .max_by_key(|class| class.max_payload())
+            .map(|class| (class, weight.div_ceil(class.max_payload())))
+    }
 }
 
 
@@ -211,6 +399,54 @@ impl Cargo {
 
         Ok(format!("Cargo '{}' is clear and safe.", self.item))
     }
+
+    /// Has it gone off, as of its last check? Cargo that keeps never has.
+    pub fn is_spoiled(&self) -> bool {
+        self.shelf_life.is_some_and(|shelf_life| shelf_life.is_spoiled())
+    }
+
+    /// Brings a perishable up to date (chilled if it's been in a reefer) and says whether it has gone off.
+    /// Cargo that keeps never has.
+    pub fn age(&mut self, chilled: bool) -> bool {
+        match &mut self.shelf_life {
+            Some(shelf_life) => {
+                shelf_life.age(chilled);
+                shelf_life.is_spoiled()
+            },
+            None => false,
+        }
+    }
+
+    /// Breaks the consignment into parts of these weights (they should add up to the whole). Any contraband rides in the first part.
+    pub fn split(mut self, weights: &[u32]) -> Vec<Cargo> {
+        let of = weights.len() as u32;
+        let mut contraband = self.contraband.take();
+        weights.iter().enumerate().map(|(i, &weight)| Cargo {
+            id: self.id,
+            item: self.item.clone(),
+            actual_weight: weight,
+            contraband: contraband.take(),
+            category: self.category,
+            part: Some(CargoPart { index: i as u32 + 1, of }),
+            shelf_life: self.shelf_life,
+        }).collect()
+    }
+
+    /// Puts the parts of a split consignment back together. Checking they're all there is the warehouse's job.
+    pub fn rejoin(parts: Vec<Cargo>) -> Option<Cargo> {
+        let mut parts = parts.into_iter();
+        let mut whole = parts.next()?;
+        for part in parts {
+            whole.actual_weight += part.actual_weight;
+            whole.contraband = whole.contraband.or(part.contraband);
+            // The whole consignment is only as fresh as its worst part.
+            if let (Some(whole_life), Some(part_life)) = (&mut whole.shelf_life, part.shelf_life) && part_life.used > whole_life.used {
+                *whole_life = part_life;
+            }
+        }
+        whole.part = None;
+        Some(whole)
+    }
 }
 
 
@@ -236,71 +472,178 @@ impl RejectedAsset {
 
 
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)] // This allows us to easily create copies of EngineType values, which is useful for passing them around without losing ownership.
-pub enum EngineType {
-    Diesel,
-    Thomas,
-    Percy,
-    Gordon,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FuelKind {
+    Coal,   // Steamies. Thomas, Percy, Gordon and friends.
+    Diesel, // And the diesels, who never let you forget it.
+}
+
+
+// Everything that makes a class of locomotive what it is. The built-in four live in builtin_engine_classes(),
+// and an engines.json file can retune them or add new classes without touching a line of code.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EngineClass {
+    pub name: String,
+    pub max_capacity: f64,        // kg it can haul
+    pub ideal_min_capacity: f64,  // Below this it's overkill (the "Goldilocks" lower bound)
+    pub max_fuel_capacity: f32,   // Let's assume these units are 'Liters' or 'Kilograms of Coal'
+    pub fuel_efficiency: f32,     // Higher is better
+    pub speed: u32,
+    pub fuel_kind: FuelKind,
+    pub maintenance_interval_km: f64, // How far it can run between visits to the shed
+    pub weight: f64,              // The engine's own mass in kg. It has to haul itself too, so it burns fuel just like the cars do.
+    pub tractive_effort: f64,     // How hard it can pull, in kN. Matters on the banks, not on the flat.
+}
+
+
+fn builtin_engine_classes() -> Vec<EngineClass> {
+    // name, capacity, ideal min, fuel, efficiency, speed, fuel kind, service interval, weight, tractive effort
+    let class = |name: &str, max_capacity, ideal_min_capacity, max_fuel_capacity, fuel_efficiency, speed, fuel_kind, maintenance_interval_km, weight, tractive_effort| EngineClass {
+        name: name.to_string(), max_capacity, ideal_min_capacity, max_fuel_capacity, fuel_efficiency, speed, fuel_kind, maintenance_interval_km, weight, tractive_effort,
+    };
+    vec![
+        class("Diesel", 20000.0, 5000.0, 3000.0, 0.50, 70*6, FuelKind::Diesel, 3000.0, 1200.0, 15.0), // Devious, but extremely efficient
+        class("Thomas", 15000.0, 0.0, 2000.0, 0.25, 60*6, FuelKind::Coal, 1500.0, 1000.0, 10.0),       // Classic, Jack of all trades
+        class("Percy", 5000.0, 0.0, 1000.0, 0.30, 40*6, FuelKind::Coal, 1200.0, 500.0, 4.0),          // Smart and efficient, but not the strongest
+        class("Gordon", 50000.0, 15000.0, 5000.0, 0.18, 80*6, FuelKind::Coal, 2000.0, 1500.0, 30.0),   // Powerful, but a gas guzzler
+    ]
+}
+
+// The one and only list of engine classes. Filled in once at startup (from engines.json, if there is one),
+// and read-only after that, so every thread can look classes up without a lock.
+static ENGINE_CLASSES: OnceLock<Vec<EngineClass>> = OnceLock::new();
+
+fn engine_classes() -> &'static [EngineClass] {
+    ENGINE_CLASSES.get_or_init(builtin_engine_classes)
+}
+
+/// Installs the engine classes from a config file. Classes with a built-in name replace the built-in, new names are added.
+/// Must be called before anyone looks up an engine, which in practice means first thing in main().
+pub fn install_engine_classes(classes: Vec<EngineClass>) -> Result<(), String> {
+    let mut registry = builtin_engine_classes();
+    for class in classes {
+        match registry.iter_mut().find(|existing| existing.name == class.name) {
+            Some(existing) => *existing = class,
+            None => registry.push(class),
+        }
+    }
+    ENGINE_CLASSES.set(registry).map_err(|_| "Engine classes were already in use before the config was loaded".to_string())
 }
 
 
+// An engine class is just an index into the registry. That keeps EngineType Copy and cheap to use as a HashMap key in the Roundhouse stalls.
+// The built-in classes always keep their slots, so they get constants.
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct EngineType(usize);
+
+
 impl EngineType {
+    pub const DIESEL: EngineType = EngineType(0);
+    pub const THOMAS: EngineType = EngineType(1);
+    pub const PERCY: EngineType = EngineType(2);
+    pub const GORDON: EngineType = EngineType(3);
+
+    pub fn named(name: &str) -> Option<EngineType> {
+        engine_classes().iter().position(|class| class.name.eq_ignore_ascii_case(name)).map(EngineType)
+    }
+
+    /// Every known class, weakest to strongest. This is the escalation order the Roundhouse works through.
+    pub fn roster() -> Vec<EngineType> {
+        let mut roster: Vec<EngineType> = (0..engine_classes().len()).map(EngineType).collect();
+        roster.sort_by(|a, b| a.max_capacity().partial_cmp(&b.max_capacity()).unwrap_or(Ordering::Equal));
+        roster
+    }
+
+    pub fn class(&self) -> &'static EngineClass {
+        &engine_classes()[self.0]
+    }
+
+    pub fn name(&self) -> &'static str {
+        &self.class().name
+    }
+
     pub fn max_capacity(&self) -> f64 {
-        match self {
-            EngineType::Percy => 5000.0,
-            EngineType::Thomas => 15000.0,
-            EngineType::Gordon => 50000.0,
-            EngineType::Diesel => 20000.0,
-        }
+        self.class().max_capacity
     }
 
     pub fn ideal_min_capacity(&self) -> f64 {
-        match self {
-            EngineType::Percy => 0000.0,
-            EngineType::Thomas => 0000.0,
-            EngineType::Gordon => 15000.0,
-            EngineType::Diesel => 5000.0,
-        }
+        self.class().ideal_min_capacity
     }
     
     pub fn max_fuel_capacity(&self) -> f32 {
-        // Let's assume these units are 'Liters' or 'Kilograms of Coal'
-        match self {
-            EngineType::Percy => 1000.0,
-            EngineType::Thomas => 2000.0,
-            EngineType::Diesel => 3000.0,
-            EngineType::Gordon => 5000.0,
-        }
+        self.class().max_fuel_capacity
     }
 
     pub fn fuel_efficiency(&self) -> f32 {
-        // Higher is better. 
-        // A Diesel might get 5.0 km/kg of fuel per ton.
-        // A Thomas (Steam) might only get 2.5 km/kg.
-        match self {
-            EngineType::Diesel => 0.50, // Devious, but extremely efficient
-            EngineType::Percy => 0.30, //  Smart and efficient, but not the strongest
-            EngineType::Thomas => 0.25, // Classic, Jack of all trades
-            EngineType::Gordon => 0.18, // Powerful, but a gas guzzler
-        }
+        self.class().fuel_efficiency
     }
 
     pub fn speed(&self) -> u32 {
-        match self {
-            EngineType::Percy => 40*6,
-            EngineType::Thomas => 60*6,
-            EngineType::Gordon => 80*6,
-            EngineType::Diesel => 70*6,
-        }
+        self.class().speed
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.class().weight
+    }
+
+    pub fn tractive_effort(&self) -> f64 {
+        self.class().tractive_effort
+    }
+}
+
+// Prints the class name, so the logs still say "Gordon" and not "EngineType(3)".
+impl std::fmt::Debug for EngineType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
     }
 }
 
+// Seed files name the class ("engine_type": "Gordon"), so serde goes through the registry too.
+impl Serialize for EngineType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for EngineType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        EngineType::named(&name).ok_or_else(|| serde::de::Error::custom(format!("unknown engine class '{}'", name)))
+    }
+}
+
+
+// Physics-lite. Not a wind tunnel, just enough for hills and heavy trains to matter.
+const GRAVITY: f64 = 9.81;
+const ROLLING_RESISTANCE: f64 = 0.002; // Steel wheels on steel rails: about 2 kg of drag per tonne.
+// Climbing 1 metre per km (a 0.1% grade) is half again as much work as the flat, compared to ROLLING_RESISTANCE.
+const GRADIENT_WORK_FACTOR: f64 = 0.5;
+// A train loaded right up to the engine's max_capacity runs at half its top speed.
+const FULL_LOAD_SLOWDOWN: f64 = 0.5;
+
+// Wear and tear. Even a freshly serviced engine can throw a coupling rod now and then, and the odds climb steeply once it's overdue.
+const BASE_BREAKDOWN_CHANCE: f64 = 0.02;       // Per hop
+const OVERDUE_BREAKDOWN_CHANCE: f64 = 0.2;     // Extra per hop, for every full service interval it's overdue
+const MAX_BREAKDOWN_CHANCE: f64 = 0.5;
+
+
+// The railway's clock. One second of wall clock is one hour on Sodor's timetable, which is also how long a
+// train's simulated journey (distance / speed) sleeps for. Starts the first time anyone asks.
+static SIM_CLOCK: OnceLock<Instant> = OnceLock::new();
+
+pub fn sim_hours_now() -> f64 {
+    SIM_CLOCK.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
 
 #[derive(Debug)]
 pub enum TrainError {
     ContrabandOnBoard(String),
     DuplicateId(u32),
+    NoRoom {
+        facility: String, // "yard", "roundhouse" or "warehouse"
+        capacity: usize,
+    },
     // ... our existing variants ...
     NoAvailableEngine,
     AssemblyFailed {
@@ -322,6 +665,26 @@ pub enum TrainError {
     },
     MissingCargo {
         cargo_id: Vec<u32>,
+    },
+    TrackNotFound {
+        origin: u32,
+        destination: u32,
+    },
+    NoCompatibleCar { // None of the empty cars can take this cargo: wrong kind of car, or not strong enough.
+        cargo_id: u32,
+        category: CargoCategory,
+        weight: u32,
+    },
+}
+
+// The ones people read most often get a proper sentence. Everything else prints as it's spelled.
+impl std::fmt::Display for TrainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrainError::TrackNotFound { origin, destination } => write!(f, "no track between Station {} and Station {}", origin, destination),
+            TrainError::NoCompatibleCar { cargo_id, category, weight } => write!(f, "no car on the railway can take cargo {} ({:?}, {}kg)", cargo_id, category, weight),
+            other => write!(f, "{:?}", other),
+        }
     }
 }
 
@@ -332,26 +695,70 @@ pub struct Engine {
     pub engine_type: EngineType,
     pub current_fuel: f32, // Replaces fuel_level
     //pub max_fuel: f32,
+    pub km_since_service: f64,    // The odometer, reset by the shed
+    pub hours_since_service: f64, // Time spent actually running, in timetable hours
+    pub home: Option<u32>,        // The depot this engine belongs to. Set by the first roundhouse it's housed in.
 }
 
 
 
 impl Engine {
+    pub fn new(id: u32, engine_type: EngineType, current_fuel: f32) -> Self {
+        Engine {
+            id,
+            engine_type,
+            current_fuel,
+            km_since_service: 0.0,
+            hours_since_service: 0.0,
+            home: None,
+        }
+    }
+
     /// THE SINGLE SOURCE OF TRUTH for fuel consumption math.
-    pub fn calculate_fuel_requirement(&self, weight: f64, distance: f64) -> f32 {
-        let work = weight as f32 * distance as f32;
+    /// `weight` is what the engine is hauling (cars and cargo); the engine's own mass is added here.
+    /// `gradient` is the climb in metres per km of track. Downhill is free, we don't model regenerative braking.
+    pub fn calculate_fuel_requirement(&self, weight: f64, distance: f64, gradient: f64) -> f32 {
+        let moving_mass = weight + self.engine_type.weight();
+        let hill = 1.0 + GRADIENT_WORK_FACTOR * gradient.max(0.0);
+        let work = (moving_mass * distance * hill) as f32;
         let quotient = self.engine_type.fuel_efficiency() * 5000.0;
         work / quotient
     }
 
+    /// The pull (kN) needed to keep `weight` plus the engine itself moving up `gradient` metres per km.
+    pub fn required_tractive_effort(&self, weight: f64, gradient: f64) -> f64 {
+        let moving_mass = weight + self.engine_type.weight();
+        moving_mass * GRAVITY * (ROLLING_RESISTANCE + gradient.max(0.0) / 1000.0) / 1000.0
+    }
+
+    /// Top speed, slowed down the closer the load gets to the engine's max_capacity.
+    pub fn speed_under_load(&self, weight: f64) -> f64 {
+        let load_ratio = (weight / self.engine_type.max_capacity()).clamp(0.0, 1.0);
+        self.engine_type.speed() as f64 * (1.0 - FULL_LOAD_SLOWDOWN * load_ratio)
+    }
+
+    /// In a consist, each engine hauls a share of the load in proportion to its strength.
+    pub fn load_share(&self, weight: f64, consist_capacity: f64) -> f64 {
+        if consist_capacity <= 0.0 {
+            return weight;
+        }
+        weight * self.engine_type.max_capacity() / consist_capacity
+    }
+
     pub fn is_ideal_for_mission(&self, weight: f64) -> bool {
         let capacity = self.engine_type.max_capacity();
         let ideal_min = self.engine_type.ideal_min_capacity();
         weight > ideal_min && weight <= capacity
     }
 
-    pub fn can_complete_mission(&self, weight: f64, distance: f64) -> bool {
-        let needed = self.calculate_fuel_requirement(weight, distance);
+    pub fn can_complete_mission(&self, weight: f64, distance: f64, gradient: f64) -> bool {
+        let pull = self.required_tractive_effort(weight, gradient);
+        if pull > self.engine_type.tractive_effort() {
+            println!("{RED}Mission Impossible: Engine {} would stall on the bank, needs {:.1}kN of pull, has {:.1}kN{RESET}", self.id, pull, self.engine_type.tractive_effort());
+            return false;
+        }
+
+        let needed = self.calculate_fuel_requirement(weight, distance, gradient);
         
         if needed > self.current_fuel {
             println!("{RED}Mission Impossible: Engine {} needs {:.1} fuel, has {:.1} fuel{RESET}", self.id, needed, self.current_fuel);
@@ -362,8 +769,8 @@ impl Engine {
         }
     }
 
-    pub fn burn_fuel(&mut self, weight: f64, distance: f64) -> Result<(), TrainError> {
-        let needed = self.calculate_fuel_requirement(weight, distance);
+    pub fn burn_fuel(&mut self, weight: f64, distance: f64, gradient: f64) -> Result<(), TrainError> {
+        let needed = self.calculate_fuel_requirement(weight, distance, gradient);
         if needed > self.current_fuel {
             Err(TrainError::MissionImpossible {
                 reason: format!("Engine {} needs {:.1} fuel, has {:.1} fuel", self.id, needed, self.current_fuel),
@@ -375,22 +782,73 @@ impl Engine {
         }
     }
 
+    pub fn log_run(&mut self, distance: f64, hours: f64) {
+        self.km_since_service += distance;
+        self.hours_since_service += hours;
+    }
+
+    // How far through its service interval the engine is. 1.0 means due, anything above is overdue.
+    pub fn wear(&self) -> f64 {
+        self.km_since_service / self.engine_type.class().maintenance_interval_km
+    }
+
+    pub fn service_due(&self) -> bool {
+        self.wear() >= 1.0
+    }
+
+    /// The chance this engine fails on its next hop.
+    pub fn breakdown_chance(&self) -> f64 {
+        let overdue = (self.wear() - 1.0).max(0.0);
+        (BASE_BREAKDOWN_CHANCE + OVERDUE_BREAKDOWN_CHANCE * overdue).min(MAX_BREAKDOWN_CHANCE)
+    }
+
+    /// Out of the shed: new boiler tubes, fresh grease, odometer back to zero.
+    pub fn service(&mut self) {
+        println!("{GREEN}🔧 Engine {} serviced after {:.0}km and {:.1}h on the road.{RESET}", self.id, self.km_since_service, self.hours_since_service);
+        self.km_since_service = 0.0;
+        self.hours_since_service = 0.0;
+    }
+
     pub fn refuel(&mut self) {
         let max = self.engine_type.max_fuel_capacity();
         if self.current_fuel < max {
             self.current_fuel = max;
-            println!("{GREEN}⛽ Engine {} refueled to max capacity ({:.1}).{RESET}", self.id, max);
+            println!("{GREEN}⛽ Engine {} took on {:?} to max capacity ({:.1}).{RESET}", self.id, self.engine_type.class().fuel_kind, max);
         }
     }
 }
 
 
+/// Can these engines, coupled together, haul `weight` over the hop? Together they must be strong enough and pull hard enough,
+/// and each one needs the fuel for its own share of the load.
+pub fn consist_can_complete_mission(engines: &[&Engine], weight: f64, distance: f64, gradient: f64) -> bool {
+    let consist_capacity: f64 = engines.iter().map(|engine| engine.engine_type.max_capacity()).sum();
+    if engines.is_empty() || consist_capacity < weight {
+        return false;
+    }
+
+    let pull: f64 = engines.iter().map(|engine| engine.required_tractive_effort(engine.load_share(weight, consist_capacity), gradient)).sum();
+    let available: f64 = engines.iter().map(|engine| engine.engine_type.tractive_effort()).sum();
+    if pull > available {
+        println!("{RED}Mission Impossible: Consist {:?} would stall on the bank, needs {:.1}kN of pull, has {:.1}kN{RESET}", engines.iter().map(|e| e.id).collect::<Vec<u32>>(), pull, available);
+        return false;
+    }
+
+    engines.iter().all(|engine| {
+        let needed = engine.calculate_fuel_requirement(engine.load_share(weight, consist_capacity), distance, gradient);
+        needed <= engine.current_fuel
+    })
+}
+
+
 
 #[derive(Debug)]
 pub struct TrainCar {
     pub id: u32,
     pub cargo: Option<Cargo>,
-    pub passenger: Option<String>,
+    pub passengers: Vec<Passenger>, // Only coaches have seats
+    pub class: CarClass,
+    pub destination: Option<u32>, // Where this car comes off, if it isn't going all the way with the train. The yard clears it.
 }
 
 
@@ -403,8 +861,8 @@ impl TrainCar {
     }
 
     pub fn gross_weight(&self) -> u32 {
-        let tare_weight = 2000; // If cars have different weights later, make this a struct field.
-        let net_weight = self.calculate_cargo_weight();
+        let tare_weight = self.class.tare();
+        let net_weight = self.calculate_cargo_weight() + self.passengers.len() as u32 * PASSENGER_WEIGHT;
         
         tare_weight + net_weight
     }
@@ -420,21 +878,65 @@ impl TrainCar {
 
 
 
+/// How a hop went, as far as the engines are concerned.
+#[derive(Debug, Clone, Copy)]
+pub struct Hop {
+    pub hours: f64,              // Time on the line: to the next stop, or to wherever it gave up
+    pub broke_down: Option<u32>, // The engine that failed part way, if one did
+}
+
+
 #[derive(Debug)]
 pub struct Train{
     pub id: u32,
     pub cars: Vec<TrainCar>,
-    pub engine: Engine, // Ownership! The Engine is PHYSICALLY in the Train now.
+    pub engines: Vec<Engine>, // Ownership! The Engines are PHYSICALLY in the Train now. Usually just one; a heavy train gets a second engine double-heading (or banking) to share the load.
     //pub distance_km: f64, // We can add more fields here as needed, like destination, mission details, etc.
     pub mission_id: Option<u32>, // This is optional because a train might be in the process of being assembled and not have a mission yet, or it might be between missions.
     // Now, for actor-based, decentralized travel across shortest route to destination
     //pub route_to_destination: Vec<String>, // A list of station names representing the planned route. This is based off the network's pathfinding algorithm. We will use this to know where to send the train next, and to report back to the mission with the path taken.
     pub destination: u32, // The final destination station name. This is used for reporting back to the mission and for the train's internal logic to know when it has arrived.
-    pub report_to: Option<Sender<MissionReport>>
+    pub report_to: Option<Sender<MissionReport>>,
+    pub service: bool, // A timetabled passenger train: it calls at every station on the way to let people off and on.
+    pub schedule: Option<ScheduledRun>, // A run of a timetabled freight service. None for trains run to order.
+    pub riders: Vec<Rider>, // Other people's freight aboard: everything on a timetabled run, or what a train run to order picked up on the way
+    pub highpriority: bool, // Carried over from the mission (passenger and timetabled trains always are). Decides who gives way in a deadlock.
+    pub priority: Priority, // The mission's terms for the train's own freight, so they survive a wreck. Riders carry their own.
+    pub deadline: Option<f64>,
+    pub block: Option<(u32, u32)>, // The block it last got the road for (from, to). Given back when it pulls in, or later if it's stood in a station with no passing loop.
 }
 
 impl Train {
-    
+
+    /// Sends the train, and anyone riding it, to `heir` instead of the closed station they were bound for.
+    pub fn readdress(&mut self, closed_station: u32, heir: u32) {
+        if self.destination == closed_station {
+            self.destination = heir;
+        }
+        for rider in self.riders.iter_mut().filter(|rider| rider.destination == closed_station) {
+            rider.destination = heir;
+        }
+    }
+
+    /// Breaks a wrecked or broken-down train up by whose freight it was carrying, so each consignment can be sent on again
+    /// (and its producer told) separately. One consignment per rider, and a train run to order has its own on top.
+    pub fn split_for_salvage(self) -> Vec<(Rider, Vec<TrainCar>)> {
+        let mut cars = self.cars;
+        let mut consignments = Vec::new();
+        for rider in self.riders {
+            let (theirs, rest): (Vec<TrainCar>, Vec<TrainCar>) = cars.into_iter()
+                .partition(|car| car.cargo.as_ref().is_some_and(|cargo| rider.cargo_ids.contains(&cargo.id)));
+            cars = rest;
+            consignments.push((rider, theirs));
+        }
+        if self.schedule.is_none() {
+            let cargo_ids = cars.iter().filter_map(|car| car.cargo.as_ref().map(|cargo| cargo.id)).collect();
+            let rider = Rider { mission_id: self.mission_id.unwrap_or(0), destination: self.destination, cargo_ids, report_to: self.report_to, priority: self.priority, deadline: self.deadline };
+            consignments.push((rider, cars));
+        }
+        consignments
+    }
+
     pub fn eject_car(&mut self, id: u32) -> Option<TrainCar> {
         if let Some(pos) = self.cars.iter().position(|c| c.id == id) {
             Some(self.cars.remove(pos))
@@ -445,18 +947,56 @@ impl Train {
     
 
     // Notice the &mut self. The train is 'taking damage' (burning fuel).
-    pub fn dispatch(&mut self, distance_to_next_stop: f64) -> Result<f64, TrainError> {
-        println!("Train {}::Engine {} is departing for ({}km)...", self.id, self.engine.id, distance_to_next_stop);
-        
-        // 1. Calculate the final weight
-        let total_weight = self.calculate_gross_weight(); // Convert to u32 for fuel calculation. In a real system, we would want to be careful about potential overflows here and might want to use a larger integer type or a different approach to weight management.
-        let speed = self.engine.engine_type.speed() as f64;
-        
-        // 2. The Consequence
-        self.engine.burn_fuel(total_weight, distance_to_next_stop)?;
+    /// A train is only as fast as its slowest engine, each pulling its share of the load. Infinite with no engines at all.
+    pub fn consist_speed(&self) -> f64 {
+        let trailing_weight = self.calculate_trailing_weight();
+        let consist_capacity = self.consist_capacity();
+        self.engines.iter()
+            .map(|engine| engine.speed_under_load(engine.load_share(trailing_weight, consist_capacity)))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn dispatch(&mut self, distance_to_next_stop: f64, gradient: f64) -> Result<Hop, TrainError> {
+        println!("Train {}::Engines {:?} departing for ({}km, climbing {:.1}m/km, {}kg all up)...", self.id, self.engine_ids(), distance_to_next_stop, gradient, self.calculate_gross_weight());
         
+        // 1. Calculate the final weight. Each engine adds its own mass inside the fuel math, so we only pass what it's hauling.
+        let trailing_weight = self.calculate_trailing_weight();
+        let consist_capacity = self.consist_capacity();
 
-        Ok(distance_to_next_stop / speed) // Return the estimated time to next stop based on speed
+        let speed = self.consist_speed();
+        if self.engines.is_empty() || speed <= 0.0 {
+            return Err(TrainError::NoAvailableEngine);
+        }
+
+        // 2. Every engine must be able to make the hop before any of them burns a drop. We don't want to strand half a consist.
+        if !consist_can_complete_mission(&self.engines.iter().collect::<Vec<&Engine>>(), trailing_weight, distance_to_next_stop, gradient) {
+            return Err(TrainError::MissionImpossible { reason: format!("Train {} cannot make the next {}km", self.id, distance_to_next_stop) });
+        }
+
+        // 3. Worn engines are more likely to give up on the way. If one does, it's somewhere out on the line, not at the next
+        // platform: the train only gets part of the way before it has to be towed back to where it came from.
+        let broke_down = self.engines.iter().find(|engine| rand::thread_rng().gen_bool(engine.breakdown_chance())).map(|engine| engine.id);
+        let part_way = if broke_down.is_some() { rand::thread_rng().gen_range(0.0..1.0) } else { 1.0 };
+
+        // 4. The Consequence. Only for the line actually covered.
+        let covered = distance_to_next_stop * part_way;
+        let hours = covered / speed;
+        for engine in self.engines.iter_mut() {
+            let share = engine.load_share(trailing_weight, consist_capacity);
+            engine.burn_fuel(share, covered, gradient)?;
+            engine.log_run(covered, hours);
+        }
+
+        Ok(Hop { hours, broke_down })
+    }
+
+    pub fn engine_ids(&self) -> Vec<u32> {
+        self.engines.iter().map(|engine| engine.id).collect()
+    }
+
+    // The combined haulage of every engine in the consist.
+    pub fn consist_capacity(&self) -> f64 {
+        self.engines.iter().map(|engine| engine.engine_type.max_capacity()).sum()
     }
 
 
@@ -471,14 +1011,20 @@ impl Train {
             .sum()
     }
 
+    // Everything behind the engine: the cars and whatever is in them.
+    pub fn calculate_trailing_weight(&self) -> f64 {
+        let consist_weight: u32 = self.cars.iter().map(|car| car.gross_weight()).sum();
+        consist_weight as f64
+    }
+
     pub fn calculate_gross_weight(&self) -> f64 {
         // Sum the gross weight of all attached cars
-        let consist_weight: u32 = self.cars.iter().map(|car| car.gross_weight()).sum();
+        let consist_weight = self.calculate_trailing_weight();
         
-        // If you want the Engine's mass to burn fuel too, you add it here.
-        // let engine_weight = 5000; 
+        // The Engines' mass burns fuel too.
+        let engine_weight: f64 = self.engines.iter().map(|engine| engine.engine_type.weight()).sum();
         
-        consist_weight as f64
+        consist_weight + engine_weight
     }
 
 }
@@ -498,7 +1044,11 @@ pub struct Mission {
     pub id: u32,
     pub request_id: u32,
     pub attempts: u32, // We can use this to track how many times we've tried to complete this mission, which can be useful for debugging and for implementing retry logic in the future.
-    pub highpriority: bool, // This can be used to allow the stations to prioritize certain missions over others. For example, a mission with perishable goods might be marked as high priority, while a mission with non-urgent cargo might be low priority. This is just a simple boolean for now, but we could expand this to an enum or a priority level system if we want more granularity in the future.
+    pub highpriority: bool, // High or Urgent. What the signal box goes by when two trains meet on a single line.
+    pub priority: Priority,
+    pub not_before: Option<f64>, // The customer can't take it before this timetable hour, so it doesn't set out in time to get there sooner
+    pub deadline: Option<f64>,
+    pub parked_since: Option<f64>, // When the station first parked it for want of an engine or cars. The longer it waits, the higher it climbs.
     pub origin: u32,
     pub destination: u32,
     pub cargo_ids: Vec<u32>,
@@ -506,11 +1056,33 @@ pub struct Mission {
     pub reply_channel: Option<Sender<MissionReport>>,
 }
 
+impl Mission {
+    /// A freight order turned into a mission. Producers do this when they claim an order, and so does a station that
+    /// claims one as a backhaul.
+    pub fn from_order(freight_order: &FreightOrder, reply_channel: Sender<MissionReport>) -> Self {
+        Mission {
+            id: freight_order.id, // We can use the freight order ID as the mission ID for simplicity, since each mission corresponds to a single freight order in this case. In a more complex system, we might want to have a separate ID generator for missions, but for this example, using the freight order ID works fine and keeps things straightforward.
+            request_id: (10 * freight_order.id) + freight_order.id, // Just an example of how we might generate a request ID based on the freight order ID. This is arbitrary and can be adjusted as needed.
+            attempts: 0, // Initialize attempts to 0
+            highpriority: freight_order.priority >= Priority::High,
+            priority: freight_order.priority,
+            not_before: freight_order.not_before,
+            deadline: freight_order.deadline,
+            parked_since: None,
+            origin: freight_order.origin,
+            destination: freight_order.destination,
+            cargo_ids: freight_order.cargo_ids.clone(), // Assuming each cargo requires one car with the same ID as the cargo for simplicity. In a real system, we would need more complex logic to determine which cars are needed for which cargo.
+            reply_channel: Some(reply_channel), // The producer's channel to receive updates about this mission
+        }
+    }
+}
+
 
 
 #[derive(Debug)]
 pub enum MissionReport {
     Success(String),
+    Late(String), // Delivered, all of it, but after the deadline
     PartialFailure(String),
     Failure(String),
 }
@@ -525,9 +1097,15 @@ pub enum StationCommand {
         train: Train,
         reply_to: Sender<Result<(), TrainError>>,
     },
+    HandleBreakdown { // An engine failed out on the line. The train was rescued back to the station it left from.
+        train: Train,
+        engine_id: u32,
+    },
     HandleEmergencySOS { 
         mission_id: u32, 
         destination: u32,
+        priority: Priority, // The consignment's own terms, so the re-order keeps them
+        deadline: Option<f64>,
         surviving_cars: Vec<TrainCar>, 
         report_to: Option<Sender<MissionReport>> 
     },
@@ -539,6 +1117,14 @@ pub enum StationCommand {
         cargo: Vec<Cargo>,
         reply_to: Sender<Result<(), TrainError>>,
     },
+    IntakeConsignment { // Cargo that already knows where it's going (from a seed file), rather than picking a random destination.
+        cargo: Cargo,
+        destination: u32,
+        priority: Priority,
+        not_before: Option<f64>,
+        deadline: Option<f64>,
+        reply_to: Sender<Result<(), TrainError>>,
+    },
     IntakeEngine {
         engine: Engine,
         reply_to: Sender<Result<(), TrainError>>,
@@ -547,7 +1133,51 @@ pub enum StationCommand {
         neighbor: u32,
         neighbor_tx: Sender<StationCommand>,
     },
-    RequestEmptyCars {
+    TrackClosed { // Engineering works: the track to this neighbor is out of service until further notice.
+        neighbor: u32,
+    },
+    TrackReopened { // The track to this neighbor is back in service. We get the neighbor's radio again so we can talk to it.
+        neighbor: u32,
+        neighbor_tx: Sender<StationCommand>,
+    },
+    TransferCargo { // A closing station hands over its warehouse. Unlike IntakeCargo, no new freight orders are raised: the old ones are re-homed in the ledger.
+        cargo: Vec<Cargo>,
+    },
+    TransferCars { // A closing station hands over its yard. Like IntakeCar, but the cars are squeezed in: they can't go back.
+        cars: Vec<TrainCar>,
+    },
+    WatchBackhauls { // A closing station hands over the backhauls it claimed, so somebody still hears how they went.
+        reports: Vec<(Receiver<MissionReport>, FreightOrder)>,
+    },
+    Decommission { // The Fat Controller is closing this station. Drain everything to the heirs (nearest first), then shut down.
+        heirs: Vec<(u32, Sender<StationCommand>)>,
+        reply_to: Sender<Result<(), TrainError>>,
+    },
+    EmptyCarRequest { // Gossip, like EngineRequest: the requester is short of empty cars. Donors send what they can spare and pass on the rest.
+        requester_id: u32,
+        request_id: u32,
+        car_class: CarClass,
+        count: u32, // How many cars are still wanted
+        ttl: u32,
+        branch_notified: [u32; 64],
+        notified_count: usize,
+    },
+    PassengersArrive { // People turn up on the platform, wanting to go somewhere.
+        passengers: Vec<Passenger>,
+    },
+    BlockCleared { // A train is out of the block between these stations. Trains held at the signal can try again.
+        from: u32,
+        to: u32,
+    },
+    ResolveDeadlock { // From the signal box: this train, held at our signal, is in a deadlock and has to give way.
+        train_id: u32,
+    },
+    RunService { // From the Scheduler: a timetabled service that starts here is due away.
+        service_id: String,
+        run_id: u32,
+    },
+    BuildCars { // Order new empty cars from the works. Paid for out of the station's car budget.
+        car_class: CarClass,
         count: u32,
     },
     EngineRequest { 
@@ -555,8 +1185,11 @@ pub enum StationCommand {
         request_id: u32, // unique ID for this specific request
         mission_id: Option<u32>, // The mission this engine request is for, if applicable. This allows us to track which mission the request belongs to and include that information in our reporting and decision-making. It's optional because we might have some engine requests that are not tied to a specific mission, such as a station requesting an engine for general use or for a future mission that has not been fully defined yet.
         min_capacity: f64,
+        paired_capacity: f64, // Capacity the requester already has on hand. Non-zero means "send me a second engine to double-head with", so donors only have to cover the shortfall.
         //TODO: The following mission_max_hop needs to be reconsidered: the engine will weigh less than it will once it has cargo, so the fuel requirement to get to the requesting station is not the same as the fuel requirement to complete the mission. We need to consider both legs of the journey in our engine suitability calculation, which is what
         mission_max_hop: f64, // NEW: The widest gap the engine will face BEFORE or AFTER it arrives to the requesting station. This allows the engine to consider not just whether it can get TO the requesting station, but if it can complete the requesting station's entire mission, which is the real question. An engine might be able to get to the station but then not have enough fuel to complete the next leg of the journey, so this gives us a more holistic view of whether the engine is truly suitable for the mission.
+        mission_max_gradient: f64, // The steepest climb (metres per km) on any hop the engine will face, for the same reason: a strong enough engine can still stall on the bank.
+        class: Option<EngineType>, // Some when a station is topping up its target mix: an engine of this class, not just any engine strong enough.
         ttl: u32,
 
         // THE FIX: A fixed-size array and a counter.
@@ -581,6 +1214,7 @@ pub enum StationCommand {
 pub struct Location {
     pub x: f64,
     pub y: f64,
+    pub elevation: f64, // Metres above sea level. Sodor is flat unless the map says otherwise.
 }
 
 
@@ -589,4 +1223,80 @@ impl Location {
     pub fn distance_to(&self, other: &Location) -> f64 {
         ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perishable(id: u32, weight: u32, used: f64) -> Cargo {
+        Cargo {
+            id,
+            item: String::from("Fish"),
+            actual_weight: weight,
+            contraband: None,
+            category: CargoCategory::Chilled,
+            part: None,
+            shelf_life: Some(ShelfLife { hours: 10.0, ambient_rate: 3.0, used, checked_at: sim_hours_now() }),
+        }
+    }
+
+    #[test]
+    fn shelf_life_ages_faster_out_of_the_cold() {
+        let two_hours_ago = sim_hours_now() - 2.0;
+        let mut chilled = ShelfLife { hours: 10.0, ambient_rate: 3.0, used: 0.0, checked_at: two_hours_ago };
+        let mut ambient = chilled;
+        chilled.age(true);
+        ambient.age(false);
+        assert!(chilled.used >= 2.0 && chilled.used < 2.5);
+        assert!(ambient.used >= 6.0 && ambient.used < 7.5);
+        assert!(!chilled.is_spoiled());
+
+        // Checked again straight away, nothing more is charged.
+        let used = ambient.used;
+        ambient.checked_at = sim_hours_now() + 1.0; // A clock that's ahead is never charged backwards either
+        ambient.age(false);
+        assert_eq!(ambient.used, used);
+    }
+
+    #[test]
+    fn rejoined_consignment_keeps_its_worst_shelf_life() {
+        let mut parts = perishable(1, 300, 0.0).split(&[100, 200]);
+        parts[1].shelf_life.as_mut().unwrap().used = 4.0;
+        let whole = Cargo::rejoin(parts).unwrap();
+        assert_eq!(whole.shelf_life.unwrap().used, 4.0);
+    }
+
+    #[test]
+    fn split_consignment_rejoins_whole() {
+        let mut cargo = perishable(1, 300, 0.0);
+        cargo.contraband = Some(String::from("Smuggled whisky"));
+        let parts = cargo.split(&[100, 200]);
+        assert_eq!(parts.iter().map(|part| part.part.unwrap().of).collect::<Vec<_>>(), vec![2, 2]);
+        assert!(parts[0].contraband.is_some() && parts[1].contraband.is_none()); // It rides in the first part
+
+        let whole = Cargo::rejoin(parts).unwrap();
+        assert_eq!(whole.actual_weight, 300);
+        assert_eq!(whole.part, None);
+        assert!(whole.contraband.is_some());
+        assert!(Cargo::rejoin(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn most_urgent_sorts_first() {
+        let mut work = vec![
+            (Priority::Normal, None),
+            (Priority::High, Some(20.0)),
+            (Priority::Normal, Some(5.0)),
+            (Priority::High, Some(10.0)),
+        ];
+        work.sort_by(|a, b| by_urgency(*a, *b));
+        assert_eq!(work, vec![
+            (Priority::High, Some(10.0)),
+            (Priority::High, Some(20.0)),
+            (Priority::Normal, Some(5.0)),
+            (Priority::Normal, None),
+        ]);
+        assert_eq!(Priority::High.raised(5), Priority::Urgent);
+    }
+}
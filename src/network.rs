@@ -1,5 +1,6 @@
-use std::collections::{HashMap, BinaryHeap};
+use std::collections::{HashMap, HashSet, BinaryHeap};
 use std::cmp::Ordering;
+use std::sync::{Arc, RwLock};
 
 // 1. The wrapper to hold a station and its cumulative distance in the queue
 #[derive(Clone, PartialEq)]
@@ -27,7 +28,11 @@ impl PartialOrd for RouteState {
 
 
 
-use crate::models::{FreightOrder, Location};
+use crate::classification::DwellRecord;
+use crate::models::{by_urgency, FreightOrder, Location};
+use crate::passengers::Journey;
+use crate::sla::DeliveryRecord;
+use crate::timetable::RunRecord;
 //use crate::facilities::Station;
 //use std::collections::HashMap;
 //use std::sync::mpsc::{};
@@ -39,13 +44,16 @@ const YELLOW: &str = "\x1b[33m";
 const CYAN: &str = "\x1b[36m";
 const BOLD: &str = "\x1b[1m";
 
-
-
-
 pub struct GlobalLedger {
     pub pending_cargo: Vec<FreightOrder>,
     //pub active_missions: Vec<Mission>,
     //pub next_mission_id: u32,
+    pub forwarding_addresses: HashMap<u32, u32>, // Decommissioned station -> the station that inherited its cargo.
+    pub journeys: Vec<Journey>, // Every passenger who got where they were going
+    pub service_runs: HashMap<u32, RunRecord>, // Every run of a timetabled service, by run ID
+    pub deliveries: HashMap<u32, DeliveryRecord>, // Every freight order's promise and how we did, by order (and mission) ID
+    pub hub_dwell: Vec<DwellRecord>, // Every car that went through a classification hub, and how long it sat there
+    pub backhauls_in_flight: usize, // Orders stations claimed for backhauls and haven't heard the last of. Producers wait for these too.
 }
 
 impl GlobalLedger {
@@ -54,7 +62,50 @@ impl GlobalLedger {
             pending_cargo: Vec::new(),
             //active_missions: Vec::new(),
             //next_mission_id: 1,
+            forwarding_addresses: HashMap::new(),
+            journeys: Vec::new(),
+            service_runs: HashMap::new(),
+            deliveries: HashMap::new(),
+            hub_dwell: Vec::new(),
+            backhauls_in_flight: 0,
+        }
+    }
+
+    /// Hands out the most urgent order: highest priority, then soonest deadline, then whoever asked first.
+    pub fn next_order(&mut self) -> Option<FreightOrder> {
+        let most_urgent = self.pending_cargo.iter().enumerate()
+            .min_by(|(_, a), (_, b)| by_urgency((a.priority, a.deadline), (b.priority, b.deadline))) // The first of equals wins
+            .map(|(i, _)| i)?;
+        Some(self.pending_cargo.remove(most_urgent))
+    }
+
+    /// Moves every pending order that departs from or is bound for `closed_station` over to `heir`, and remembers the
+    /// forwarding address so orders that come back from a Producer's retry loop later can follow it too.
+    pub fn rehome_orders(&mut self, closed_station: u32, heir: u32) -> usize {
+        let mut moved = 0;
+        for order in self.pending_cargo.iter_mut().filter(|order| order.origin == closed_station || order.destination == closed_station) {
+            if order.origin == closed_station {
+                order.origin = heir;
+            }
+            if order.destination == closed_station {
+                order.destination = heir;
+            }
+            moved += 1;
         }
+        self.forwarding_addresses.insert(closed_station, heir);
+        moved
+    }
+
+    /// Follows the chain of forwarding addresses (a station's heir may itself have closed since).
+    pub fn forwarding_address(&self, station: u32) -> Option<u32> {
+        let mut current = *self.forwarding_addresses.get(&station)?;
+        let mut hops = 0;
+        while let Some(next) = self.forwarding_addresses.get(&current) {
+            current = *next;
+            hops += 1;
+            if hops > self.forwarding_addresses.len() { break; } // A cycle would be a bug, but let's not spin forever on it.
+        }
+        Some(current)
     }
 }
 
@@ -65,6 +116,10 @@ impl GlobalLedger {
 pub type StationId = u32;
 pub type Distance = f64;
 
+// The map every station thread shares. Readers (pathfinding, distance lookups) take the read lock,
+// the Fat Controller takes the write lock when engineering works close or reopen a track.
+pub type SharedNetwork = Arc<RwLock<RailwayNetwork>>;
+
 
 pub struct RailwayNetwork {
     // Maps (Origin, Destination) -> Distance in km
@@ -72,7 +127,12 @@ pub struct RailwayNetwork {
     // We keep this purely for UI/Debugging translation, NOT for logic.
     //pub station_names: HashMap<StationId, String>,
     //missions: HashMap<u32, Mission>, // <-- The Source of Truth for all missions on the network
-    station_locations: HashMap<u32, Location>
+    station_locations: HashMap<u32, Location>,
+    // Tracks taken out of service (engineering works, washouts). Keyed by (lower id, higher id) so both directions share one entry.
+    // We keep the distance so reopening the track doesn't need to redo the geometry.
+    closed_tracks: HashMap<(StationId, StationId), Distance>,
+    // Stations with a maintenance shed, so a station without one knows where to send its due engines.
+    maintenance_sheds: HashSet<StationId>,
 }
 
 impl RailwayNetwork {
@@ -83,6 +143,8 @@ impl RailwayNetwork {
             //station_names: HashMap::new(),
             //missions: HashMap::new(),
             station_locations: HashMap::new(),
+            closed_tracks: HashMap::new(),
+            maintenance_sheds: HashSet::new(),
         }
     }
     
@@ -92,6 +154,46 @@ impl RailwayNetwork {
         self.station_locations.insert(id, location);
     }
 
+    pub fn set_maintenance_shed(&mut self, id: u32, has_shed: bool) {
+        if has_shed {
+            self.maintenance_sheds.insert(id);
+        } else {
+            self.maintenance_sheds.remove(&id);
+        }
+    }
+
+    /// The way to the nearest station with a maintenance shed (other than `origin` itself). None if there's no shed we can reach.
+    pub fn nearest_shed(&self, origin: StationId) -> Option<(Distance, Vec<StationId>)> {
+        self.maintenance_sheds.iter()
+            .filter(|shed| **shed != origin)
+            .filter_map(|shed| self.find_shortest_path(origin, *shed))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    pub fn station_ids(&self) -> Vec<StationId> {
+        self.station_locations.keys().copied().collect()
+    }
+
+    pub fn has_station(&self, id: u32) -> bool {
+        self.station_locations.contains_key(&id)
+    }
+
+    /// Removes a station and every track (open or closed) that touches it. Close its tracks with the
+    /// Fat Controller first if the neighbors need to be told.
+    pub fn deregister_station(&mut self, id: u32) {
+        self.station_locations.remove(&id);
+        self.maintenance_sheds.remove(&id);
+        if let Some(neighbors) = self.tracks.remove(&id) {
+            for (neighbor, _) in neighbors {
+                if let Some(v) = self.tracks.get_mut(&neighbor) {
+                    v.retain(|(dest, _)| *dest != id);
+                }
+            }
+        }
+        self.closed_tracks.retain(|(a, b), _| *a != id && *b != id);
+        println!("{YELLOW}Network: Station {} has been removed from the map.{RESET}", id);
+    }
+
 
     pub fn add_track(&mut self, a: u32, b: u32) {
         // 1. Look up the locations from our internal directory
@@ -101,6 +203,14 @@ impl RailwayNetwork {
         // 2. Do the math internally (No manual work for the user!)
         let distance = loc_a.distance_to(loc_b);
 
+        self.add_track_with_length(a, b, distance);
+    }
+
+    /// Lays a track whose length we already know (e.g. measured along a surveyed polyline), instead of the straight line between the stations.
+    pub fn add_track_with_length(&mut self, a: u32, b: u32, distance: Distance) {
+        assert!(self.station_locations.contains_key(&a), "Station A not found!");
+        assert!(self.station_locations.contains_key(&b), "Station B not found!");
+
         // 3. Insert both directions automatically
         // We use `entry().or_insert_with()` to either get the existing vector of tracks for that station or create a new one if it doesn't exist. Then we push the new track onto that vector. This way, we maintain a complete list of all tracks connected to each station.
         
@@ -115,8 +225,8 @@ impl RailwayNetwork {
         }
         
         println!("{CYAN}Network: Laying track between {} and {} ({:.2}km){RESET}", a, b, distance);
-        self.tracks.entry(a).or_insert_with(Vec::new).push((b, distance));
-        self.tracks.entry(b).or_insert_with(Vec::new).push((a, distance));
+        self.tracks.entry(a).or_default().push((b, distance));
+        self.tracks.entry(b).or_default().push((a, distance));
         println!("{CYAN}Network: Track laid between {} and {} ({:.2}km){RESET}", a, b, distance);
         
     }
@@ -139,6 +249,26 @@ impl RailwayNetwork {
         None
     }
 
+    /// How steeply the track climbs from `origin` to `destination`, in metres per km. Downhill (and flat) is 0.
+    pub fn get_gradient(&self, origin: StationId, destination: StationId) -> Option<f64> {
+        let distance = self.get_distance(origin, destination)?;
+        let rise = self.station_locations.get(&destination)?.elevation - self.station_locations.get(&origin)?.elevation;
+        if distance <= 0.0 {
+            return Some(0.0);
+        }
+        Some((rise / distance).max(0.0))
+    }
+
+    /// The worst a route can throw at an engine: its longest hop and its steepest climb, (max hop distance, max hop gradient).
+    /// The engine refuels at every station, so it only ever has to survive one hop at a time.
+    pub fn route_profile(&self, route: &[StationId]) -> (Distance, f64) {
+        route.windows(2).fold((0.0, 0.0), |(max_distance, max_gradient), pair| {
+            let distance = self.get_distance(pair[0], pair[1]).unwrap_or(0.0);
+            let gradient = self.get_gradient(pair[0], pair[1]).unwrap_or(0.0);
+            (max_distance.max(distance), max_gradient.max(gradient))
+        })
+    }
+
     // pub fn get_mission(&self, mission_id: &u32) -> Option<&Mission> {
     //     self.missions.get(mission_id)
     // }
@@ -147,6 +277,15 @@ impl RailwayNetwork {
 
     // Returns an Option containing a tuple: (Total Distance, Vector of Station Names in order)
     pub fn find_shortest_path(&self, origin: StationId, destination: StationId) -> Option<(Distance, Vec<StationId>)> {
+        self.find_path(origin, destination, None)
+    }
+
+    /// The shortest path that keeps off one track (either direction), e.g. a block that's jammed. None if there's no other way round.
+    pub fn find_shortest_path_avoiding(&self, origin: StationId, destination: StationId, avoid: (StationId, StationId)) -> Option<(Distance, Vec<StationId>)> {
+        self.find_path(origin, destination, Some(Self::track_key(avoid.0, avoid.1)))
+    }
+
+    fn find_path(&self, origin: StationId, destination: StationId, avoid: Option<(StationId, StationId)>) -> Option<(Distance, Vec<StationId>)> {
         
         // 1. The Scoreboard: Tracks the shortest known cumulative distance to each station
         let mut distances: HashMap<StationId, Distance> = HashMap::new();
@@ -204,6 +343,9 @@ impl RailwayNetwork {
             
             if let Some(v) = self.tracks.get(&station) {
                 for (track_dest, track_distance) in v {
+                    if avoid == Some(Self::track_key(station, *track_dest)) {
+                        continue; // Not this way
+                    }
                     // Calculate the cumulative distance to this neighbor
                     let next_cost = cost + track_distance;
                     let neighbor_best = *distances.get(track_dest).unwrap_or(&f64::INFINITY);
@@ -233,4 +375,41 @@ impl RailwayNetwork {
         self.tracks.get(station_id)
     }
 
+
+    // Normalise a pair of stations into the key used by closed_tracks, so (a, b) and (b, a) are the same track.
+    fn track_key(a: StationId, b: StationId) -> (StationId, StationId) {
+        if a < b { (a, b) } else { (b, a) }
+    }
+
+    /// Takes a track out of service in both directions. Returns the length of the closed track, or None if there was no open track to close.
+    /// Once closed, the track is invisible to get_distance, get_tracks and find_shortest_path, so every station routes around it on its next lookup.
+    pub fn close_track(&mut self, a: StationId, b: StationId) -> Option<Distance> {
+        let distance = self.get_distance(a, b)?;
+
+        if let Some(neighbors) = self.tracks.get_mut(&a) {
+            neighbors.retain(|(dest, _)| *dest != b);
+        }
+        if let Some(neighbors) = self.tracks.get_mut(&b) {
+            neighbors.retain(|(dest, _)| *dest != a);
+        }
+
+        self.closed_tracks.insert(Self::track_key(a, b), distance);
+        println!("{RED}Network: Track between {} and {} ({:.2}km) is CLOSED.{RESET}", a, b, distance);
+        Some(distance)
+    }
+
+    /// Puts a previously closed track back into service. Returns the track length, or None if the track was never closed.
+    pub fn reopen_track(&mut self, a: StationId, b: StationId) -> Option<Distance> {
+        let distance = self.closed_tracks.remove(&Self::track_key(a, b))?;
+
+        self.tracks.entry(a).or_default().push((b, distance));
+        self.tracks.entry(b).or_default().push((a, distance));
+        println!("{GREEN}Network: Track between {} and {} ({:.2}km) is OPEN again.{RESET}", a, b, distance);
+        Some(distance)
+    }
+
+    pub fn is_track_closed(&self, a: StationId, b: StationId) -> bool {
+        self.closed_tracks.contains_key(&Self::track_key(a, b))
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::sim_hours_now;
+
+// People, not freight. Passengers turn up on a station's platform wanting to go somewhere, and wait there for a
+// passenger train that calls at their destination. Passenger trains run to a timetable set in the network config
+// ("passenger_services"), stop at every station on the way to let people off and on, and hand the coaches in at the terminus.
+// Every finished journey goes in the ledger, so we can see at the end of the run how long everyone waited and rode.
+
+const RESET: &str = "\x1b[0m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+
+pub const PASSENGER_WEIGHT: u32 = 80; // kg, luggage included. The engine has to pull them too.
+
+static GLOBAL_PASSENGER_ID: AtomicU32 = AtomicU32::new(0);
+
+
+#[derive(Debug)]
+pub struct Passenger {
+    pub id: u32,
+    pub name: String,
+    pub origin: u32,
+    pub destination: u32,
+    pub arrived_at: f64,         // Timetable hour they turned up on the platform
+    pub boarded_at: Option<f64>, // Timetable hour they got on a train. None while they're still waiting.
+}
+
+impl Passenger {
+    pub fn new(name: &str, origin: u32, destination: u32) -> Self {
+        Passenger {
+            id: GLOBAL_PASSENGER_ID.fetch_add(1, Ordering::SeqCst),
+            name: name.to_string(),
+            origin,
+            destination,
+            arrived_at: sim_hours_now(),
+            boarded_at: None,
+        }
+    }
+
+    /// Everyone who has turned up on a platform so far, whether or not they got where they were going.
+    pub fn total_booked() -> u32 {
+        GLOBAL_PASSENGER_ID.load(Ordering::SeqCst)
+    }
+
+    /// They've stepped off at their destination. Wraps their trip up for the ledger.
+    pub fn finish_journey(self, now: f64) -> Journey {
+        let boarded_at = self.boarded_at.unwrap_or(now);
+        Journey {
+            passenger_id: self.id,
+            name: self.name,
+            origin: self.origin,
+            destination: self.destination,
+            waited_hours: boarded_at - self.arrived_at,
+            travel_hours: now - boarded_at,
+        }
+    }
+}
+
+
+/// One finished trip, platform to platform.
+#[derive(Debug)]
+pub struct Journey {
+    pub passenger_id: u32,
+    pub name: String,
+    pub origin: u32,
+    pub destination: u32,
+    pub waited_hours: f64, // On the first platform, before they got on anything
+    pub travel_hours: f64, // From first boarding to stepping off, changes included
+}
+
+impl Journey {
+    pub fn total_hours(&self) -> f64 {
+        self.waited_hours + self.travel_hours
+    }
+}
+
+
+/// A passenger train that leaves this station every `every_hours` for `terminus`, calling at every station on the way.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PassengerService {
+    pub terminus: u32,
+    pub every_hours: f64,
+    #[serde(default = "default_coaches")]
+    pub coaches: u32,
+}
+
+fn default_coaches() -> u32 {
+    2
+}
+
+
+/// The end-of-run summary: every finished journey, and how many passengers never made it.
+pub fn print_passenger_report(journeys: &[Journey]) {
+    let booked = Passenger::total_booked();
+    if booked == 0 {
+        return;
+    }
+    println!("\n{BOLD}{CYAN}--- Passenger Report: {} of {} passenger(s) reached their destination ---{RESET}", journeys.len(), booked);
+    for journey in journeys {
+        println!("  {} (#{}): Station {} -> Station {} | waited {:.1}h, travelled {:.1}h, {:.1}h door to door",
+            journey.name, journey.passenger_id, journey.origin, journey.destination, journey.waited_hours, journey.travel_hours, journey.total_hours());
+    }
+    if !journeys.is_empty() {
+        let count = journeys.len() as f64;
+        println!("  {BOLD}Average: waited {:.1}h, travelled {:.1}h.{RESET}",
+            journeys.iter().map(|j| j.waited_hours).sum::<f64>() / count, journeys.iter().map(|j| j.travel_hours).sum::<f64>() / count);
+    }
+    let stranded = booked as usize - journeys.len();
+    if stranded > 0 {
+        println!("  {YELLOW}{} passenger(s) were still on a platform or a train when the run ended.{RESET}", stranded);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+use crate::facilities::Roundhouse;
+use crate::models::{Engine, EngineType};
+
+// How a roundhouse picks which engine to send out when more than one could do the job.
+// The roundhouse does the physics (who can haul this far, up this bank, with this partner); the policy only chooses among the survivors.
+// Each station picks its own policy in the network config, e.g. "engine_policy": "most_rested".
+// The same goes for which engines a station keeps once the work is done ("rebalance").
+
+
+/// Everything a policy needs to know about the job.
+pub struct EngineQuery {
+    pub total_weight: f64,
+    pub paired_capacity: f64, // Strength of the engine it will be coupled to, 0 if it runs alone
+    pub distance_km: f64,
+    pub gradient: f64,
+    pub lending: bool,        // True when the engine is leaving to help another station
+}
+
+impl EngineQuery {
+    /// The part of the load an engine of this class pulls once it's coupled to its partner.
+    pub fn share_for(&self, etype: EngineType) -> f64 {
+        self.total_weight * etype.max_capacity() / (etype.max_capacity() + self.paired_capacity)
+    }
+}
+
+
+pub trait EngineSelectionPolicy: Send {
+    fn name(&self) -> &'static str;
+
+    /// Picks one of the capable engines and returns its ID. `candidates` come weakest class first,
+    /// and longest-waiting first within a class. None means "none of these, thanks".
+    fn choose(&self, candidates: &[&Engine], roundhouse: &Roundhouse, query: &EngineQuery) -> Option<u32>;
+}
+
+
+/// The original behaviour: the weakest engine that can manage. Saves the big engines for the big jobs.
+pub struct WeakestCapable;
+
+impl EngineSelectionPolicy for WeakestCapable {
+    fn name(&self) -> &'static str { "weakest-capable" }
+
+    fn choose(&self, candidates: &[&Engine], _roundhouse: &Roundhouse, _query: &EngineQuery) -> Option<u32> {
+        candidates.first().map(|engine| engine.id)
+    }
+}
+
+
+/// Not too weak, not too strong: prefer an engine whose ideal load range covers the job. Falls back to weakest-capable.
+pub struct GoldilocksIdeal;
+
+impl EngineSelectionPolicy for GoldilocksIdeal {
+    fn name(&self) -> &'static str { "goldilocks" }
+
+    fn choose(&self, candidates: &[&Engine], _roundhouse: &Roundhouse, query: &EngineQuery) -> Option<u32> {
+        candidates.iter()
+            .find(|engine| engine.is_ideal_for_mission(query.share_for(engine.engine_type)))
+            .or(candidates.first())
+            .map(|engine| engine.id)
+    }
+}
+
+
+/// Whoever burns the least fuel on this job, heavy engines and all.
+pub struct FuelMinimising;
+
+impl EngineSelectionPolicy for FuelMinimising {
+    fn name(&self) -> &'static str { "fuel-minimising" }
+
+    fn choose(&self, candidates: &[&Engine], _roundhouse: &Roundhouse, query: &EngineQuery) -> Option<u32> {
+        candidates.iter()
+            .min_by(|a, b| {
+                let fuel_a = a.calculate_fuel_requirement(query.share_for(a.engine_type), query.distance_km, query.gradient);
+                let fuel_b = b.calculate_fuel_requirement(query.share_for(b.engine_type), query.distance_km, query.gradient);
+                fuel_a.partial_cmp(&fuel_b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|engine| engine.id)
+    }
+}
+
+
+/// First in, first out across every class: the engine that has been sitting in the roundhouse longest goes next.
+pub struct MostRested;
+
+impl EngineSelectionPolicy for MostRested {
+    fn name(&self) -> &'static str { "most-rested" }
+
+    fn choose(&self, candidates: &[&Engine], roundhouse: &Roundhouse, _query: &EngineQuery) -> Option<u32> {
+        candidates.iter()
+            .min_by(|a, b| roundhouse.housed_since(a.id).partial_cmp(&roundhouse.housed_since(b.id)).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|engine| engine.id)
+    }
+}
+
+
+/// Weakest-capable for our own trains, but we never lend out the last engine of a class. Somebody has to stay home.
+pub struct ReservePreserving;
+
+impl EngineSelectionPolicy for ReservePreserving {
+    fn name(&self) -> &'static str { "reserve-preserving" }
+
+    fn choose(&self, candidates: &[&Engine], roundhouse: &Roundhouse, query: &EngineQuery) -> Option<u32> {
+        candidates.iter()
+            .find(|engine| !query.lending || roundhouse.stalls.get(&engine.engine_type).is_some_and(|queue| queue.len() > 1))
+            .map(|engine| engine.id)
+    }
+}
+
+
+/// The names the network config uses for each policy.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EnginePolicyKind {
+    #[default]
+    WeakestCapable,
+    Goldilocks,
+    FuelMinimising,
+    MostRested,
+    ReservePreserving,
+}
+
+impl EnginePolicyKind {
+    pub fn build(self) -> Box<dyn EngineSelectionPolicy> {
+        match self {
+            EnginePolicyKind::WeakestCapable => Box::new(WeakestCapable),
+            EnginePolicyKind::Goldilocks => Box::new(GoldilocksIdeal),
+            EnginePolicyKind::FuelMinimising => Box::new(FuelMinimising),
+            EnginePolicyKind::MostRested => Box::new(MostRested),
+            EnginePolicyKind::ReservePreserving => Box::new(ReservePreserving),
+        }
+    }
+}
+
+
+/// What a station does with engines that belong to somebody else, once it has no missions waiting.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RebalancePolicy {
+    /// Keep whatever turns up. Engines drift to wherever the trains end.
+    Off,
+    /// Send every foreign engine home.
+    #[default]
+    ReturnHome,
+    /// Keep up to the station's target count of each class, and send the foreign surplus home.
+    TargetMix,
+}
@@ -0,0 +1,285 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{sim_hours_now, StationCommand};
+
+// Block signalling. Every track between two stations is one block, and a train may only enter it once the signal box
+// has given it the road. The train holds its reservation for the whole hop and gives it back when it pulls in at the
+// far end (or is wrecked, or towed back). Until then, anyone else wanting that block waits at the station.
+//
+// One signal box for the whole railway, shared by every station. It doesn't know about routes, only blocks.
+//
+// A station without a passing loop has nowhere to put a train that's only passing through, so such a train stays
+// in the block it came in on until it gets the road for the next one. On single track that's how you get deadlock:
+// two trains nose to nose, each sitting in the block the other one wants. So the signal box also keeps the wait-for
+// graph (who is waiting on whom), looks for cycles every time a train is held, and picks a train to give way.
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+
+pub const DEFAULT_BLOCK_CAPACITY: u32 = 1; // One train in the block at a time (per direction, on double track). The way it's always been done.
+
+pub type SharedSignalBox = Arc<Mutex<SignalBox>>;
+
+
+/// How a track is worked.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackWorking {
+    Single,     // One line for both directions: `capacity` trains on it in total
+    #[default]
+    Double,     // A line each way: `capacity` trains in each direction
+}
+
+impl TrackWorking {
+    pub fn is_double(&self) -> bool {
+        *self == TrackWorking::Double
+    }
+}
+
+
+/// What to do with a deadlock. Either way, the train that gives way is the lowest-priority one in the cycle
+/// (the one that joined the queue last, if they're all the same).
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadlockPolicy {
+    #[default]
+    BackOff,    // It gives up the block it's sitting in and sets back to the station it came from
+    Reroute,    // It finds another way round that keeps off the block it wants. If there isn't one, it backs off.
+}
+
+
+/// A train held at the signal, as the signal box sees it: what it wants, what it's sitting on, and who to tell if it has to give way.
+#[derive(Debug, Clone)]
+pub struct HeldTrain {
+    pub station: u32,
+    pub wants: (u32, u32),          // The block it's waiting for, from the station it's at to its next stop
+    pub holds: Option<(u32, u32)>,  // The block it's still standing in, at a station with no passing loop
+    pub behind: Option<u32>,        // The train ahead of it in the queue for the same block, which has to go first
+    pub highpriority: bool,
+    pub since: f64,
+    pub station_tx: Sender<StationCommand>,
+}
+
+/// One deadlock found in the wait-for graph, and what was done about it.
+#[derive(Debug, Clone)]
+pub struct Deadlock {
+    pub detected_at: f64,
+    pub cycle: Vec<u32>,                // The trains in it, each waiting on the next (and the last on the first)
+    pub victim: u32,
+    pub station: u32,                   // Where the victim is standing
+    pub resolution: Option<String>,     // Filled in by the victim's station once it has given way
+}
+
+
+/// A train that had to wait at the signal before it could leave.
+#[derive(Debug, Clone)]
+pub struct BlockWait {
+    pub train_id: u32,
+    pub station: u32,
+    pub next_stop: u32,
+    pub hours: f64,
+}
+
+
+pub struct SignalBox {
+    tracks: HashMap<(u32, u32), (TrackWorking, u32)>, // Block -> how it's worked and how many trains it takes. Missing means double track, default capacity.
+    occupied: HashMap<(u32, u32), Vec<(u32, u32)>>,   // Block -> (train ID, the station it entered from) for every train in it
+    no_passing_loop: HashSet<u32>,                     // Stations where a through train can't get off the main line
+    held: HashMap<u32, HeldTrain>,                     // Train ID -> what it's waiting for. The nodes of the wait-for graph.
+    resolving: HashSet<u32>,                           // Victims told to give way that haven't done it yet, so we don't pick on them twice
+    pub policy: DeadlockPolicy,
+    pub waits: Vec<BlockWait>,
+    pub deadlocks: Vec<Deadlock>,
+}
+
+impl SignalBox {
+    pub fn new(policy: DeadlockPolicy) -> Self {
+        SignalBox {
+            tracks: HashMap::new(),
+            occupied: HashMap::new(),
+            no_passing_loop: HashSet::new(),
+            held: HashMap::new(),
+            resolving: HashSet::new(),
+            policy,
+            waits: Vec::new(),
+            deadlocks: Vec::new(),
+        }
+    }
+
+    // Blocks don't have a direction, so we always file them under the lower station ID first.
+    fn block(a: u32, b: u32) -> (u32, u32) {
+        (a.min(b), a.max(b))
+    }
+
+    pub fn set_working(&mut self, a: u32, b: u32, working: TrackWorking, capacity: u32) {
+        self.tracks.insert(Self::block(a, b), (working, capacity.max(1)));
+    }
+
+    pub fn working(&self, a: u32, b: u32) -> (TrackWorking, u32) {
+        self.tracks.get(&Self::block(a, b)).copied().unwrap_or((TrackWorking::Double, DEFAULT_BLOCK_CAPACITY))
+    }
+
+    pub fn set_passing_loop(&mut self, station: u32, passing_loop: bool) {
+        if passing_loop {
+            self.no_passing_loop.remove(&station);
+        } else {
+            self.no_passing_loop.insert(station);
+        }
+    }
+
+    pub fn has_passing_loop(&self, station: u32) -> bool {
+        !self.no_passing_loop.contains(&station)
+    }
+
+    /// Asks for the road from `from` to `to`. True if the train may go (and now holds the block), false if it has to wait.
+    /// A train already standing in the block (turning back the way it came) doesn't count against itself.
+    pub fn try_reserve(&mut self, from: u32, to: u32, train_id: u32) -> bool {
+        let (working, capacity) = self.working(from, to);
+        let occupants = self.occupied.entry(Self::block(from, to)).or_default();
+        let others = occupants.iter().filter(|(id, _)| *id != train_id);
+        let in_use = match working {
+            TrackWorking::Single => others.count(),
+            TrackWorking::Double => others.filter(|(_, entered_from)| *entered_from == from).count(),
+        };
+        if in_use >= capacity as usize {
+            return false;
+        }
+        occupants.retain(|(id, _)| *id != train_id);
+        occupants.push((train_id, from));
+        true
+    }
+
+    /// The train is out of the block.
+    pub fn release(&mut self, from: u32, to: u32, train_id: u32) {
+        if let Some(occupants) = self.occupied.get_mut(&Self::block(from, to)) {
+            occupants.retain(|(id, _)| *id != train_id);
+        }
+    }
+
+    /// A train is (still) held at the signal. Called again on every retry, which keeps what it holds up to date.
+    pub fn hold(&mut self, train_id: u32, held: HeldTrain) {
+        self.held.insert(train_id, held);
+    }
+
+    /// The train got the road, gave way, or left the station some other way. It's out of the wait-for graph.
+    pub fn stop_holding(&mut self, train_id: u32) {
+        self.held.remove(&train_id);
+        self.resolving.remove(&train_id);
+    }
+
+    // Who is `train_id` waiting on? Whichever held trains are in the block it wants (counting the same way try_reserve does),
+    // and the train ahead of it in the queue. A train that's moving isn't an edge: it will be out of the block by itself.
+    fn waiting_on(&self, train_id: u32) -> Vec<u32> {
+        let Some(held) = self.held.get(&train_id) else {
+            return Vec::new();
+        };
+        let (from, to) = held.wants;
+        let (working, _) = self.working(from, to);
+        self.occupied.get(&Self::block(from, to)).into_iter().flatten()
+            .filter(|(id, entered_from)| *id != train_id && (working == TrackWorking::Single || *entered_from == from))
+            .map(|(id, _)| *id)
+            .chain(held.behind)
+            .filter(|id| self.held.contains_key(id))
+            .collect()
+    }
+
+    // Depth-first search along the wait-for edges. Returns the cycle through `train_id`'s path, if there is one.
+    fn find_cycle(&self, train_id: u32, path: &mut Vec<u32>, done: &mut HashSet<u32>) -> Option<Vec<u32>> {
+        if let Some(start) = path.iter().position(|id| *id == train_id) {
+            return Some(path[start..].to_vec());
+        }
+        if !done.insert(train_id) {
+            return None; // Been here by another way and found nothing
+        }
+        path.push(train_id);
+        for next in self.waiting_on(train_id) {
+            if let Some(cycle) = self.find_cycle(next, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    /// Looks for a cycle in the wait-for graph. If there is one, picks the train that gives way, logs it, and returns
+    /// it along with the mailbox of the station it's standing at. Trains already told to give way are left out.
+    pub fn detect_deadlock(&mut self) -> Option<(Deadlock, Sender<StationCommand>)> {
+        let mut done = HashSet::new();
+        let mut starts: Vec<u32> = self.held.keys().copied().filter(|id| !self.resolving.contains(id)).collect();
+        starts.sort(); // Same answer whatever order the HashMap feels like today
+        let cycle = starts.into_iter().find_map(|id| self.find_cycle(id, &mut Vec::new(), &mut done))?;
+        if cycle.iter().any(|id| self.resolving.contains(id)) {
+            return None; // Already being sorted out
+        }
+
+        // Low priority gives way first, then a train that's standing in a block (so it has somewhere to set back to), then whoever joined the queue last.
+        let victim = *cycle.iter().min_by(|a, b| {
+            let (a, b) = (&self.held[*a], &self.held[*b]);
+            a.highpriority.cmp(&b.highpriority)
+                .then(b.holds.is_some().cmp(&a.holds.is_some()))
+                .then(b.since.partial_cmp(&a.since).unwrap_or(std::cmp::Ordering::Equal))
+        })?;
+        let held = &self.held[&victim];
+        let deadlock = Deadlock { detected_at: sim_hours_now(), cycle, victim, station: held.station, resolution: None };
+        let station_tx = held.station_tx.clone();
+        println!("{BOLD}{RED}Signal box: DEADLOCK #{} between Trains {:?}. Train {} at Station {} gives way.{RESET}", self.deadlocks.len() + 1, deadlock.cycle, victim, deadlock.station);
+        self.resolving.insert(victim);
+        self.deadlocks.push(deadlock.clone());
+        Some((deadlock, station_tx))
+    }
+
+    /// The victim's station reports what it did.
+    pub fn resolved(&mut self, victim: u32, resolution: String) {
+        if let Some(deadlock) = self.deadlocks.iter_mut().rev().find(|deadlock| deadlock.victim == victim && deadlock.resolution.is_none()) {
+            deadlock.resolution = Some(resolution);
+        }
+    }
+}
+
+
+/// The end-of-run summary: how often trains were held at the signal, for how long, where it was worst, and any deadlocks.
+pub fn print_signalling_report(signal_box: &SignalBox) {
+    let waits = &signal_box.waits;
+    println!("\n{BOLD}{CYAN}--- Signalling: {} train(s) held at the signal ---{RESET}", waits.len());
+    print_deadlocks(&signal_box.deadlocks, signal_box.policy);
+    if waits.is_empty() {
+        return;
+    }
+    let total: f64 = waits.iter().map(|wait| wait.hours).sum();
+    let longest = waits.iter().max_by(|a, b| a.hours.partial_cmp(&b.hours).unwrap_or(std::cmp::Ordering::Equal));
+    println!("  {BOLD}{:.1}h lost in total, {:.1}h on average.{RESET}", total, total / waits.len() as f64);
+    if let Some(wait) = longest {
+        println!("  {YELLOW}Longest: Train {} waited {:.1}h at Station {} for the block to Station {}.{RESET}", wait.train_id, wait.hours, wait.station, wait.next_stop);
+    }
+
+    let mut by_block: HashMap<(u32, u32), (usize, f64)> = HashMap::new();
+    for wait in waits {
+        let entry = by_block.entry((wait.station, wait.next_stop)).or_default();
+        entry.0 += 1;
+        entry.1 += wait.hours;
+    }
+    let mut by_block: Vec<((u32, u32), (usize, f64))> = by_block.into_iter().collect();
+    by_block.sort_by(|a, b| b.1.1.partial_cmp(&a.1.1).unwrap_or(std::cmp::Ordering::Equal));
+    for ((station, next_stop), (count, hours)) in by_block {
+        println!("  Station {} -> Station {}: {} wait(s), {:.1}h", station, next_stop, count, hours);
+    }
+}
+
+fn print_deadlocks(deadlocks: &[Deadlock], policy: DeadlockPolicy) {
+    if deadlocks.is_empty() {
+        println!("  {BOLD}No deadlocks.{RESET}");
+        return;
+    }
+    let unresolved = deadlocks.iter().filter(|deadlock| deadlock.resolution.is_none()).count();
+    println!("  {BOLD}{RED}{} deadlock(s) detected (policy: {:?}), {} still unresolved at the end.{RESET}", deadlocks.len(), policy, unresolved);
+    for (i, deadlock) in deadlocks.iter().enumerate() {
+        println!("  #{} at {:.1}h: Trains {:?}. Train {} at Station {} {}.", i + 1, deadlock.detected_at, deadlock.cycle, deadlock.victim, deadlock.station, deadlock.resolution.as_deref().unwrap_or("was still to give way"));
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::FreightOrder;
+
+// What we promised the customer, and whether we kept to it. Some orders come with a delivery window: not before a
+// given hour (they've nowhere to put it yet), and no later than another (the deadline). The ledger keeps one record
+// per order from the moment a Producer turns it into a mission. The origin writes down when it expects the train in as
+// it leaves, and the destination writes down when it actually got there. A salvaged or re-ordered consignment keeps
+// its first record, so a wreck doesn't reset the clock.
+//
+// At the end of the run, the SLA report adds it all up for each origin and destination.
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+
+
+/// The ledger's record of one freight order's delivery.
+#[derive(Debug, Clone)]
+pub struct DeliveryRecord {
+    pub origin: u32,
+    pub destination: u32,
+    pub not_before: Option<f64>,   // The window opens
+    pub deadline: Option<f64>,     // The window closes
+    pub planned_at: Option<f64>,   // When we expected it in, worked out as it left the origin
+    pub delivered_at: Option<f64>, // When it got there. None if it never did.
+}
+
+/// How a delivery measured up against its window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Punctuality {
+    Early(f64), // Hours before the window opened
+    InWindow,
+    Late(f64),  // Hours after the deadline
+}
+
+impl DeliveryRecord {
+    pub fn new(order: &FreightOrder) -> Self {
+        DeliveryRecord {
+            origin: order.origin,
+            destination: order.destination,
+            not_before: order.not_before,
+            deadline: order.deadline,
+            planned_at: None,
+            delivered_at: None,
+        }
+    }
+
+    pub fn has_window(&self) -> bool {
+        self.not_before.is_some() || self.deadline.is_some()
+    }
+
+    /// None until it's delivered. An order with no window is always in it.
+    pub fn punctuality(&self) -> Option<Punctuality> {
+        let delivered = self.delivered_at?;
+        if let Some(deadline) = self.deadline && delivered > deadline {
+            return Some(Punctuality::Late(delivered - deadline));
+        }
+        if let Some(opens) = self.not_before && delivered < opens {
+            return Some(Punctuality::Early(opens - delivered));
+        }
+        Some(Punctuality::InWindow)
+    }
+}
+
+
+/// The end-of-run SLA report, one line per origin and destination.
+pub fn print_sla_report(deliveries: &HashMap<u32, DeliveryRecord>) {
+    if deliveries.is_empty() {
+        return;
+    }
+    let mut lanes: BTreeMap<(u32, u32), Vec<&DeliveryRecord>> = BTreeMap::new();
+    for record in deliveries.values() {
+        lanes.entry((record.origin, record.destination)).or_default().push(record);
+    }
+
+    let promised = deliveries.values().filter(|record| record.has_window()).count();
+    println!("\n{BOLD}{CYAN}--- Delivery SLA: {} order(s), {} with a delivery window ---{RESET}", deliveries.len(), promised);
+    for ((origin, destination), records) in &lanes {
+        let delivered: Vec<&&DeliveryRecord> = records.iter().filter(|record| record.delivered_at.is_some()).collect();
+        let punctuality: Vec<Punctuality> = delivered.iter().filter(|record| record.has_window()).filter_map(|record| record.punctuality()).collect();
+        let in_window = punctuality.iter().filter(|p| **p == Punctuality::InWindow).count();
+        let early = punctuality.iter().filter(|p| matches!(p, Punctuality::Early(_))).count();
+        let late: Vec<f64> = punctuality.iter().filter_map(|p| if let Punctuality::Late(hours) = p { Some(*hours) } else { None }).collect();
+        let undelivered = records.len() - delivered.len();
+        let colour = if undelivered > 0 { RED } else if !late.is_empty() || early > 0 { YELLOW } else { GREEN };
+
+        print!("  {colour}Station {} -> Station {}: {} order(s), {} delivered", origin, destination, records.len(), delivered.len());
+        if !punctuality.is_empty() {
+            print!(" | {} in window, {} early, {} late ({:.0}% kept)", in_window, early, late.len(), 100.0 * in_window as f64 / punctuality.len() as f64);
+        }
+        if !late.is_empty() {
+            print!(", {:.1}h late on average", late.iter().sum::<f64>() / late.len() as f64);
+        }
+        // How far off our own estimate was, for every delivery we'd planned.
+        let slips: Vec<f64> = delivered.iter().filter_map(|record| Some(record.delivered_at? - record.planned_at?)).collect();
+        if !slips.is_empty() {
+            print!(" | {:+.1}h against plan on average", slips.iter().sum::<f64>() / slips.len() as f64);
+        }
+        if undelivered > 0 {
+            print!(" | {} never arrived", undelivered);
+        }
+        println!("{RESET}");
+    }
+}
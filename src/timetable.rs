@@ -0,0 +1,263 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{sim_hours_now, EngineType, MissionReport, Priority, StationCommand, Switchboard};
+use crate::network::{GlobalLedger, SharedNetwork};
+
+// Trains that run whether or not anybody has ordered one. A timetabled service has a fixed list of calling points,
+// departs its first station at set hours of the day (on set days of the week), always runs behind the same class of
+// engine, and takes at most so many cars. Freight that happens to be going its way can book a place on it instead of
+// getting a train of its own.
+//
+// The Scheduler is the station master's clock: it wakes up every so often, and when a departure is due it tells the
+// origin station to run the service. The stations do the rest. Every run is logged in the ledger so we can see how
+// punctual each service was at the end of the day.
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+
+pub const HOURS_PER_DAY: f64 = 24.0;
+pub const ON_TIME_TOLERANCE_HOURS: f64 = 1.0; // Up to an hour late still counts as on time. This is Sodor, not Switzerland.
+pub const BOOKING_WINDOW_HOURS: f64 = 4.0;    // Freight only waits for a service that leaves its origin within this long
+pub const BOOKING_GRACE_HOURS: f64 = 8.0;     // After the booked departure, how long freight waits for the train before getting one of its own
+pub const HOLD_LIMIT_HOURS: f64 = 6.0;        // How long a run waits at its origin for an engine before it's cancelled
+const STOP_ALLOWANCE_HOURS: f64 = 0.25;      // Time allowed at each intermediate call when working out when a run is due in
+const SCHEDULER_TICK: Duration = Duration::from_millis(100);
+
+pub const CONSOLIDATED_SERVICE: &str = "consolidated"; // The service ID of a train a station made up from several orders of its own
+
+static NEXT_RUN_ID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(1);
+
+/// A fresh run ID, for the Scheduler's runs and for stations making up consolidated trains alike.
+pub fn next_run_id() -> u32 {
+    NEXT_RUN_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+}
+
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+
+    /// The railway opens on a Monday morning, at timetable hour 0.
+    pub fn of_day(day: u64) -> Weekday {
+        Weekday::ALL[(day % 7) as usize]
+    }
+}
+
+fn every_day() -> Vec<Weekday> {
+    Weekday::ALL.to_vec()
+}
+
+
+/// One timetabled service, e.g. { "id": "TB1", "route": [0, 2, 3, 1], "departures": [2.0, 14.0], "engine_class": "Gordon", "max_cars": 4 }.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ScheduledService {
+    pub id: String,
+    pub route: Vec<u32>,        // Calling points, origin first and terminus last
+    pub departures: Vec<f64>,   // Hours of the day it leaves the origin
+    #[serde(default = "every_day")]
+    pub days: Vec<Weekday>,
+    pub engine_class: EngineType,
+    pub max_cars: u32,
+    // How long the run should take, origin to terminus. Leave it out and the Scheduler works it out from the map and the engine's top speed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running_hours: Option<f64>,
+}
+
+impl ScheduledService {
+    pub fn origin(&self) -> Option<u32> {
+        self.route.first().copied()
+    }
+
+    /// Does this service call at `from` and then, later on, at `to`?
+    pub fn serves(&self, from: u32, to: u32) -> bool {
+        match (self.route.iter().position(|&id| id == from), self.route.iter().rposition(|&id| id == to)) {
+            (Some(board), Some(alight)) => board < alight,
+            _ => false,
+        }
+    }
+
+    /// The first departure from the origin at or after timetable hour `after`, looking a week ahead.
+    pub fn next_departure(&self, after: f64) -> Option<f64> {
+        let first_day = (after / HOURS_PER_DAY).floor().max(0.0) as u64;
+        (first_day..first_day + 8)
+            .filter(|day| self.days.contains(&Weekday::of_day(*day)))
+            .flat_map(|day| self.departures.iter().map(move |hour| day as f64 * HOURS_PER_DAY + hour))
+            .filter(|time| *time >= after)
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    /// Departures from the origin in the window (from, to].
+    fn departures_between(&self, from: f64, to: f64) -> Vec<f64> {
+        let first_day = (from / HOURS_PER_DAY).floor().max(0.0) as u64;
+        let last_day = (to / HOURS_PER_DAY).floor().max(0.0) as u64;
+        let mut due: Vec<f64> = (first_day..=last_day)
+            .filter(|day| self.days.contains(&Weekday::of_day(*day)))
+            .flat_map(|day| self.departures.iter().map(move |hour| day as f64 * HOURS_PER_DAY + hour))
+            .filter(|time| *time > from && *time <= to)
+            .collect();
+        due.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        due
+    }
+}
+
+
+/// One run of a service out on the line: where it still has to call. (Whose freight it has aboard is on the train, as riders.)
+/// A consolidated train (several orders from one station, going the same way) runs like this too, as a one-off run of
+/// `CONSOLIDATED_SERVICE` with no timetable record.
+#[derive(Debug)]
+pub struct ScheduledRun {
+    pub run_id: u32,
+    pub service_id: String,
+    pub calls: VecDeque<u32>, // Calling points still ahead, terminus last
+    pub max_cars: u32,
+    pub max_hop: f64,         // The longest and steepest hops anywhere on the service, so we never pick up more than the engine can haul all the way
+    pub max_gradient: f64,
+}
+
+/// A consignment riding on someone else's train: whose it is, where it gets off, and who to tell when it does.
+#[derive(Debug)]
+pub struct Rider {
+    pub mission_id: u32,
+    pub destination: u32,
+    pub cargo_ids: Vec<u32>,
+    pub report_to: Option<Sender<MissionReport>>,
+    pub priority: Priority,
+    pub deadline: Option<f64>,
+}
+
+
+/// The ledger's record of one run of a service.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub service_id: String,
+    pub scheduled_at: f64,          // When it was due to leave the origin
+    pub due_at: f64,                // When it was due in at the terminus
+    pub departed_at: Option<f64>,
+    pub arrived_at: Option<f64>,
+    pub cancelled: bool,            // Never got away, or gave up short of the terminus
+    pub cars_carried: u32,          // Cars picked up over the whole run
+}
+
+impl RunRecord {
+    pub fn is_on_time(&self) -> bool {
+        self.arrived_at.is_some_and(|arrived| arrived <= self.due_at + ON_TIME_TOLERANCE_HOURS)
+    }
+}
+
+
+pub struct Scheduler {
+    pub services: Arc<Vec<ScheduledService>>,
+    pub switchboard: Switchboard,
+    pub ledger: Arc<Mutex<GlobalLedger>>,
+    pub map: SharedNetwork,
+}
+
+impl Scheduler {
+    pub fn new(services: Arc<Vec<ScheduledService>>, switchboard: Switchboard, ledger: Arc<Mutex<GlobalLedger>>, map: SharedNetwork) -> Self {
+        Scheduler { services, switchboard, ledger, map }
+    }
+
+    /// Runs for as long as the railway does. Nobody joins this thread; it stops when the simulation ends.
+    pub fn start(self) -> JoinHandle<()> {
+        thread::spawn(move || {
+            println!("{CYAN}Scheduler: {} timetabled service(s) on the board.{RESET}", self.services.len());
+            let mut last_tick = -f64::EPSILON; // So a departure at hour 0 isn't missed
+            loop {
+                let now = sim_hours_now();
+                for service in self.services.iter() {
+                    for scheduled_at in service.departures_between(last_tick, now) {
+                        self.call_departure(service, scheduled_at);
+                    }
+                }
+                last_tick = now;
+                thread::sleep(SCHEDULER_TICK);
+            }
+        })
+    }
+
+    fn call_departure(&self, service: &ScheduledService, scheduled_at: f64) {
+        let Some(origin) = service.origin() else {
+            return;
+        };
+        let run_id = next_run_id();
+        let due_at = scheduled_at + service.running_hours.unwrap_or_else(|| self.estimate_running_hours(service));
+        self.ledger.lock().unwrap().service_runs.insert(run_id, RunRecord {
+            service_id: service.id.clone(),
+            scheduled_at,
+            due_at,
+            departed_at: None,
+            arrived_at: None,
+            cancelled: false,
+            cars_carried: 0,
+        });
+
+        let origin_tx = self.switchboard.read().unwrap().get(&origin).cloned();
+        match origin_tx {
+            Some(tx) if tx.send(StationCommand::RunService { service_id: service.id.clone(), run_id }).is_ok() => {
+                println!("{BOLD}{CYAN}Scheduler: Service {} (run {}) is due away from Station {} at {:.1}h.{RESET}", service.id, run_id, origin, scheduled_at);
+            },
+            _ => {
+                println!("{RED}Scheduler: Station {} isn't answering. Service {} (run {}) is cancelled.{RESET}", origin, service.id, run_id);
+                if let Some(record) = self.ledger.lock().unwrap().service_runs.get_mut(&run_id) {
+                    record.cancelled = true;
+                }
+            },
+        }
+    }
+
+    // Leg by leg along the map at the engine's top speed, plus a little at each call.
+    fn estimate_running_hours(&self, service: &ScheduledService) -> f64 {
+        let map = self.map.read().unwrap();
+        let speed = service.engine_class.class().speed.max(1) as f64;
+        let distance: f64 = service.route.windows(2)
+            .filter_map(|leg| map.find_shortest_path(leg[0], leg[1]).map(|(distance, _)| distance))
+            .sum();
+        distance / speed + STOP_ALLOWANCE_HOURS * service.route.len().saturating_sub(2) as f64
+    }
+}
+
+
+/// The end-of-run punctuality report, one line per service.
+pub fn print_punctuality_report(services: &[ScheduledService], runs: &HashMap<u32, RunRecord>) {
+    if services.is_empty() {
+        return;
+    }
+    println!("\n{BOLD}{CYAN}--- Timetable Performance ---{RESET}");
+    for service in services {
+        let service_runs: Vec<&RunRecord> = runs.values().filter(|run| run.service_id == service.id).collect();
+        let arrived: Vec<&RunRecord> = service_runs.iter().copied().filter(|run| run.arrived_at.is_some()).collect();
+        let on_time = arrived.iter().filter(|run| run.is_on_time()).count();
+        let cancelled = service_runs.iter().filter(|run| run.cancelled).count();
+        let cars: u32 = service_runs.iter().map(|run| run.cars_carried).sum();
+        let colour = if arrived.is_empty() || on_time < arrived.len() { YELLOW } else { GREEN };
+
+        print!("  {colour}Service {}: {} run(s) called, {} arrived, {} on time, {} cancelled, {} car(s) carried",
+            service.id, service_runs.len(), arrived.len(), on_time, cancelled, cars);
+        if !arrived.is_empty() {
+            let average_delay = arrived.iter().map(|run| (run.arrived_at.unwrap_or(run.due_at) - run.due_at).max(0.0)).sum::<f64>() / arrived.len() as f64;
+            print!(" | {:.0}% on time, {:.1}h late on average", 100.0 * on_time as f64 / arrived.len() as f64, average_delay);
+        }
+        println!("{RESET}");
+    }
+}